@@ -0,0 +1,166 @@
+use std::io::{BufRead, Write};
+
+use gameboy::{Emulation, debugger::{parse_value, Access, Breakpoint, DebugStop, Register, Watchpoint}, error::GameBoyError};
+
+// Blocking commands give the control back after a minute of emulated time
+const MAX_RUN_FRAMES: u64 = 60 * 60;
+
+const HELP: &str = "\
+s, step [N]             execute N instructions
+n, next                 step over a CALL or RST
+c, continue             resume the emulation until a breakpoint or watchpoint
+u, until ADDRESS        run until the PC reaches ADDRESS
+b, break ADDRESS [COND] add a breakpoint, optionally on a condition like A==3F or HL>=C000
+w, watch ADDRESS [r|w|rw] add a memory watchpoint, on writes by default
+d, delete b|w INDEX     remove a breakpoint or watchpoint
+l, list                 list breakpoints and watchpoints
+r, regs                 show the registers
+x ADDRESS [LENGTH]      dump memory
+i [ADDRESS] [COUNT]     disassemble instructions, from the PC by default
+q, quit                 stop the emulation";
+
+pub enum ReplExit {
+    Continue,
+    Quit,
+}
+
+// Reads commands from the terminal until the emulation has to continue
+pub fn repl(emu: &mut Emulation) -> Result<ReplExit, GameBoyError> {
+    print_registers(emu);
+    let stdin = std::io::stdin();
+
+    loop {
+        print!("(gb) ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(ReplExit::Quit);
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, arguments)) = words.split_first() else {
+            continue;
+        };
+
+        match command {
+            "s" | "step" => {
+                let count = arguments.first().and_then(|count| count.parse().ok()).unwrap_or(1);
+                for _ in 0..count {
+                    let stop = emu.step_instruction()?;
+                    if stop != DebugStop::Step {
+                        println!("{}", stop);
+                        break;
+                    }
+                }
+                print_registers(emu);
+            },
+            "n" | "next" => {
+                print_stop(emu.step_over(MAX_RUN_FRAMES)?);
+                print_registers(emu);
+            },
+            "u" | "until" => match arguments.first().map(|address| parse_value(address)) {
+                Some(Ok(address)) => {
+                    print_stop(emu.run_to(address, MAX_RUN_FRAMES)?);
+                    print_registers(emu);
+                },
+                Some(Err(error)) => println!("{}", error),
+                None => println!("Missing address"),
+            },
+            "c" | "continue" => return Ok(ReplExit::Continue),
+            "b" | "break" => match parse_breakpoint(arguments) {
+                Ok(breakpoint) => emu.debugger().add_breakpoint(breakpoint),
+                Err(error) => println!("{}", error),
+            },
+            "w" | "watch" => match parse_watchpoint(arguments) {
+                Ok(watchpoint) => emu.debugger().add_watchpoint(watchpoint),
+                Err(error) => println!("{}", error),
+            },
+            "d" | "delete" => {
+                let index = arguments.get(1).and_then(|index| index.parse().ok());
+                let removed = match (arguments.first(), index) {
+                    (Some(&"b"), Some(index)) => emu.debugger().remove_breakpoint(index).is_some(),
+                    (Some(&"w"), Some(index)) => emu.debugger().remove_watchpoint(index).is_some(),
+                    _ => false,
+                };
+                if !removed {
+                    println!("Nothing to delete, use d b|w INDEX");
+                }
+            },
+            "l" | "list" => {
+                for (index, breakpoint) in emu.debugger().breakpoints().iter().enumerate() {
+                    match breakpoint.condition {
+                        Some(condition) => println!("b {}: {:04X} if {:?} {:?} {:X}", index, breakpoint.address, condition.register, condition.comparison, condition.value),
+                        None => println!("b {}: {:04X}", index, breakpoint.address),
+                    }
+                }
+                for (index, watchpoint) in emu.debugger().watchpoints().iter().enumerate() {
+                    println!("w {}: {:04X} {:?}", index, watchpoint.address, watchpoint.access);
+                }
+            },
+            "r" | "regs" => print_registers(emu),
+            "x" => match arguments.first().map(|address| parse_value(address)) {
+                Some(Ok(address)) => {
+                    let length = arguments.get(1).and_then(|length| parse_value(length).ok()).unwrap_or(0x10);
+                    print_memory(emu, address, length);
+                },
+                Some(Err(error)) => println!("{}", error),
+                None => println!("Missing address"),
+            },
+            "i" => {
+                let address = match arguments.first().map(|address| parse_value(address)) {
+                    Some(Ok(address)) => address,
+                    Some(Err(error)) => {
+                        println!("{}", error);
+                        continue;
+                    },
+                    None => emu.read_register(Register::PC),
+                };
+                let count = arguments.get(1).and_then(|count| count.parse().ok()).unwrap_or(10);
+                for line in emu.disassemble(address, count) {
+                    println!("{}", line);
+                }
+            },
+            "q" | "quit" => return Ok(ReplExit::Quit),
+            "h" | "help" => println!("{}", HELP),
+            _ => println!("Unknown command {}, type h for help", command),
+        }
+    }
+}
+
+fn parse_breakpoint(arguments: &[&str]) -> Result<Breakpoint, String> {
+    let address = parse_value(arguments.first().ok_or("Missing address")?)?;
+    let condition = match arguments.get(1..) {
+        Some(condition) if !condition.is_empty() => Some(condition.concat().parse()?),
+        _ => None,
+    };
+    Ok(Breakpoint { address, condition })
+}
+
+fn parse_watchpoint(arguments: &[&str]) -> Result<Watchpoint, String> {
+    let address = parse_value(arguments.first().ok_or("Missing address")?)?;
+    let access = arguments.get(1).map(|access| access.parse()).transpose()?.unwrap_or(Access::Write);
+    Ok(Watchpoint { address, access })
+}
+
+fn print_stop(stop: DebugStop) {
+    if stop != DebugStop::Step {
+        println!("{}", stop);
+    }
+}
+
+fn print_registers(emu: &Emulation) {
+    let registers = [Register::A, Register::F, Register::B, Register::C, Register::D, Register::E, Register::H, Register::L];
+    let values: Vec<String> = registers.iter().map(|register| format!("{:?}:{:02X}", register, emu.read_register(*register))).collect();
+    println!("{} SP:{:04X}", values.join(" "), emu.read_register(Register::SP));
+    for line in emu.disassemble(emu.read_register(Register::PC), 1) {
+        println!("{}", line);
+    }
+}
+
+fn print_memory(emu: &Emulation, address: u16, length: u16) {
+    for row in (0..length).step_by(16) {
+        let start = address.wrapping_add(row);
+        let bytes: Vec<String> = (0..16.min(length - row)).map(|offset| format!("{:02X}", emu.read_memory(start.wrapping_add(offset)))).collect();
+        println!("{:04X}: {}", start, bytes.join(" "));
+    }
+}
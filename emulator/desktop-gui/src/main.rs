@@ -1,20 +1,81 @@
+mod debugger;
 mod screen;
 
-use std::{io::Error, time::{Duration, Instant}};
+use std::{io::Error, path::{Path, PathBuf}, time::{Duration, Instant}};
 
 use clap::Parser;
-use gameboy::{Emulation, cartridge::Cartridge, SCREEN_WIDTH, SCREEN_HEIGHT, TILEDATA_WIDTH};
-use sdl2::{event::Event, keyboard::Keycode};
+use gameboy::{Emulation, cartridge::Cartridge, debugger::parse_value, error::GameBoyError, trace::{TraceFilter, TraceSink}, link::{printer::GameBoyPrinter, tcp::{TcpLinkCable, DEFAULT_SYNC_WINDOW}}, model::Model, movie::Movie, palette::DMGPalettes, filter::{Filter, ScreenFilter}, rom::BootROM, rewind::REWIND_INTERVAL, SCREEN_WIDTH, SCREEN_HEIGHT, SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT, TILEDATA_WIDTH};
+use sdl2::{event::Event, keyboard::{Keycode, Mod}};
 
 use gameboy::*;
 
-use crate::screen::Screen;
+use crate::{debugger::{repl, ReplExit}, screen::Screen};
 
 const FRAME_TIME: u128 = 1000/60;
 
 #[derive(Parser)]
 struct Cli {
-    cartridge: Option<std::path::PathBuf>
+    cartridge: Option<std::path::PathBuf>,
+    /// Wait for a link cable partner on this port
+    #[arg(long, value_name = "PORT", conflicts_with = "link_connect")]
+    link_listen: Option<u16>,
+    /// Connect the link cable to a partner at HOST:PORT
+    #[arg(long, value_name = "HOST:PORT")]
+    link_connect: Option<String>,
+    /// Milliseconds a transfer waits for the partner before reading 0xFF
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_SYNC_WINDOW.as_millis() as u64)]
+    link_sync_window: u64,
+    /// Plug a Game Boy Printer and save the printed pages as PNG in DIR
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["link_listen", "link_connect"])]
+    printer: Option<std::path::PathBuf>,
+    /// Record the joypad input of every frame into a movie file
+    #[arg(long, value_name = "FILE", conflicts_with = "play")]
+    record: Option<std::path::PathBuf>,
+    /// Replay the input of a recorded movie file
+    #[arg(long, value_name = "FILE")]
+    play: Option<std::path::PathBuf>,
+    /// Stop with an error on illegal opcodes instead of locking up the CPU
+    #[arg(long)]
+    strict: bool,
+    /// Start the cartridge directly with the state the boot ROM leaves
+    #[arg(long)]
+    skip_boot: bool,
+    /// Emulate DMG0, DMG, MGB, SGB, SGB2 or CGB, only the DMG boot ROM is built in
+    #[arg(long, value_name = "MODEL", conflicts_with = "boot_rom")]
+    model: Option<Model>,
+    /// Run a dumped boot ROM, the model is the one it belongs to
+    #[arg(long, value_name = "FILE")]
+    boot_rom: Option<std::path::PathBuf>,
+    /// Colors of DMG games: greyscale, dmg, pocket, light, or 4 colors as
+    /// RRGGBB separated by commas, or 12 for the background, OBJ0 and OBJ1
+    #[arg(long, value_name = "PALETTE")]
+    palette: Option<DMGPalettes>,
+    /// Upscale the screen with nearest, scale2x, scale3x, hq2x or lcd, F10 changes it
+    #[arg(long, value_name = "FILTER", default_value_t = Filter::Nearest)]
+    filter: Filter,
+    /// Blend every frame with the last one like the slow DMG LCD, F12 toggles it
+    #[arg(long)]
+    ghosting: bool,
+    /// Start paused in a terminal debugger, breakpoints and watchpoints return to it
+    #[arg(long)]
+    debug: bool,
+    /// Log the CPU state before every instruction into FILE, in Gameboy Doctor format
+    #[arg(long, value_name = "FILE")]
+    trace: Option<std::path::PathBuf>,
+    /// Only trace instructions with the PC in START-END (hexadecimal)
+    #[arg(long, value_name = "START-END", value_parser = parse_range, requires = "trace")]
+    trace_range: Option<std::ops::RangeInclusive<u16>>,
+    /// Only trace instructions in ROM bank N
+    #[arg(long, value_name = "N", requires = "trace")]
+    trace_bank: Option<usize>,
+    /// Start after the boot ROM with LY stuck at 0x90, as Gameboy Doctor expects
+    #[arg(long)]
+    doctor: bool,
+}
+
+fn parse_range(range: &str) -> Result<std::ops::RangeInclusive<u16>, String> {
+    let (start, end) = range.split_once('-').ok_or("expected START-END")?;
+    Ok(parse_value(start)?..=parse_value(end)?)
 }
 
 fn main() -> Result<(), Error> {
@@ -22,8 +83,8 @@ fn main() -> Result<(), Error> {
     
     let cartridge: Option<Cartridge>;
 
-    if let Some(c) = args.cartridge {
-        cartridge = Some(Cartridge::new(c)?);
+    if let Some(c) = &args.cartridge {
+        cartridge = Some(Cartridge::new(c.clone())?);
         println!("Loading cartridge {} with type {:?}", 
                 cartridge.as_ref().unwrap().title(), 
                 cartridge.as_ref().unwrap().ctype());
@@ -39,6 +100,52 @@ fn main() -> Result<(), Error> {
     // }
 
     let mut emu = Emulation::new(cartridge);
+    if let Some(model) = args.model {
+        emu.set_model(model);
+    }
+    if let Some(path) = &args.boot_rom {
+        emu.set_boot_rom(BootROM::from_file(path)?);
+        println!("Running the {} boot ROM", emu.model());
+    }
+    emu.set_strict(args.strict);
+    if let Some(palette) = args.palette {
+        emu.set_palettes(palette);
+    }
+    if args.skip_boot {
+        emu.skip_boot_rom();
+    }
+    if args.doctor {
+        emu.gameboy_doctor();
+    }
+    if let Some(path) = &args.trace {
+        let filter = TraceFilter { pc_range: args.trace_range.clone(), bank: args.trace_bank };
+        emu.start_trace(TraceSink::file(path)?, filter);
+    }
+
+    // Kept to report the pages that could not be saved
+    let mut printer = None;
+    let sync_window = Duration::from_millis(args.link_sync_window);
+    if let Some(port) = args.link_listen {
+        println!("Waiting for link cable partner on port {}", port);
+        emu.connect_link_cable(Box::new(TcpLinkCable::listen(port, sync_window)?));
+    } else if let Some(address) = args.link_connect {
+        emu.connect_link_cable(Box::new(TcpLinkCable::connect(address.as_str(), sync_window)?));
+        println!("Link cable connected to {}", address);
+    } else if let Some(dir) = args.printer {
+        std::fs::create_dir_all(&dir)?;
+        println!("Printer connected, pages are saved in {}", dir.display());
+        let connected = GameBoyPrinter::with_output_dir(dir);
+        emu.connect_link_cable(Box::new(connected.clone()));
+        printer = Some(connected);
+    }
+
+    if let Some(path) = &args.play {
+        let movie = Movie::load(path)?;
+        println!("Playing movie {} with {} frames", path.display(), movie.frames());
+        emu.play_movie(movie)?;
+    } else if args.record.is_some() {
+        emu.start_recording();
+    }
 
     let sdl_context = sdl2::init().unwrap();
     let mut event_pump = sdl_context.event_pump().unwrap();
@@ -46,7 +153,12 @@ fn main() -> Result<(), Error> {
     // Interaction with hosting machine: screen, keyboard input, ...    
     let video = sdl_context.video().unwrap();
 
-    let mut screen = Screen::new(&video, "Game Boy", SCREEN_WIDTH, SCREEN_HEIGHT, 4, 0);    
+    // The Super Game Boy draws a border around the game
+    let mut screen = match emu.model() {
+        Model::SGB | Model::SGB2 => Screen::new(&video, "Super Game Boy", SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT, 3, 0),
+        _ => Screen::new(&video, "Game Boy", SCREEN_WIDTH, SCREEN_HEIGHT, 4, 0),
+    };
+    let mut screen_filter = ScreenFilter::new(args.filter, args.ghosting);
     let mut tddebug = Screen::new(&video, "Tile data", TILEDATA_WIDTH, TILEDATA_HEIGHT, 2, 500);
     let mut bgdebug = Screen::new(&video, "Background", BACKGROUND_WIDTH, BACKGROUND_HEIGHT, 2, 900);
     
@@ -55,13 +167,38 @@ fn main() -> Result<(), Error> {
 
     emu.start();
 
+    if args.debug {
+        println!("Debugger started, type h for help");
+        if let ReplExit::Quit = repl(&mut emu)? {
+            return Ok(());
+        }
+    }
+
     let mut result_message: String = String::from("");
+    // The game runs backwards while Backspace is held
+    let mut rewinding = false;
 
     'running: loop {
 
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit {..} => break 'running,
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } if save_state_slot(keycode).is_some() => {
+                    let slot = save_state_slot(keycode).unwrap();
+                    let path = save_state_path(args.cartridge.as_deref(), slot);
+                    // F1-F9 save the state in a slot, with Shift they load it
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        match std::fs::read(&path).map_err(GameBoyError::from).and_then(|state| emu.load_state(&state)) {
+                            Ok(_) => println!("Loaded state from slot {}", slot),
+                            Err(error) => println!("Could not load slot {}: {}", slot, error),
+                        }
+                    } else {
+                        match std::fs::write(&path, emu.save_state()) {
+                            Ok(_) => println!("Saved state in slot {}", slot),
+                            Err(error) => println!("Could not save slot {}: {}", slot, error),
+                        }
+                    }
+                },
                 Event::KeyDown { keycode, .. } => {
                     match keycode {
                         Some(Keycode::Escape)   => { 
@@ -76,6 +213,16 @@ fn main() -> Result<(), Error> {
                         Some(Keycode::Down)     => emu.button_pressed(Button::Down),
                         Some(Keycode::Left)     => emu.button_pressed(Button::Left),
                         Some(Keycode::Right)    => emu.button_pressed(Button::Right),
+                        Some(Keycode::Backspace) => rewinding = true,
+                        Some(Keycode::F10)      => {
+                            screen_filter.filter = screen_filter.filter.next();
+                            println!("Filter: {}", screen_filter.filter);
+                        },
+                        Some(Keycode::F11)      => screen.toggle_fullscreen(),
+                        Some(Keycode::F12)      => {
+                            screen_filter.ghosting = !screen_filter.ghosting;
+                            println!("Ghosting: {}", if screen_filter.ghosting { "on" } else { "off" });
+                        },
                         _                       => {},
                     }
                     
@@ -90,6 +237,7 @@ fn main() -> Result<(), Error> {
                         Some(Keycode::Down)     => emu.button_released(Button::Down),
                         Some(Keycode::Left)     => emu.button_released(Button::Left),
                         Some(Keycode::Right)    => emu.button_released(Button::Right),
+                        Some(Keycode::Backspace) => rewinding = false,
                         _                       => {},
                     }
                     
@@ -100,16 +248,34 @@ fn main() -> Result<(), Error> {
 
         if emu.running {
             let now = Instant::now();
-            // Emulation step
-            match emu.step() {
-                Ok(emustep) => {
-                    screen.render(emustep.framebuffer);
-                    tddebug.render(emustep.tiledata);  
-                    bgdebug.render(emustep.background);            
-                },
-                Err(error) => {
-                    result_message = format!("{:?}", error);
-                    break 'running
+            // Emulation step, or one snapshot back while rewinding
+            let emustep = if rewinding { emu.rewind(REWIND_INTERVAL).ok() } else {
+                match emu.step() {
+                    Ok(emustep) => Some(emustep),
+                    Err(error) => {
+                        result_message = format!("{}", error);
+                        break 'running
+                    }
+                }
+            };
+            // Nothing to show when there is no history left to rewind
+            if let Some(emustep) = emustep {
+                for event in &emustep.events {
+                    println!("{}", event);
+                }
+                for error in printer.iter().flat_map(GameBoyPrinter::take_save_errors) {
+                    println!("{}", error);
+                }
+                screen.render_rgba(screen_filter.apply(&emustep.rgba_framebuffer));
+                tddebug.render(emustep.tiledata);  
+                bgdebug.render(emustep.background);            
+
+                let stopped = emustep.events.iter().any(|event| matches!(event, EmulationEvent::Break(_)));
+                if stopped {
+                    if let ReplExit::Quit = repl(&mut emu)? {
+                        result_message = String::from("User terminated emulation.");
+                        break 'running
+                    }
                 }
             }
             let elapsed_processing = now.elapsed();
@@ -126,7 +292,24 @@ fn main() -> Result<(), Error> {
     
     }
 
+    emu.stop_trace()?;
+
+    if let (Some(path), Some(movie)) = (&args.record, emu.stop_recording()) {
+        movie.save(path)?;
+        println!("Recorded {} frames into {}", movie.frames(), path.display());
+    }
+
     println!("Emulation terminated in {} seconds, total executed cycles: {} and {} frames. Reason: {}", execution_time.as_secs_f32() , emu.total_cycles, displayed_frames, result_message );
     
     Ok(())
-}
\ No newline at end of file
+}
+
+fn save_state_slot(keycode: Keycode) -> Option<usize> {
+    let keys = [Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5, Keycode::F6, Keycode::F7, Keycode::F8, Keycode::F9];
+    keys.iter().position(|key| *key == keycode).map(|index| index + 1)
+}
+
+// Slots are saved next to the cartridge as game.ss1 ... game.ss9
+fn save_state_path(cartridge: Option<&Path>, slot: usize) -> PathBuf {
+    cartridge.unwrap_or(Path::new("gameboy")).with_extension(format!("ss{}", slot))
+}
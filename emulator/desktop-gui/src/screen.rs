@@ -1,32 +1,34 @@
-use gameboy::{ColoredPixel, GameBoyFrame};
-use sdl2::{VideoSubsystem, render::Canvas, video::{Window, WindowPos}, pixels::Color, rect::Point};
+use gameboy::{ColoredPixel, GameBoyFrame, RGBAFrame};
+use sdl2::{VideoSubsystem, render::{Canvas, Texture}, video::{FullscreenType, Window, WindowPos}, pixels::{Color, PixelFormatEnum}};
 
 pub struct Screen {
+    // Frames are copied into it and the GPU scales it to the window. Without
+    // texture lifetimes it lives as long as the renderer of the canvas
+    texture: Texture,
     canvas: Canvas<Window>,
-    width: u32,
-    height: u32,
 }
 
-fn color_from_pixel(pixel: ColoredPixel) -> Color {
+fn color_from_pixel(pixel: ColoredPixel) -> [u8; 4] {
     match pixel {
-        ColoredPixel::White => Color::RGB(255, 255, 255),
-        ColoredPixel::LightGray => Color::RGB(192, 192, 192),
-        ColoredPixel::DarkGray => Color::RGB(96, 96, 96),
-        ColoredPixel::Black => Color::RGB(0, 0, 0),
+        ColoredPixel::White => [255, 255, 255, 255],
+        ColoredPixel::LightGray => [192, 192, 192, 255],
+        ColoredPixel::DarkGray => [96, 96, 96, 255],
+        ColoredPixel::Black => [0, 0, 0, 255],
     }
 }
 
 impl Screen {
     pub fn new(
-        video: &VideoSubsystem, 
-        title: &str, 
-        width: u32, 
-        height: u32, 
-        scale: u32, 
+        video: &VideoSubsystem,
+        title: &str,
+        width: u32,
+        height: u32,
+        scale: u32,
         posx_offset: i32
     ) -> Screen {
         let mut window = video.window(title, width * scale, height * scale)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
 
@@ -35,25 +37,55 @@ impl Screen {
 
         let mut canvas = window.into_canvas().build().unwrap();
 
+        // The frame keeps its aspect ratio and is only scaled by whole
+        // factors, the rest of the window stays black
+        canvas.set_logical_size(width, height).unwrap();
+        canvas.set_integer_scale(true).unwrap();
         canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.set_scale(scale as f32, scale as f32).unwrap();
         canvas.clear();
 
-        Screen { canvas, width, height }
+        let texture = Screen::create_texture(&canvas, width, height);
+
+        Screen { texture, canvas }
+    }
+
+    // RGBA32 is the byte order of the frames whatever the endianness
+    fn create_texture(canvas: &Canvas<Window>, width: u32, height: u32) -> Texture {
+        canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+            .unwrap()
     }
 
     pub(crate) fn render(&mut self, frame: GameBoyFrame) {
-        self.canvas.clear();
+        let buffer: Vec<u8> = frame.buffer.iter().flat_map(|pixel| color_from_pixel(*pixel)).collect();
+        self.render_rgba(RGBAFrame { width: frame.width, height: frame.height, buffer });
+    }
 
-        for x in 0..self.width as usize {
-            for y in 0..self.height as usize {
-                let pixel = frame.buffer[x + y * (self.width as usize)];
-                self.canvas.set_draw_color(color_from_pixel(pixel));
-                let point = Point::new(x as i32, y as i32);
-                self.canvas.draw_point(point).unwrap();
-            }
+    // Upscaling filters change the size of the frames, the logical size of
+    // the window stays the same
+    pub(crate) fn render_rgba(&mut self, frame: RGBAFrame) {
+        let query = self.texture.query();
+        if (query.width, query.height) != (frame.width, frame.height) {
+            let texture = Screen::create_texture(&self.canvas, frame.width, frame.height);
+            // The old texture is not used anymore and its renderer is still alive
+            unsafe { std::mem::replace(&mut self.texture, texture).destroy() };
         }
-                
+        self.texture.update(None, &frame.buffer, frame.width as usize * 4).unwrap();
+
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, None).unwrap();
         self.canvas.present();
     }
-}
\ No newline at end of file
+
+    // Borderless fullscreen at the desktop resolution
+    pub(crate) fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(error) = window.set_fullscreen(fullscreen) {
+            println!("Could not toggle fullscreen: {}", error);
+        }
+    }
+}
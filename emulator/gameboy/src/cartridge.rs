@@ -1,10 +1,13 @@
 use std::path::PathBuf;
 
-use super::gameboy::GameBoy;
+use super::{error::GameBoyError, gameboy::GameBoy};
 
 // const HEADER_BEGIN: usize = 0x0100;
 // const HEADER_END: usize = 0x014F;
 
+// Two banks of 16 KiB, the whole 0x0000-0x7FFF area is always mapped
+const MIN_ROM_SIZE: usize = 0x8000;
+
 // const CARTRIDGE_SIZE: usize = HEADER_END+1;
 
 // const ENTRY_START_ADDR: usize = 0x0100;
@@ -18,7 +21,16 @@ use super::gameboy::GameBoy;
 const TITLE_START_ADDR: usize = 0x0134;
 const TITLE_END_ADDR: usize = 0x0143;
 
-//const LICENSEE_ADDR: usize = 0x0144;
+// Last byte of the title on older cartridges
+// https://gbdev.io/pandocs/The_Cartridge_Header.html#0143--cgb-flag
+const CGB_FLAG_ADDR: usize = 0x0143;
+
+const LICENSEE_ADDR: usize = 0x0144;
+
+// https://gbdev.io/pandocs/The_Cartridge_Header.html#0146--sgb-flag
+const SGB_FLAG_ADDR: usize = 0x0146;
+// SGB functions also need 0x33 here, which points to the new licensee code
+const OLD_LICENSEE_ADDR: usize = 0x014B;
 
 const CTYPE_ADDR: usize = 0x0147;
 
@@ -70,10 +82,13 @@ pub enum CartridgeType {
 }
 
 impl Cartridge {
-    pub fn new(file: PathBuf) -> Result<Cartridge, std::io::Error> {
+    pub fn new(file: PathBuf) -> Result<Cartridge, GameBoyError> {
         let data = std::fs::read(file)?;       
-        let title = parse_title(&data);
-        let ctype = CartridgeType::from(data[CTYPE_ADDR]);
+        if data.len() < MIN_ROM_SIZE {
+            return Err(GameBoyError::InvalidCartridge(format!("{} bytes is too small", data.len())));
+        }
+        let title = parse_title(&data)?;
+        let ctype = CartridgeType::try_from(data[CTYPE_ADDR])?;
 
         Ok(Cartridge { data, title, ctype })
     }   
@@ -86,6 +101,41 @@ impl Cartridge {
         self.ctype.clone()
     }
 
+    // Enhanced for the CGB or only working on it
+    pub fn supports_cgb(&self) -> bool {
+        self.data[CGB_FLAG_ADDR] & 0x80 != 0
+    }
+
+    // Sends commands to the Super Game Boy
+    pub fn supports_sgb(&self) -> bool {
+        self.data[SGB_FLAG_ADDR] == 0x03 && self.data[OLD_LICENSEE_ADDR] == 0x33
+    }
+
+    // Sum of the 16 title bytes of the games licensed by Nintendo, the CGB
+    // boot ROM uses it to pick a palette for DMG games
+    // https://gbdev.io/pandocs/Power_Up_Sequence.html#compatibility-palettes
+    pub(crate) fn nintendo_title_checksum(&self) -> Option<u8> {
+        let licensee = self.data[OLD_LICENSEE_ADDR];
+        let nintendo = licensee == 0x01 || (licensee == 0x33 && self.data[LICENSEE_ADDR..LICENSEE_ADDR + 2] == *b"01");
+        let title = &self.data[TITLE_START_ADDR..=TITLE_END_ADDR];
+        nintendo.then(|| title.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte)))
+    }
+
+    // CRC-32 of the whole ROM, identifies the game in save states and movies
+    pub fn checksum(&self) -> u32 {
+        crc32(&self.data)
+    }
+
+    // ROM bank mapped at the address, without memory bank controllers bank 1
+    // is always the one at 0x4000
+    pub(crate) fn rom_bank(address: u16) -> Option<usize> {
+        match address {
+            0x0000..=0x3FFF => Some(0),
+            0x4000..=0x7FFF => Some(1),
+            _ => None,
+        }
+    }
+
     pub(crate) fn read_byte(gb: &GameBoy, address: u16) -> u8 {
         if let Some(cartridge) = &gb.cartridge {
             cartridge.data[address as usize]
@@ -96,19 +146,49 @@ impl Cartridge {
     }
 }
 
-fn parse_title(buffer: &Vec<u8>) -> String {
+// ROMs of the gb-test-roms submodule in assets
+#[cfg(test)]
+impl Cartridge {
+    pub(crate) fn test_rom(rom: &str) -> Cartridge {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets/gb-test-roms").join(rom);
+        Cartridge::new(path).unwrap()
+    }
+
+    pub(crate) fn cpu_instrs(rom: &str) -> Cartridge {
+        Cartridge::test_rom(&format!("cpu_instrs/individual/{}", rom))
+    }
+
+    pub(crate) fn halt_bug() -> Cartridge {
+        Cartridge::test_rom("halt_bug.gb")
+    }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+fn parse_title(buffer: &[u8]) -> Result<String, GameBoyError> {
     let start = TITLE_START_ADDR;
     let end = TITLE_END_ADDR;
 
-    std::str::from_utf8(&buffer[start..end])
-                        .expect("invalid utf-8 sequence")
-                        .trim_matches(char::from(0))
-                        .to_string()              
+    let title = std::str::from_utf8(&buffer[start..end])
+                        .map_err(|_| GameBoyError::InvalidCartridge(String::from("title is not valid UTF-8")))?;
+    Ok(title.trim_matches(char::from(0)).to_string())
 }
 
-impl std::convert::From<u8> for CartridgeType {
-    fn from(byte: u8) -> Self {
-        match byte {
+impl std::convert::TryFrom<u8> for CartridgeType {
+    type Error = GameBoyError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        let ctype = match byte {
             0x00 => CartridgeType::ROM(ROMVersion::Empty),
             0x01 => CartridgeType::MBC1(MBCExtras::Empty),
             0x02 => CartridgeType::MBC1(MBCExtras::Ram),
@@ -137,7 +217,8 @@ impl std::convert::From<u8> for CartridgeType {
             0xFD => CartridgeType::Tama5,
             0xFE => CartridgeType::HuC3,
             0xFF => CartridgeType::HuC1,
-            _ => panic!("Invalid CartridgeType")
-        }
+            _ => return Err(GameBoyError::UnsupportedMapper(byte))
+        };
+        Ok(ctype)
     }
 }
\ No newline at end of file
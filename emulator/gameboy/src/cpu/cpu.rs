@@ -1,10 +1,12 @@
-use std::io::Error;
-
+use crate::debugger::Debugger;
+use crate::error::GameBoyError;
 use crate::gameboy::GameBoy;
 use crate::io::interrupts::Interrupts;
-use crate::io::io::{SERIAL_CONTROL_ADDRESS, SERIAL_DATA_ADDRESS, SerialTransferMode};
-use crate::io::timers::Timers;
+use crate::io::hdma::HDMA;
+use crate::io::joypad::Joypad;
 use crate::mmu::{MMU, Address};
+use crate::savestate::{StateReader, StateWriter};
+use crate::trace::Tracer;
 
 use super::instructions::decode::Instruction;
 use super::registers::Registers;
@@ -18,7 +20,15 @@ pub(crate) struct CPU{
     pub(crate) sp: StackPointer,
     pub(crate) pc: ProgramCounter,
     pub(crate) is_halted: bool,
+    pub(crate) is_stopped: bool,
     pub(crate) ime: bool,
+    // EI enables interrupts only after the next instruction
+    pub(crate) ime_scheduled: bool,
+    pub(crate) halt_bug: bool,
+    // Locked up after an illegal opcode until the next power on
+    pub(crate) is_locked: bool,
+    // Clock cycles elapsed during the current step
+    pub(crate) cycles: ClockCycles,
 }
 
 impl CPU {
@@ -28,83 +38,210 @@ impl CPU {
             sp: 0x0, 
             pc: 0x0,  
             is_halted: false,
-            ime: true,
+            is_stopped: false,
+            ime: false,
+            ime_scheduled: false,
+            halt_bug: false,
+            is_locked: false,
+            cycles: 0,
         }
     }
 
-    pub(crate) fn step(gb: &mut GameBoy) -> Result<ClockCycles, Error> {
-        let mut mcycles = MachineCycles::One;
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.regs.get_af());
+        state.write_u16(self.regs.get_bc());
+        state.write_u16(self.regs.get_de());
+        state.write_u16(self.regs.get_hl());
+        state.write_u16(self.sp);
+        state.write_u16(self.pc);
+        state.write_bool(self.is_halted);
+        state.write_bool(self.is_stopped);
+        state.write_bool(self.ime);
+        state.write_bool(self.ime_scheduled);
+        state.write_bool(self.halt_bug);
+        state.write_bool(self.is_locked);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.regs.set_af(state.read_u16()?);
+        self.regs.set_bc(state.read_u16()?);
+        self.regs.set_de(state.read_u16()?);
+        self.regs.set_hl(state.read_u16()?);
+        self.sp = state.read_u16()?;
+        self.pc = state.read_u16()?;
+        self.is_halted = state.read_bool()?;
+        self.is_stopped = state.read_bool()?;
+        self.ime = state.read_bool()?;
+        self.ime_scheduled = state.read_bool()?;
+        self.halt_bug = state.read_bool()?;
+        self.is_locked = state.read_bool()?;
+        Ok(())
+    }
+
+    // Registers as the boot ROM of the model leaves them when jumping to the cartridge
+    // https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers
+    pub(crate) fn post_boot(gb: &mut GameBoy) {
+        let [af, bc, de, hl] = gb.model.post_boot_registers(gb.cartridge.as_ref());
+        gb.cpu.regs.set_af(af);
+        gb.cpu.regs.set_bc(bc);
+        gb.cpu.regs.set_de(de);
+        gb.cpu.regs.set_hl(hl);
+        gb.cpu.sp = 0xFFFE;
+        gb.cpu.pc = 0x0100;
+    }
+
+    pub(crate) fn step(gb: &mut GameBoy) -> Result<ClockCycles, GameBoyError> {
+        gb.cpu.cycles = 0;
+
+        if gb.cpu.is_locked {
+            // The rest of the system keeps running
+            CPU::internal_cycle(gb);
+            return Ok(gb.cpu.cycles);
+        }
+
+        if gb.cpu.is_stopped {
+            // Low power mode, only a joypad line going low wakes the CPU up
+            if Joypad::some_line_low(gb) {
+                gb.cpu.is_stopped = false;
+            }else{
+                CPU::internal_cycle(gb);
+                return Ok(gb.cpu.cycles);
+            }
+        }
+
+        // The CPU waits while a VRAM DMA copies a block, even when halted
+        if HDMA::is_pending(gb) {
+            HDMA::transfer_block(gb);
+            return Ok(gb.cpu.cycles);
+        }
 
         CPU::handle_interrupts(gb);
         
-        if !gb.cpu.is_halted {
+        if gb.cpu.is_halted {
+            CPU::internal_cycle(gb);
+        } else if Debugger::should_break(gb) {
+            // The instruction runs when the debugger resumes
+        } else {
+            Tracer::trace(gb)?;
+            let ime_scheduled = gb.cpu.ime_scheduled;
+            let start = gb.cpu.cycles;
             let instruction = CPU::fetch_decode(gb)?;
-            // if gb.cpu.pc > 0x100 {
-            //     println!("{} {:?}", gb, instruction);
-            // }
-            //println!("{:?}", gb.io.joypad);
-            mcycles = instruction.execute(gb)?;           
-        }
+            let cycles = ClockCycles::from(instruction.execute(gb)?);
 
-        Timers::tick(gb, u8::from(mcycles.clone()));        
+            // Memory accesses already advanced the system, the remaining
+            // cycles of the instruction are internal ones
+            debug_assert!(gb.cpu.cycles - start <= cycles);
+            while gb.cpu.cycles - start < cycles {
+                CPU::internal_cycle(gb);
+            }
 
-        Ok(ClockCycles::from(mcycles))
+            // The instruction after EI has been executed, unless it was a DI
+            if ime_scheduled && gb.cpu.ime_scheduled {
+                gb.cpu.ime = true;
+                gb.cpu.ime_scheduled = false;
+            }
+        }
+
+        Ok(gb.cpu.cycles)
     }   
 
-    pub(super) fn fetch_decode(gb: &GameBoy) -> Result<Instruction, Error> {
-        let instruction_byte = MMU::read_byte(gb, gb.cpu.pc);
-        let byte0 = MMU::read_byte(gb, gb.cpu.pc+1);
+    pub(super) fn fetch_decode(gb: &mut GameBoy) -> Result<Instruction, GameBoyError> {
+        let pc = gb.cpu.pc;
+        let instruction_byte = CPU::read_cycle(gb, gb.cpu.pc);
 
-        let prefixed = instruction_byte == 0xCB;
-        let mut instruction_byte = instruction_byte;
-        if prefixed {
-            instruction_byte = byte0;
+        if gb.cpu.halt_bug {
+            // The PC fails to increment after the opcode fetch, so the same
+            // byte is read again as the next one
+            gb.cpu.halt_bug = false;
+            gb.cpu.pc = gb.cpu.pc.wrapping_sub(1);
         }
 
-        if prefixed {
-            Instruction::from_byte_prefixed(instruction_byte)
+        if instruction_byte == 0xCB {
+            let instruction_byte = CPU::read_cycle(gb, gb.cpu.pc.wrapping_add(1));
+            Ok(Instruction::from_byte_prefixed(instruction_byte))
         } else {
             Instruction::from_byte_not_prefixed(instruction_byte)
+                .ok_or(GameBoyError::IllegalOpcode { pc, opcode: instruction_byte })
         }      
     }
 
-    pub(crate) fn send_serial(gb: &mut GameBoy) -> Option<u8> {
-        let serial_transfer = SerialTransferMode::parse_from_byte(MMU::read_byte(&gb, SERIAL_CONTROL_ADDRESS));
-        let serial_data = MMU::read_byte(&gb, SERIAL_DATA_ADDRESS);
-        
-        match serial_transfer {
-            SerialTransferMode::TransferInternalClock => Some(serial_data),
-            SerialTransferMode::TransferExternalClock => Some(serial_data),
-            _ => None
-        }
+    // Every memory access takes a machine cycle, the rest of the system is
+    // advanced before the access so it sees the state at that moment. Only
+    // the accesses of the program are seen by the watchpoints
+    pub(crate) fn read_cycle(gb: &mut GameBoy, address: Address) -> u8 {
+        gb.cycle();
+        let value = MMU::read_byte(gb, address);
+        Debugger::watch_read(gb, address, value);
+        value
+    }
+
+    pub(crate) fn write_cycle(gb: &mut GameBoy, address: Address, value: u8) {
+        gb.cycle();
+        Debugger::watch_write(gb, address, value);
+        MMU::write_byte(gb, address, value);
     }
 
+    pub(crate) fn internal_cycle(gb: &mut GameBoy) {
+        gb.cycle();
+    }
+
+    // Immediate operands are the bytes after the opcode
+    pub(crate) fn read_next_byte(gb: &mut GameBoy) -> u8 {
+        CPU::read_cycle(gb, gb.cpu.pc.wrapping_add(1))
+    }
+
+    pub(crate) fn read_next_word(gb: &mut GameBoy) -> u16 {
+        let lsb = CPU::read_cycle(gb, gb.cpu.pc.wrapping_add(1)) as u16;
+        let msb = CPU::read_cycle(gb, gb.cpu.pc.wrapping_add(2)) as u16;
+        (msb << 8) | lsb
+    }
+
+    // https://gbdev.io/pandocs/Interrupts.html#interrupt-handling
     pub(crate) fn handle_interrupts(gb: &mut GameBoy) {
-        if Interrupts::some_interrupt_enabled(gb) {
-            if gb.cpu.ime {
-                let interrupt = Interrupts::interrupt_to_handle(gb).unwrap();
-                gb.cpu.is_halted = false;
-                gb.cpu.ime = false;
-                CPU::push_stack(gb, gb.cpu.pc);
-                gb.cpu.pc = interrupt.handler();
-            }else{
-                gb.cpu.is_halted = false;
-            }
+        if !Interrupts::some_interrupt_enabled(gb) {
+            return;
+        }
+
+        // A pending interrupt wakes up the CPU even if it is not serviced
+        gb.cpu.is_halted = false;
+
+        if !gb.cpu.ime {
+            return;
         }
+
+        gb.cpu.ime = false;
+
+        // Two wait states before pushing the PC
+        CPU::internal_cycle(gb);
+        CPU::internal_cycle(gb);
+
+        // The interrupt is chosen after pushing the PC upper byte, if that write
+        // lands on IE and disables it the dispatch is cancelled and jumps to 0x0000
+        gb.cpu.sp = gb.cpu.sp.wrapping_sub(1);
+        CPU::write_cycle(gb, gb.cpu.sp, ((gb.cpu.pc & 0xFF00) >> 8) as u8);
+        let interrupt = Interrupts::interrupt_to_handle(gb);
+        gb.cpu.sp = gb.cpu.sp.wrapping_sub(1);
+        CPU::write_cycle(gb, gb.cpu.sp, (gb.cpu.pc & 0xFF) as u8);
+
+        gb.cpu.pc = match interrupt {
+            Some(interrupt) => interrupt.handler(),
+            None => 0x0000
+        };
+        CPU::internal_cycle(gb);
     }
 
     pub(crate) fn push_stack(gb: &mut GameBoy, value: u16) {
         gb.cpu.sp = gb.cpu.sp.wrapping_sub(1);
-        MMU::write_byte(gb, gb.cpu.sp, ((value & 0xFF00) >> 8) as u8);
+        CPU::write_cycle(gb, gb.cpu.sp, ((value & 0xFF00) >> 8) as u8);
         gb.cpu.sp = gb.cpu.sp.wrapping_sub(1);
-        MMU::write_byte(gb, gb.cpu.sp, (value & 0xFF) as u8);
+        CPU::write_cycle(gb, gb.cpu.sp, (value & 0xFF) as u8);
     }
     
     pub(crate) fn pop_stack(gb: &mut GameBoy) -> u16 {
-        let lsb = MMU::read_byte(&gb, gb.cpu.sp) as u16;
+        let lsb = CPU::read_cycle(gb, gb.cpu.sp) as u16;
         gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
     
-        let msb = MMU::read_byte(&gb, gb.cpu.sp) as u16;
+        let msb = CPU::read_cycle(gb, gb.cpu.sp) as u16;
         gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
     
         (msb << 8) | lsb
@@ -1,12 +1,12 @@
-use std::io::Error;
+use crate::error::GameBoyError;
 
-use crate::{cpu::cpu::MachineCycles, gameboy::GameBoy, mmu::MMU};
+use crate::{cpu::cpu::{MachineCycles, CPU}, gameboy::GameBoy};
 
 use super::decode::{RegistersIndDir, WordRegister, RegistersIndirect, BitType, RotateDirection, BitTarget, ResSetType, Instruction};
 
 impl Instruction {
 
-    pub(super) fn add(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn add(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         let (new_value, did_overflow) = gb.cpu.regs.a.overflowing_add(value);
@@ -26,9 +26,9 @@ impl Instruction {
         }
     }
 
-    pub(super) fn addsps8(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
+    pub(super) fn addsps8(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
         // To preserve the sign
-        let value = MMU::read_next_byte(gb, gb.cpu.pc) as i8 as i16 as u16;
+        let value = CPU::read_next_byte(gb) as i8 as i16 as u16;
 
         let new_value = gb.cpu.sp.wrapping_add(value);
         gb.cpu.regs.flags.zero = false;
@@ -40,7 +40,7 @@ impl Instruction {
         Ok(MachineCycles::Four)
     }
 
-    pub(super) fn add16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, Error> {
+    pub(super) fn add16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, GameBoyError> {
         let value = match target {
             WordRegister::BC => gb.cpu.regs.get_bc(),
             WordRegister::DE => gb.cpu.regs.get_de(),
@@ -58,7 +58,7 @@ impl Instruction {
         Ok(MachineCycles::Two)
     }
 
-    pub(super) fn adc(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn adc(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         let (new_value1, did_overflow1) = gb.cpu.regs.a.overflowing_add(value);
@@ -78,7 +78,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn sub(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn sub(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         let (new_value, did_overflow) = gb.cpu.regs.a.overflowing_sub(value);
@@ -97,7 +97,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn sbc(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn sbc(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
 
         let value = get_arithmetic_target_val(gb, &target);
 
@@ -122,7 +122,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn and(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn and(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         gb.cpu.regs.a = gb.cpu.regs.a & value;
@@ -139,7 +139,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn xor(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn xor(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         gb.cpu.regs.a = gb.cpu.regs.a ^ value;
@@ -156,7 +156,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn or(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn or(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         gb.cpu.regs.a = gb.cpu.regs.a | value;
@@ -173,7 +173,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn cp(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, Error> {
+    pub(super) fn cp(&self, gb: &mut GameBoy, target: RegistersIndDir) -> Result<MachineCycles, GameBoyError> {
         let value = get_arithmetic_target_val(gb, &target);
 
         let (result, did_overflow) = gb.cpu.regs.a.overflowing_sub(value);
@@ -191,7 +191,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn inc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
+    pub(super) fn inc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.regs.flags.subtract = false;
 
         match target {
@@ -231,11 +231,11 @@ impl Instruction {
                 gb.cpu.regs.l = gb.cpu.regs.l.wrapping_add(1);
             },
             RegistersIndirect::HLI => {
-                let old_val = MMU::read_byte(gb, gb.cpu.regs.get_hl());
+                let old_val = CPU::read_cycle(gb, gb.cpu.regs.get_hl());
                 gb.cpu.regs.flags.half_carry = (old_val & 0xF).wrapping_add(0b1 & 0xF) > 0xF;
                 let new_val = old_val.wrapping_add(1);
                 gb.cpu.regs.flags.zero = new_val == 0;
-                MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_val);
+                CPU::write_cycle(gb, gb.cpu.regs.get_hl(), new_val);
             }
         };
   
@@ -247,7 +247,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn dec(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
+    pub(super) fn dec(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.regs.flags.subtract = true;
 
         match target {
@@ -287,11 +287,11 @@ impl Instruction {
                 gb.cpu.regs.l = gb.cpu.regs.l.wrapping_sub(1);
             },
             RegistersIndirect::HLI => {
-                let old_val = MMU::read_byte(gb, gb.cpu.regs.get_hl());
+                let old_val = CPU::read_cycle(gb, gb.cpu.regs.get_hl());
                 gb.cpu.regs.flags.half_carry = (old_val & 0xF).wrapping_sub(0b1 & 0xF) > 0xF;
                 let new_val = old_val.wrapping_sub(1);
                 gb.cpu.regs.flags.zero = new_val == 0;
-                MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_val);
+                CPU::write_cycle(gb, gb.cpu.regs.get_hl(), new_val);
             }
         };
   
@@ -303,7 +303,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn inc16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, Error> {
+    pub(super) fn inc16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, GameBoyError> {
         match target {
             WordRegister::BC => gb.cpu.regs.set_bc(gb.cpu.regs.get_bc().wrapping_add(1)),
             WordRegister::DE => gb.cpu.regs.set_de(gb.cpu.regs.get_de().wrapping_add(1)),
@@ -314,7 +314,7 @@ impl Instruction {
         Ok(MachineCycles::Two)
     }
 
-    pub(super) fn dec16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, Error> {
+    pub(super) fn dec16(&self, gb: &mut GameBoy, target: WordRegister) -> Result<MachineCycles, GameBoyError> {
         match target {
             WordRegister::BC => gb.cpu.regs.set_bc(gb.cpu.regs.get_bc().wrapping_sub(1)),
             WordRegister::DE => gb.cpu.regs.set_de(gb.cpu.regs.get_de().wrapping_sub(1)),
@@ -325,13 +325,13 @@ impl Instruction {
         Ok(MachineCycles::Two)
     }
 
-    pub(super) fn bit(&self, gb: &mut GameBoy, bit_type: BitType) -> Result<MachineCycles, Error> {
+    pub(super) fn bit(&self, gb: &mut GameBoy, bit_type: BitType) -> Result<MachineCycles, GameBoyError> {
         let BitType::Registers(t, s) = bit_type;
         let target = t;
         let source = s;
 
         let i = get_position_by_bittarget(target);
-        let value = get_register_indirect_val(gb, source.clone());
+        let value = get_register_indirect_val(gb, &source);
         let bit_value = get_bit_val(i, value);
 
         gb.cpu.regs.flags.zero = !bit_value;
@@ -347,38 +347,39 @@ impl Instruction {
 
     // RLA, RRA, ... are legacy instructions made for compatibility with 8080
     // No zero flag is set
-    pub(super) fn rla(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &RegistersIndirect::A, RotateDirection::Left, false);
+    pub(super) fn rla(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.regs.a = bitwise_rotate(gb, gb.cpu.regs.a, RotateDirection::Left, false);
         gb.cpu.regs.flags.zero = false;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
 
-    pub(super) fn rlca(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &RegistersIndirect::A, RotateDirection::Left, true);
+    pub(super) fn rlca(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.regs.a = bitwise_rotate(gb, gb.cpu.regs.a, RotateDirection::Left, true);
         gb.cpu.regs.flags.zero = false;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
 
-    pub(super) fn rra(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &RegistersIndirect::A, RotateDirection::Right, false);
+    pub(super) fn rra(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.regs.a = bitwise_rotate(gb, gb.cpu.regs.a, RotateDirection::Right, false);
         gb.cpu.regs.flags.zero = false;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
 
-    pub(super) fn rrca(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &RegistersIndirect::A, RotateDirection::Right, true);
+    pub(super) fn rrca(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.regs.a = bitwise_rotate(gb, gb.cpu.regs.a, RotateDirection::Right, true);
         gb.cpu.regs.flags.zero = false;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
 
-    pub(super) fn sla(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Left, true);
-        res_set(gb, ResSetType::Registers(BitTarget::Zero, target.clone()), false);
-        set_flag_zero(gb, &target);
+    pub(super) fn sla(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = set_bit_val(0, false, bitwise_rotate(gb, value, RotateDirection::Left, true));
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         
         match target {
@@ -387,13 +388,13 @@ impl Instruction {
         }
     }
 
-    pub(super) fn sra(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        let value = get_register_indirect_val(gb, target.clone());
+    pub(super) fn sra(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
         let bit7 = get_bit_val(7, value);
 
-        bitwise_rotate(gb, &target, RotateDirection::Right, true);
-        res_set(gb, ResSetType::Registers(BitTarget::Seven, target.clone()), bit7);
-        set_flag_zero(gb, &target);
+        let new_value = set_bit_val(7, bit7, bitwise_rotate(gb, value, RotateDirection::Right, true));
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
 
         match target {
@@ -402,10 +403,11 @@ impl Instruction {
         }
     }
 
-    pub(super) fn srl(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Right, true);
-        res_set(gb, ResSetType::Registers(BitTarget::Seven, target.clone()), false);
-        set_flag_zero(gb, &target);
+    pub(super) fn srl(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = set_bit_val(7, false, bitwise_rotate(gb, value, RotateDirection::Right, true));
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         
         match target {
@@ -414,9 +416,11 @@ impl Instruction {
         }
     }
 
-    pub(super) fn rr(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Right, false);
-        set_flag_zero(gb, &target);
+    pub(super) fn rr(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = bitwise_rotate(gb, value, RotateDirection::Right, false);
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         
         match target {
@@ -425,9 +429,11 @@ impl Instruction {
         }
     }
 
-    pub(super) fn rrc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Right, true);
-        set_flag_zero(gb, &target);
+    pub(super) fn rrc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = bitwise_rotate(gb, value, RotateDirection::Right, true);
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
 
         match target {
@@ -436,9 +442,11 @@ impl Instruction {
         }
     }
 
-    pub(super) fn rl(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Left, false);
-        set_flag_zero(gb, &target);
+    pub(super) fn rl(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = bitwise_rotate(gb, value, RotateDirection::Left, false);
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
 
         match target {
@@ -447,9 +455,11 @@ impl Instruction {
         }
     }
 
-    pub(super) fn rlc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        bitwise_rotate(gb, &target, RotateDirection::Left, true);
-        set_flag_zero(gb, &target);
+    pub(super) fn rlc(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
+        let new_value = bitwise_rotate(gb, value, RotateDirection::Left, true);
+        set_register_indirect_val(gb, &target, new_value);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
 
         match target {
@@ -458,36 +468,28 @@ impl Instruction {
         }
     }
 
-    pub(super) fn swap(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, Error> {
-        let value = get_register_indirect_val(gb, target.clone());
+    pub(super) fn swap(&self, gb: &mut GameBoy, target: RegistersIndirect) -> Result<MachineCycles, GameBoyError> {
+        let value = get_register_indirect_val(gb, &target);
 
         let low = value & 0x0F;
         let high = value & 0xF0;
 
         let new_value = (low << 4).wrapping_add(high >> 4);
-
-        match target {
-            RegistersIndirect::A => { gb.cpu.regs.a = new_value; },
-            RegistersIndirect::B => { gb.cpu.regs.b = new_value; },
-            RegistersIndirect::C => { gb.cpu.regs.c = new_value; },
-            RegistersIndirect::D => { gb.cpu.regs.d = new_value; },
-            RegistersIndirect::E => { gb.cpu.regs.e = new_value; },
-            RegistersIndirect::H => { gb.cpu.regs.h = new_value; },
-            RegistersIndirect::L => { gb.cpu.regs.l = new_value; },
-            RegistersIndirect::HLI => {
-                MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_value);
-            }
-        };
+        set_register_indirect_val(gb, &target, new_value);
 
         gb.cpu.regs.flags.subtract = false;
         gb.cpu.regs.flags.half_carry = false;
         gb.cpu.regs.flags.carry = false;
-        set_flag_zero(gb, &target);
+        gb.cpu.regs.flags.zero = new_value == 0;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
-        Ok(MachineCycles::Two)
+
+        match target {
+            RegistersIndirect::HLI => Ok(MachineCycles::Four),
+            _ => Ok(MachineCycles::Two),
+        }
     }
 
-    pub(super) fn res(&self, gb: &mut GameBoy, target: ResSetType) -> Result<MachineCycles, Error> {
+    pub(super) fn res(&self, gb: &mut GameBoy, target: ResSetType) -> Result<MachineCycles, GameBoyError> {
 
         let ResSetType::Registers(_, register) = target.clone();
 
@@ -501,7 +503,7 @@ impl Instruction {
         }
     }
 
-    pub(super) fn set(&self, gb: &mut GameBoy, target: ResSetType) -> Result<MachineCycles, Error> {
+    pub(super) fn set(&self, gb: &mut GameBoy, target: ResSetType) -> Result<MachineCycles, GameBoyError> {
 
         let ResSetType::Registers(_, register) = target.clone();
 
@@ -518,13 +520,13 @@ impl Instruction {
 
 // RR r and RL r instructions
 // If is_rc is true we consider the RLC and RRC instructions, otherwise the RL and RR
-fn bitwise_rotate(gb: &mut GameBoy, target: &RegistersIndirect, direction: RotateDirection, is_rc: bool) {
+fn bitwise_rotate(gb: &mut GameBoy, value: u8, direction: RotateDirection, is_rc: bool) -> u8 {
     gb.cpu.regs.flags.subtract = false;
     gb.cpu.regs.flags.half_carry = false;
 
     match direction {
-        RotateDirection::Left => shift_left_register(gb, &target, is_rc),
-        RotateDirection::Right => shift_right_register(gb, &target, is_rc),
+        RotateDirection::Left => shift_left(gb, value, is_rc),
+        RotateDirection::Right => shift_right(gb, value, is_rc),
     }
 }
 
@@ -533,22 +535,12 @@ pub(super) fn res_set(gb: &mut GameBoy, target: ResSetType, value: bool) {
     
     let i = get_position_by_bittarget(bt);
 
-    match register {
-        RegistersIndirect::A => gb.cpu.regs.a = set_bit_val(i, value, gb.cpu.regs.a),
-        RegistersIndirect::B => gb.cpu.regs.b = set_bit_val(i, value, gb.cpu.regs.b),
-        RegistersIndirect::C => gb.cpu.regs.c = set_bit_val(i, value, gb.cpu.regs.c),
-        RegistersIndirect::D => gb.cpu.regs.d = set_bit_val(i, value, gb.cpu.regs.d),
-        RegistersIndirect::E => gb.cpu.regs.e = set_bit_val(i, value, gb.cpu.regs.e),
-        RegistersIndirect::H   => gb.cpu.regs.h = set_bit_val(i, value, gb.cpu.regs.h),
-        RegistersIndirect::L   => gb.cpu.regs.l = set_bit_val(i, value, gb.cpu.regs.l),
-        RegistersIndirect::HLI => {
-            let new_value = set_bit_val(i, value, MMU::read_byte(gb, gb.cpu.regs.get_hl()));
-            MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_value);
-        }
-    };
+    let new_value = set_bit_val(i, value, get_register_indirect_val(gb, &register));
+    set_register_indirect_val(gb, &register, new_value);
 }
 
-fn get_register_indirect_val(gb: &GameBoy, source: RegistersIndirect) -> u8 {
+// (HL) is accessed through the CPU, so each call takes a machine cycle
+fn get_register_indirect_val(gb: &mut GameBoy, source: &RegistersIndirect) -> u8 {
     match source {
         RegistersIndirect::A => gb.cpu.regs.a,
         RegistersIndirect::B => gb.cpu.regs.b,
@@ -557,11 +549,24 @@ fn get_register_indirect_val(gb: &GameBoy, source: RegistersIndirect) -> u8 {
         RegistersIndirect::E => gb.cpu.regs.e,
         RegistersIndirect::H => gb.cpu.regs.h,
         RegistersIndirect::L => gb.cpu.regs.l,
-        RegistersIndirect::HLI => MMU::read_byte(gb, gb.cpu.regs.get_hl()),
+        RegistersIndirect::HLI => CPU::read_cycle(gb, gb.cpu.regs.get_hl()),
+    }
+}
+
+fn set_register_indirect_val(gb: &mut GameBoy, target: &RegistersIndirect, value: u8) {
+    match target {
+        RegistersIndirect::A => gb.cpu.regs.a = value,
+        RegistersIndirect::B => gb.cpu.regs.b = value,
+        RegistersIndirect::C => gb.cpu.regs.c = value,
+        RegistersIndirect::D => gb.cpu.regs.d = value,
+        RegistersIndirect::E => gb.cpu.regs.e = value,
+        RegistersIndirect::H => gb.cpu.regs.h = value,
+        RegistersIndirect::L => gb.cpu.regs.l = value,
+        RegistersIndirect::HLI => CPU::write_cycle(gb, gb.cpu.regs.get_hl(), value),
     }
 }
 
-fn get_arithmetic_target_val(gb: &GameBoy, target: &RegistersIndDir) -> u8 {
+fn get_arithmetic_target_val(gb: &mut GameBoy, target: &RegistersIndDir) -> u8 {
     match target {
         RegistersIndDir::A     => gb.cpu.regs.a,
         RegistersIndDir::B     => gb.cpu.regs.b,
@@ -570,103 +575,35 @@ fn get_arithmetic_target_val(gb: &GameBoy, target: &RegistersIndDir) -> u8 {
         RegistersIndDir::E     => gb.cpu.regs.e,
         RegistersIndDir::H     => gb.cpu.regs.h,
         RegistersIndDir::L     => gb.cpu.regs.l,
-        RegistersIndDir::HLI   => MMU::read_byte(gb, gb.cpu.regs.get_hl()),
-        RegistersIndDir::D8    => MMU::read_next_byte(gb, gb.cpu.pc)
+        RegistersIndDir::HLI   => CPU::read_cycle(gb, gb.cpu.regs.get_hl()),
+        RegistersIndDir::D8    => CPU::read_next_byte(gb)
     }
 }
 
-fn shift_left_register(gb: &mut GameBoy, target: &RegistersIndirect, is_rlc: bool) {
-    let new_bit0;
-    let prev_bit7;
+fn shift_left(gb: &mut GameBoy, value: u8, is_rlc: bool) -> u8 {
+    let prev_bit7 = get_bit_val(7, value);
 
-    match target {
-        RegistersIndirect::A => prev_bit7 = get_bit_val(7,gb.cpu.regs.a),
-        RegistersIndirect::B => prev_bit7 = get_bit_val(7,gb.cpu.regs.b),
-        RegistersIndirect::C => prev_bit7 = get_bit_val(7,gb.cpu.regs.c),
-        RegistersIndirect::D => prev_bit7 = get_bit_val(7,gb.cpu.regs.d),
-        RegistersIndirect::E => prev_bit7 = get_bit_val(7,gb.cpu.regs.e),
-        RegistersIndirect::H   => prev_bit7 = get_bit_val(7,gb.cpu.regs.h),
-        RegistersIndirect::L   => prev_bit7 = get_bit_val(7,gb.cpu.regs.l),
-        RegistersIndirect::HLI => { 
-            let hl_value = MMU::read_byte(gb, gb.cpu.regs.get_hl());
-            prev_bit7 = get_bit_val(7,hl_value);
-        }
-    };
-
-    if is_rlc {
-        new_bit0 = prev_bit7;
-        gb.cpu.regs.flags.carry = prev_bit7;
+    let new_bit0 = if is_rlc {
+        prev_bit7
     }else{
-        new_bit0 = gb.cpu.regs.flags.carry;
-        gb.cpu.regs.flags.carry = prev_bit7;
-    }
-
-    match target {
-        RegistersIndirect::A => { gb.cpu.regs.a = (gb.cpu.regs.a << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::B => { gb.cpu.regs.b = (gb.cpu.regs.b << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::C => { gb.cpu.regs.c = (gb.cpu.regs.c << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::D => { gb.cpu.regs.d = (gb.cpu.regs.d << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::E => { gb.cpu.regs.e = (gb.cpu.regs.e << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::H => { gb.cpu.regs.h = (gb.cpu.regs.h << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::L => { gb.cpu.regs.l = (gb.cpu.regs.l << 1).wrapping_add(new_bit0 as u8); },
-        RegistersIndirect::HLI => {
-            let new_val = (MMU::read_byte(gb, gb.cpu.regs.get_hl()) << 1).wrapping_add(new_bit0 as u8);
-            MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_val);
-        }
+        gb.cpu.regs.flags.carry
     };
+    gb.cpu.regs.flags.carry = prev_bit7;
 
+    (value << 1).wrapping_add(new_bit0 as u8)
 }
 
-fn shift_right_register(gb: &mut GameBoy, target: &RegistersIndirect, is_rrc: bool) {
-    let new_bit7;
-    let prev_bit0;
+fn shift_right(gb: &mut GameBoy, value: u8, is_rrc: bool) -> u8 {
+    let prev_bit0 = get_bit_val(0, value);
 
-    match target {
-        RegistersIndirect::A => prev_bit0 = get_bit_val(0,gb.cpu.regs.a),
-        RegistersIndirect::B => prev_bit0 = get_bit_val(0,gb.cpu.regs.b),
-        RegistersIndirect::C => prev_bit0 = get_bit_val(0,gb.cpu.regs.c),
-        RegistersIndirect::D => prev_bit0 = get_bit_val(0,gb.cpu.regs.d),
-        RegistersIndirect::E => prev_bit0 = get_bit_val(0,gb.cpu.regs.e),
-        RegistersIndirect::H   => prev_bit0 = get_bit_val(0,gb.cpu.regs.h),
-        RegistersIndirect::L   => prev_bit0 = get_bit_val(0,gb.cpu.regs.l),
-        RegistersIndirect::HLI => prev_bit0 = get_bit_val(0,MMU::read_byte(gb, gb.cpu.regs.get_hl()))
-    };
-
-    if is_rrc {
-        new_bit7 = prev_bit0;
-        gb.cpu.regs.flags.carry = prev_bit0;
+    let new_bit7 = if is_rrc {
+        prev_bit0
     }else{
-        new_bit7 = gb.cpu.regs.flags.carry;
-        gb.cpu.regs.flags.carry = prev_bit0;
-    }
-    
-    match target {
-        RegistersIndirect::A => { gb.cpu.regs.a = (gb.cpu.regs.a >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::B => { gb.cpu.regs.b = (gb.cpu.regs.b >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::C => { gb.cpu.regs.c = (gb.cpu.regs.c >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::D => { gb.cpu.regs.d = (gb.cpu.regs.d >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::E => { gb.cpu.regs.e = (gb.cpu.regs.e >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::H => { gb.cpu.regs.h = (gb.cpu.regs.h >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::L => { gb.cpu.regs.l = (gb.cpu.regs.l >> 1).wrapping_add((new_bit7 as u8) << 7); },
-        RegistersIndirect::HLI => {
-            let new_val = (MMU::read_byte(gb, gb.cpu.regs.get_hl()) >> 1).wrapping_add((new_bit7 as u8) << 7);
-            MMU::write_byte(gb, gb.cpu.regs.get_hl(), new_val);
-        }
+        gb.cpu.regs.flags.carry
     };
+    gb.cpu.regs.flags.carry = prev_bit0;
 
-}
-
-fn set_flag_zero(gb: &mut GameBoy, target: &RegistersIndirect) {
-    match target {
-        RegistersIndirect::A => { gb.cpu.regs.flags.zero = gb.cpu.regs.a == 0; },
-        RegistersIndirect::B => { gb.cpu.regs.flags.zero = gb.cpu.regs.b == 0; },
-        RegistersIndirect::C => { gb.cpu.regs.flags.zero = gb.cpu.regs.c == 0; },
-        RegistersIndirect::D => { gb.cpu.regs.flags.zero = gb.cpu.regs.d == 0; },
-        RegistersIndirect::E => { gb.cpu.regs.flags.zero = gb.cpu.regs.e == 0; },
-        RegistersIndirect::H => { gb.cpu.regs.flags.zero = gb.cpu.regs.h == 0; },
-        RegistersIndirect::L => { gb.cpu.regs.flags.zero = gb.cpu.regs.l == 0; },
-        RegistersIndirect::HLI => { gb.cpu.regs.flags.zero = MMU::read_byte(gb, gb.cpu.regs.get_hl()) == 0; }
-    };
+    (value >> 1).wrapping_add((new_bit7 as u8) << 7)
 }
 
 fn get_position_by_bittarget(target:BitTarget) -> usize {
@@ -1,12 +1,12 @@
-use std::io::Error;
+use crate::{error::GameBoyError, EmulationEvent};
 
-use crate::{cpu::cpu::{MachineCycles, CPU}, mmu::MMU, gameboy::GameBoy, io::interrupts::Interrupts};
+use crate::{cpu::cpu::{MachineCycles, CPU}, gameboy::GameBoy, io::{interrupts::Interrupts, io::IO, timers::Timers}};
 
 use super::decode::*;
 
 impl Instruction {
 
-    pub(crate) fn execute(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
+    pub(crate) fn execute(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
         match self.clone() {
             Instruction::CALL(test)             => self.call(gb, test),
             Instruction::RET(test)              => self.ret(gb, test),
@@ -59,19 +59,18 @@ impl Instruction {
             Instruction::DI                               => self.di(gb),
             Instruction::RES(target)          => self.res(gb, target),
             Instruction::SET(target)          => self.set(gb, target),
-            Instruction::STOP                             => self.nop(gb),
+            Instruction::STOP                             => self.stop(gb),
+            Instruction::ILLEGAL(opcode)              => self.illegal(gb, opcode),
         }
     }    
 
-    fn jump(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, Error> {
+    fn jump(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, GameBoyError> {
+        // The address is read even if the jump is not taken
+        let address = CPU::read_next_word(gb);
         let should_jump = should_jump(gb, test);
      
         if should_jump {
-            // Gameboy is little endian so read pc + 2 as most significant bit
-            // and pc + 1 as least significant bit
-            let least_significant_byte = MMU::read_byte(gb, gb.cpu.pc + 1) as u16;
-            let most_significant_byte = MMU::read_byte(gb, gb.cpu.pc + 2) as u16;
-            gb.cpu.pc = (most_significant_byte << 8) | least_significant_byte;
+            gb.cpu.pc = address;
     
             Ok(MachineCycles::Four)
         } else {
@@ -80,11 +79,11 @@ impl Instruction {
         }
     }
     
-    fn jump_relative(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, Error> {
+    fn jump_relative(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, GameBoyError> {
+        let offset: i8 = CPU::read_next_byte(gb) as i8;
         let should_jump = should_jump(gb, test);
      
         if should_jump {
-            let offset: i8 = MMU::read_next_byte(gb, gb.cpu.pc) as i8;
             gb.cpu.pc = gb.cpu.pc.wrapping_add(2i8.wrapping_add(offset) as u16);
     
             Ok(MachineCycles::Three)
@@ -94,29 +93,46 @@ impl Instruction {
         }
     }
     
-    fn jump_hl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn jump_hl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.pc = gb.cpu.regs.get_hl();
         Ok(MachineCycles::One)
     }   
         
-    fn nop(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
+    fn nop(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
     
-    fn ei(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
-        gb.cpu.ime = true;
+    // https://gbdev.io/pandocs/Reducing_Power_Consumption.html#using-the-stop-instruction
+    fn stop(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
+        // STOP is followed by a byte that is ignored
+        gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
+
+        if IO::speed_switch_armed(gb) {
+            // On CGB, STOP with KEY1 armed switches the CPU speed instead of stopping
+            IO::switch_speed(gb);
+        }else{
+            gb.cpu.is_stopped = true;
+        }
+
+        Timers::reset_div(gb);
+        Ok(MachineCycles::One)
+    }
+
+    fn ei(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.ime_scheduled = true;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
     
-    fn di(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn di(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.ime = false;
+        gb.cpu.ime_scheduled = false;
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::One)
     }
     
-    fn scf(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn scf(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.regs.flags.carry = true;
         gb.cpu.regs.flags.subtract = false;
         gb.cpu.regs.flags.half_carry = false;
@@ -124,7 +140,7 @@ impl Instruction {
         Ok(MachineCycles::One)
     }
     
-    fn cpl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn cpl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.regs.a = !gb.cpu.regs.a; 
         gb.cpu.regs.flags.subtract = true;
         gb.cpu.regs.flags.half_carry = true;
@@ -132,7 +148,7 @@ impl Instruction {
         Ok(MachineCycles::One)
     }
     
-    fn ccf(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn ccf(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.regs.flags.carry = !gb.cpu.regs.flags.carry;
         gb.cpu.regs.flags.subtract = false;
         gb.cpu.regs.flags.half_carry = false;
@@ -141,7 +157,7 @@ impl Instruction {
     }
     
     // https://forums.nesdev.org/viewtopic.php?t=15944
-    fn daa(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn daa(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         if !gb.cpu.regs.flags.subtract {  // after an addition, adjust if (half-)carry occurred or if result is out of bounds
             if gb.cpu.regs.flags.carry || gb.cpu.regs.a > 0x99 { 
                 gb.cpu.regs.a = gb.cpu.regs.a.wrapping_add(0x60);
@@ -165,12 +181,13 @@ impl Instruction {
         Ok(MachineCycles::One)
     }
     
-    fn call(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, Error> {
+    fn call(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, GameBoyError> {
+        let jump_addr = CPU::read_next_word(gb);
         let should_jump = should_jump(gb, test);
 
         if should_jump {
-            let jump_addr = MMU::read_next_word(gb, gb.cpu.pc);
             gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
+            CPU::internal_cycle(gb);
             CPU::push_stack(gb, gb.cpu.pc);
             gb.cpu.pc = jump_addr;
             Ok(MachineCycles::Six)
@@ -180,7 +197,14 @@ impl Instruction {
         }
     }
     
-    fn ret(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, Error> {
+    fn ret(&self, gb: &mut GameBoy , test: JumpTest) -> Result<MachineCycles, GameBoyError> {
+        if let JumpTest::Always = test {
+            gb.cpu.pc = CPU::pop_stack(gb);
+            return Ok(MachineCycles::Four);
+        }
+
+        // Checking the condition takes an extra cycle
+        CPU::internal_cycle(gb);
         let jump_condition = should_jump(gb, test);
         if jump_condition {
             gb.cpu.pc = CPU::pop_stack(gb, );
@@ -191,13 +215,14 @@ impl Instruction {
         }
     }
     
-    fn reti(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn reti(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.pc = CPU::pop_stack(gb, );
         gb.cpu.ime = true;
         Ok(MachineCycles::Four)
     }
     
-    fn rst(&self, gb: &mut GameBoy , target: BitTarget) -> Result<MachineCycles, Error> {
+    fn rst(&self, gb: &mut GameBoy , target: BitTarget) -> Result<MachineCycles, GameBoyError> {
+        CPU::internal_cycle(gb);
         CPU::push_stack(gb, gb.cpu.pc.wrapping_add(u16::from(self.size())));
     
         let address: u16 = match target {
@@ -215,8 +240,8 @@ impl Instruction {
         Ok(MachineCycles::Four)
     }
     
-    fn ldhlspd8(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
-        let value = MMU::read_next_byte(gb, gb.cpu.pc) as i8 as u16;
+    fn ldhlspd8(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
+        let value = CPU::read_next_byte(gb) as i8 as u16;
         let new_value = gb.cpu.sp.wrapping_add(value);
     
         gb.cpu.regs.flags.zero = false;
@@ -229,25 +254,25 @@ impl Instruction {
         Ok(MachineCycles::Three)        
     }
     
-    fn ldsphl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
+    fn ldsphl(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
         gb.cpu.sp = gb.cpu.regs.get_hl();
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::Two) 
     }
     
-    fn ldspa16(&self, gb: &mut GameBoy) -> Result<MachineCycles, Error> {
-        let address = MMU::read_next_word(gb, gb.cpu.pc);
+    fn ldspa16(&self, gb: &mut GameBoy) -> Result<MachineCycles, GameBoyError> {
+        let address = CPU::read_next_word(gb);
     
         let lsb = (gb.cpu.sp & 0x00FF) as u8;
         let msb = ((gb.cpu.sp & 0xFF00) >> 8) as u8;
 
-        MMU::write_byte(gb, address, lsb);
-        MMU::write_byte(gb, address.wrapping_add(1), msb);
+        CPU::write_cycle(gb, address, lsb);
+        CPU::write_cycle(gb, address.wrapping_add(1), msb);
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::Five) 
     }
     
-    fn load(&self, gb: &mut GameBoy , load_type: LoadType) -> Result<MachineCycles, Error> {
+    fn load(&self, gb: &mut GameBoy , load_type: LoadType) -> Result<MachineCycles, GameBoyError> {
          
         match &load_type {
             LoadType::Byte(target, source) => {
@@ -259,8 +284,8 @@ impl Instruction {
                     RegistersIndDir::E   => gb.cpu.regs.e,
                     RegistersIndDir::H   => gb.cpu.regs.h,
                     RegistersIndDir::L   => gb.cpu.regs.l,
-                    RegistersIndDir::D8  => MMU::read_next_byte(gb, gb.cpu.pc),
-                    RegistersIndDir::HLI => MMU::read_byte(gb, gb.cpu.regs.get_hl())
+                    RegistersIndDir::D8  => CPU::read_next_byte(gb),
+                    RegistersIndDir::HLI => CPU::read_cycle(gb, gb.cpu.regs.get_hl())
                 };
                 match target {
                     RegistersIndirect::A   => gb.cpu.regs.a = source_value,
@@ -270,45 +295,38 @@ impl Instruction {
                     RegistersIndirect::E   => gb.cpu.regs.e = source_value,
                     RegistersIndirect::H   => gb.cpu.regs.h = source_value,
                     RegistersIndirect::L   => gb.cpu.regs.l = source_value,
-                    RegistersIndirect::HLI => MMU::write_byte(gb, gb.cpu.regs.get_hl(), source_value)
+                    RegistersIndirect::HLI => CPU::write_cycle(gb, gb.cpu.regs.get_hl(), source_value)
                 };
     
             },
             LoadType::Word(target) => {
+                let value = CPU::read_next_word(gb);
                 match target {
-                    WordRegister::BC => {
-                        gb.cpu.regs.set_bc(MMU::read_next_word(gb, gb.cpu.pc));
-                    },
-                    WordRegister::DE => {
-                        gb.cpu.regs.set_de(MMU::read_next_word(gb, gb.cpu.pc));
-                    },
-                    WordRegister::HL => {
-                        gb.cpu.regs.set_hl(MMU::read_next_word(gb, gb.cpu.pc));
-                    },
-                    WordRegister::SP => {
-                        gb.cpu.sp = MMU::read_next_word(gb, gb.cpu.pc);
-                    }
+                    WordRegister::BC => gb.cpu.regs.set_bc(value),
+                    WordRegister::DE => gb.cpu.regs.set_de(value),
+                    WordRegister::HL => gb.cpu.regs.set_hl(value),
+                    WordRegister::SP => gb.cpu.sp = value,
                 }
             },
             LoadType::AFromIndirect(target) => {
                 match target {
                     LoadIndirectSource::BC => {
                         let addr = gb.cpu.regs.get_bc();
-                        gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                        gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                     },
                     LoadIndirectSource::DE => {
                         let addr = gb.cpu.regs.get_de();
-                        gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                        gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                     },
                     LoadIndirectSource::HLInc => {
                         let addr = gb.cpu.regs.get_hl();
-                        gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                        gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                         let new_value = gb.cpu.regs.get_hl().wrapping_add(1);
                         gb.cpu.regs.set_hl(new_value);
                     },
                     LoadIndirectSource::HLDec => {
                         let addr = gb.cpu.regs.get_hl();
-                        gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                        gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                         let new_value = gb.cpu.regs.get_hl().wrapping_sub(1);
                         gb.cpu.regs.set_hl(new_value);
                     }
@@ -318,32 +336,33 @@ impl Instruction {
                 match target {
                     LoadIndirectSource::BC => {
                         let addr = gb.cpu.regs.get_bc();
-                        MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                        CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                     },
                     LoadIndirectSource::DE => {
                         let addr = gb.cpu.regs.get_de();
-                        MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                        CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                     },
                     LoadIndirectSource::HLInc => {
                         let addr = gb.cpu.regs.get_hl();
-                        MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                        CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                         let new_value = gb.cpu.regs.get_hl().wrapping_add(1);
                         gb.cpu.regs.set_hl(new_value);
                     },
                     LoadIndirectSource::HLDec => {
                         let addr = gb.cpu.regs.get_hl();
-                        MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                        CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                         let new_value = gb.cpu.regs.get_hl().wrapping_sub(1);
                         gb.cpu.regs.set_hl(new_value);
                     }
                 }
             },
             LoadType::AFromDirect => {
-                let address = MMU::read_next_word(gb, gb.cpu.pc);
-                gb.cpu.regs.a = MMU::read_byte(gb, address);
+                let address = CPU::read_next_word(gb);
+                gb.cpu.regs.a = CPU::read_cycle(gb, address);
             },
             LoadType::DirectFromA => {
-                MMU::write_byte(gb, MMU::read_next_word(gb, gb.cpu.pc), gb.cpu.regs.a);
+                let address = CPU::read_next_word(gb);
+                CPU::write_cycle(gb, address, gb.cpu.regs.a);
             }
         }
 
@@ -351,6 +370,8 @@ impl Instruction {
     
         // Result
         match load_type {
+            LoadType::Byte(RegistersIndirect::HLI, RegistersIndDir::D8) => Ok(MachineCycles::Three),
+            LoadType::Byte(_, RegistersIndDir::D8) => Ok(MachineCycles::Two),
             LoadType::Byte(_,RegistersIndDir::HLI) => Ok(MachineCycles::Two) ,
             LoadType::Byte(RegistersIndirect::HLI, _) => Ok(MachineCycles::Two) ,
             LoadType::AFromIndirect(_) => Ok(MachineCycles::Two) ,
@@ -362,48 +383,49 @@ impl Instruction {
         }
     }
     
-    fn ldff(&self, gb: &mut GameBoy , load_type: LoadFFType) -> Result<MachineCycles, Error> {
+    fn ldff(&self, gb: &mut GameBoy , load_type: LoadFFType) -> Result<MachineCycles, GameBoyError> {
         match load_type {
             LoadFFType::AtoFFC => { 
                 let addr: u16 = 0xFF00 + gb.cpu.regs.c as u16;       
-                MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                 gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
                 Ok(MachineCycles::Two) 
             },
             LoadFFType::FFCtoA => {
                 let addr: u16 = 0xFF00 + gb.cpu.regs.c as u16;        
-                gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                 gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
                 Ok(MachineCycles::Two) 
             },
             LoadFFType::AtoFFa8 => {
-                let addr: u16 = 0xFF00 + MMU::read_next_byte(gb, gb.cpu.pc) as u16;        
-                MMU::write_byte(gb, addr, gb.cpu.regs.a);
+                let addr: u16 = 0xFF00 + CPU::read_next_byte(gb) as u16;        
+                CPU::write_cycle(gb, addr, gb.cpu.regs.a);
                 gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
                 Ok(MachineCycles::Three)
             },
             LoadFFType::FFa8toA => {
-                let addr: u16 = 0xFF00 + MMU::read_next_byte(gb, gb.cpu.pc) as u16;        
-                gb.cpu.regs.a = MMU::read_byte(gb, addr);
+                let addr: u16 = 0xFF00 + CPU::read_next_byte(gb) as u16;        
+                gb.cpu.regs.a = CPU::read_cycle(gb, addr);
                 gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
                 Ok(MachineCycles::Three)
             }
         }
     }
     
-    pub(crate) fn push(&self, gb: &mut GameBoy , target: StackTarget) -> Result<MachineCycles, Error> {
+    pub(crate) fn push(&self, gb: &mut GameBoy , target: StackTarget) -> Result<MachineCycles, GameBoyError> {
         let value = match target {
             StackTarget::BC => gb.cpu.regs.get_bc(),
             StackTarget::DE => gb.cpu.regs.get_de(),
             StackTarget::HL => gb.cpu.regs.get_hl(),
             StackTarget::AF => gb.cpu.regs.get_af(),
         };
+        CPU::internal_cycle(gb);
         CPU::push_stack(gb, value);
         gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         Ok(MachineCycles::Four)
     }
     
-    pub(crate) fn pop(&self, gb: &mut GameBoy , target: StackTarget) -> Result<MachineCycles, Error> {
+    pub(crate) fn pop(&self, gb: &mut GameBoy , target: StackTarget) -> Result<MachineCycles, GameBoyError> {
         let result = CPU::pop_stack(gb, );
         match target {
             StackTarget::BC => gb.cpu.regs.set_bc(result),
@@ -415,17 +437,26 @@ impl Instruction {
         Ok(MachineCycles::Three)
     }
     
-    fn halt(&self, gb: &mut GameBoy ) -> Result<MachineCycles, Error> {
-        if Interrupts::some_interrupt_enabled(gb) {
-            if !gb.cpu.ime {
-                // Halt bug, no PC increment
-            }else{
-                // We ignore the halting
-                gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
-            }
+    fn illegal(&self, gb: &mut GameBoy, opcode: u8) -> Result<MachineCycles, GameBoyError> {
+        if gb.strict {
+            return Err(GameBoyError::IllegalOpcode { pc: gb.cpu.pc, opcode });
+        }
+
+        // The CPU hangs without advancing the PC, not even interrupts wake it up
+        gb.cpu.is_locked = true;
+        gb.events.push(EmulationEvent::Locked { pc: gb.cpu.pc, opcode });
+        Ok(MachineCycles::One)
+    }
+
+    fn halt(&self, gb: &mut GameBoy ) -> Result<MachineCycles, GameBoyError> {
+        gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
+
+        if !gb.cpu.ime && Interrupts::some_interrupt_enabled(gb) {
+            // Halt bug, the CPU does not halt and the next byte is read twice
+            gb.cpu.halt_bug = true;
         }else{
+            // With IME set and a pending interrupt, it is serviced on the next step
             gb.cpu.is_halted = true;
-            gb.cpu.pc = gb.cpu.pc.wrapping_add(u16::from(self.size()));
         }
         
         Ok(MachineCycles::One)
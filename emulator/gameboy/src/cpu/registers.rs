@@ -33,7 +33,7 @@ impl Registers {
                 }
     }
 
-    pub(super) fn get_bc(&self) -> u16 {
+    pub(crate) fn get_bc(&self) -> u16 {
         (self.b as u16) << 8 | self.c as u16
     }
     
@@ -42,7 +42,7 @@ impl Registers {
         self.c = (value & 0xFF) as u8;
     }
 
-    pub(super) fn get_de(&self) -> u16 {
+    pub(crate) fn get_de(&self) -> u16 {
         (self.d as u16) << 8 | self.e as u16
     }
 
@@ -51,7 +51,7 @@ impl Registers {
         self.e = (value & 0xFF) as u8;
     }
 
-    pub(super) fn get_hl(&self) -> u16 {
+    pub(crate) fn get_hl(&self) -> u16 {
         (self.h as u16) << 8 | self.l as u16
     }
 
@@ -60,7 +60,7 @@ impl Registers {
         self.l = (value & 0xFF) as u8;
     }
 
-    pub(super) fn get_af(&self) -> u16 {
+    pub(crate) fn get_af(&self) -> u16 {
         (self.a as u16) << 8 | u8::from(self.flags.clone()) as u16
     }
 
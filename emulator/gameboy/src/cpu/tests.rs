@@ -1,8 +1,5 @@
 #[cfg(test)]
-use std::path::PathBuf;
-
-#[cfg(test)]
-use crate::{cartridge::Cartridge, cpu::instructions::decode::{Instruction, RegistersIndDir, StackTarget, RegistersIndirect}, gameboy::GameBoy, mmu::MMU};
+use crate::{Button, EmulationEvent, cartridge::Cartridge, io::joypad::Joypad, cpu::{cpu::CPU, instructions::decode::{Instruction, RegistersIndDir, StackTarget, RegistersIndirect}}, error::GameBoyError, gameboy::GameBoy, mmu::MMU, runner::{Runner, StopCondition, StopReason}};
 
 #[test]
 fn add_without_carry() {
@@ -367,98 +364,317 @@ fn srl() {
 
 #[cfg(test)]
 fn assert_serial_result(cartridge: Cartridge) {
-    let mut gb: GameBoy = GameBoy::new(Some(cartridge));
-    let mut serial = Vec::<char>::new();
-    loop {
-        match gb.tick() {
-            Ok(_) => {
-                if let Some(data) = gb.read_serial() {   
-                    serial.push(data as char);
-                    let result_str = serial.iter().cloned().collect::<String>();
-                    if result_str.contains("Passed") {
-                        println!("{result_str}");
-                        assert!(true);
-                        break
-                    }else if result_str.contains("Failed") {
-                        println!("{result_str}");
-                        assert!(false);
-                        break
-                    }       
-                }
-            },
-            _ => assert!(false)
-        }        
-    }
+    let result = Runner::new(cartridge, 60 * 60)
+        .stop_when(StopCondition::SerialContains(String::from("Passed")))
+        .stop_when(StopCondition::SerialContains(String::from("Failed")))
+        .run()
+        .unwrap();
+
+    println!("{}", String::from_utf8_lossy(&result.serial));
+    assert_eq!(result.reason, StopReason::Matched(StopCondition::SerialContains(String::from("Passed"))));
 }
 
 #[test]
 fn cpu_instrs_01() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/01-special.gb")).unwrap();    
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("01-special.gb"));
 }
 
 #[test]
 fn cpu_instrs_02() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/02-interrupts.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("02-interrupts.gb"));
 }
 
 #[test]
 fn cpu_instrs_03() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/03-op sp,hl.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("03-op sp,hl.gb"));
 }
 
 #[test]
 fn cpu_instrs_04() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/04-op r,imm.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("04-op r,imm.gb"));
 }
 
 #[test]
 fn cpu_instrs_05() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/05-op rp.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("05-op rp.gb"));
 }
 
 #[test]
 fn cpu_instrs_06() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/06-ld r,r.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("06-ld r,r.gb"));
 }
 
 #[test]
 fn cpu_instrs_07() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/07-jr,jp,call,ret,rst.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("07-jr,jp,call,ret,rst.gb"));
 }
 
 #[test]
 fn cpu_instrs_08() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/08-misc instrs.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("08-misc instrs.gb"));
 }
 
 #[test]
 fn cpu_instrs_09() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/09-op r,r.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("09-op r,r.gb"));
 }
 
 #[test]
 fn cpu_instrs_10() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/10-bit ops.gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("10-bit ops.gb"));
 }
 
 #[test]
 fn cpu_instrs_11() {
-    let cartridge = Cartridge::new(PathBuf::from("assets/gb-test-roms/cpu_instrs/individual/11-op a,(hl).gb")).unwrap();
-    assert_serial_result(cartridge);
+    assert_serial_result(Cartridge::cpu_instrs("11-op a,(hl).gb"));
+}
+
+#[cfg(test)]
+fn load_program(gb: &mut GameBoy, address: u16, program: &[u8]) {
+    for (offset, byte) in program.iter().enumerate() {
+        MMU::write_byte(gb, address + offset as u16, *byte);
+    }
+    gb.cpu.pc = address;
+}
+
+#[test]
+fn halt_bug() {
+    assert_serial_result(Cartridge::halt_bug());
+}
+
+#[test]
+fn halt_bug_repeats_the_next_byte() {
+    let mut gb = GameBoy::new(None);
+    gb.cpu.sp = 0xDFFF;
+
+    // HALT, INC A, NOP
+    load_program(&mut gb, 0xC000, &[0x76, 0x3C, 0x00]);
+
+    // IME is disabled and a timer interrupt is pending
+    gb.cpu.ime = false;
+    MMU::write_byte(&mut gb, 0xFFFF, 0b00000100);
+    MMU::write_byte(&mut gb, 0xFF0F, 0b00000100);
+
+    CPU::step(&mut gb).unwrap();
+    assert!(!gb.cpu.is_halted);
+    assert_eq!(gb.cpu.pc, 0xC001);
+
+    // INC A is executed twice because the PC is not incremented the first time
+    CPU::step(&mut gb).unwrap();
+    assert_eq!(gb.cpu.regs.a, 1);
+    assert_eq!(gb.cpu.pc, 0xC001);
+
+    CPU::step(&mut gb).unwrap();
+    assert_eq!(gb.cpu.regs.a, 2);
+    assert_eq!(gb.cpu.pc, 0xC002);
 }
 
-// #[test]
-// fn halt_bug() {
-//     let cartridge = Cartridge::halt_bug();
-//     assert_serial_result(cartridge);
-// }
\ No newline at end of file
+#[test]
+fn ei_delay() {
+    let mut gb = GameBoy::new(None);
+    gb.cpu.sp = 0xDFFF;
+
+    // EI, NOP, NOP
+    load_program(&mut gb, 0xC000, &[0xFB, 0x00, 0x00]);
+
+    MMU::write_byte(&mut gb, 0xFFFF, 0b00000001);
+    MMU::write_byte(&mut gb, 0xFF0F, 0b00000001);
+
+    CPU::step(&mut gb).unwrap();
+    assert!(!gb.cpu.ime);
+
+    // The instruction after EI runs before any interrupt is serviced
+    CPU::step(&mut gb).unwrap();
+    assert!(gb.cpu.ime);
+    assert_eq!(gb.cpu.pc, 0xC002);
+
+    gb.cpu.cycles = 0;
+    CPU::handle_interrupts(&mut gb);
+    assert_eq!(gb.cpu.cycles, 20);
+    assert_eq!(gb.cpu.pc, 0x0040);
+    assert_eq!(CPU::pop_stack(&mut gb), 0xC002);
+}
+
+#[test]
+fn ei_di_cancels() {
+    let mut gb = GameBoy::new(None);
+
+    // EI, DI, NOP
+    load_program(&mut gb, 0xC000, &[0xFB, 0xF3, 0x00]);
+
+    CPU::step(&mut gb).unwrap();
+    CPU::step(&mut gb).unwrap();
+    CPU::step(&mut gb).unwrap();
+    assert!(!gb.cpu.ime);
+}
+
+#[test]
+fn ie_push() {
+    let mut gb = GameBoy::new(None);
+
+    // Pushing the PC upper byte overwrites IE and disables the VBlank interrupt
+    gb.cpu.sp = 0x0000;
+    gb.cpu.pc = 0xC000;
+    gb.cpu.ime = true;
+    MMU::write_byte(&mut gb, 0xFFFF, 0b00000001);
+    MMU::write_byte(&mut gb, 0xFF0F, 0b00000001);
+
+    CPU::handle_interrupts(&mut gb);
+
+    assert_eq!(gb.cpu.pc, 0x0000);
+    assert_eq!(MMU::read_byte(&gb, 0xFFFF), 0xC0);
+    assert_eq!(MMU::read_byte(&gb, 0xFF0F) & 0b00000001, 1);
+}
+
+#[test]
+fn stop_ignores_key1_on_dmg() {
+    let mut gb = GameBoy::new(None);
+    load_program(&mut gb, 0xC000, &[0x10, 0x00]);
+
+    // KEY1 is not a register of the DMG, arming it does nothing
+    MMU::write_byte(&mut gb, 0xFF4D, 0x01);
+    CPU::step(&mut gb).unwrap();
+    assert!(gb.cpu.is_stopped);
+    assert!(!gb.io.double_speed);
+}
+
+#[test]
+fn stop_until_joypad() {
+    let mut gb = GameBoy::new(None);
+
+    // STOP, 0x00, INC A
+    load_program(&mut gb, 0xC000, &[0x10, 0x00, 0x3C]);
+
+    // Select action buttons
+    MMU::write_byte(&mut gb, 0xFF00, 0x10);
+
+    CPU::step(&mut gb).unwrap();
+    assert!(gb.cpu.is_stopped);
+    assert_eq!(gb.cpu.pc, 0xC002);
+    assert_eq!(MMU::read_byte(&gb, 0xFF04), 0);
+
+    // Pressing a direction is not seen on the selected line
+    Joypad::button_pressed(&mut gb, Button::Up);
+    CPU::step(&mut gb).unwrap();
+    assert!(gb.cpu.is_stopped);
+    assert_eq!(gb.cpu.pc, 0xC002);
+
+    Joypad::button_pressed(&mut gb, Button::A);
+    CPU::step(&mut gb).unwrap();
+    assert!(!gb.cpu.is_stopped);
+    assert_eq!(gb.cpu.regs.a, 1);
+}
+
+#[test]
+fn read_timing() {
+    let mut gb = GameBoy::new(None);
+
+    // Timer enabled, TIMA incremented every 16 clocks
+    MMU::write_byte(&mut gb, 0xFF07, 0b101);
+
+    // LD A, (0xFF05)
+    load_program(&mut gb, 0xC000, &[0xFA, 0x05, 0xFF]);
+
+    // TIMA is read on the last machine cycle, once the timer has advanced
+    assert_eq!(CPU::step(&mut gb).unwrap(), 16);
+    assert_eq!(gb.cpu.regs.a, 1);
+}
+
+#[test]
+fn tima_reload_delay() {
+    let mut gb = GameBoy::new(None);
+
+    MMU::write_byte(&mut gb, 0xFF05, 0xFF);
+    MMU::write_byte(&mut gb, 0xFF06, 0xAB);
+    MMU::write_byte(&mut gb, 0xFF07, 0b101);
+    MMU::write_byte(&mut gb, 0xFF0F, 0);
+
+    for _ in 0..4 {
+        gb.cycle();
+    }
+    // TIMA reads 0 for a machine cycle before being reloaded from TMA
+    assert_eq!(MMU::read_byte(&gb, 0xFF05), 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF0F) & 0b100, 0);
+
+    gb.cycle();
+    assert_eq!(MMU::read_byte(&gb, 0xFF05), 0xAB);
+    assert_eq!(MMU::read_byte(&gb, 0xFF0F) & 0b100, 0b100);
+}
+
+#[test]
+fn oam_dma() {
+    let mut gb = GameBoy::new(None);
+
+    for i in 0..0xA0 {
+        MMU::write_byte(&mut gb, 0xC100 + i, i as u8);
+    }
+    MMU::write_byte(&mut gb, 0xFF46, 0xC1);
+
+    gb.cycle();
+    gb.cycle();
+    // OAM is not accessible by the CPU during the transfer
+    assert_eq!(MMU::read_byte(&gb, 0xFE00), 0xFF);
+
+    for _ in 0..0xA0 {
+        gb.cycle();
+    }
+    assert_eq!(MMU::read_byte(&gb, 0xFE00), 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFE9F), 0x9F);
+    assert_eq!(MMU::read_byte(&gb, 0xFF46), 0xC1);
+}
+
+#[test]
+fn illegal_opcode_locks_up() {
+    let mut gb = GameBoy::new(None);
+    load_program(&mut gb, 0xC000, &[0x00, 0xD3, 0x00]);
+    gb.cpu.ime = true;
+
+    gb.tick().unwrap();
+    gb.tick().unwrap();
+    assert!(gb.cpu.is_locked);
+    assert_eq!(gb.events, vec![EmulationEvent::Locked { pc: 0xC001, opcode: 0xD3 }]);
+
+    // Neither instructions nor interrupts move the CPU anymore
+    MMU::write_byte(&mut gb, 0xFFFF, 0xFF);
+    MMU::write_byte(&mut gb, 0xFF0F, 0xFF);
+    for _ in 0..100 {
+        assert_eq!(gb.tick().unwrap(), 4);
+    }
+    assert_eq!(gb.cpu.pc, 0xC001);
+    assert!(gb.cpu.ime);
+}
+
+#[test]
+fn illegal_opcode_error() {
+    let mut gb = GameBoy::new(None);
+    gb.strict = true;
+    load_program(&mut gb, 0xC000, &[0x00, 0xD3]);
+
+    assert!(gb.tick().is_ok());
+    match gb.tick() {
+        Err(GameBoyError::IllegalOpcode { pc, opcode }) => assert_eq!((pc, opcode), (0xC001, 0xD3)),
+        _ => panic!("Expected an illegal opcode error"),
+    }
+    assert!(!gb.cpu.is_locked);
+}
+
+#[test]
+fn all_illegal_opcodes_decoded() {
+    for opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD] {
+        assert!(matches!(Instruction::from_byte_not_prefixed(opcode), Some(Instruction::ILLEGAL(byte)) if byte == opcode));
+    }
+}
+
+#[test]
+fn skip_boot_state() {
+    let mut gb = GameBoy::new(None);
+    gb.skip_boot();
+
+    assert_eq!(gb.to_string(), "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:FF,FF,FF,FF");
+    // LCDC, BGP, DIV, IF, DMA and the boot switch. STAT mode bits follow the LCD timing
+    for (address, value) in [(0xFF40, 0x91), (0xFF47, 0xFC), (0xFF04, 0xAB), (0xFF0F, 0xE1), (0xFF46, 0xFF), (0xFF50, 0x01)] {
+        assert_eq!(MMU::read_byte(&gb, address), value, "Register {:04X}", address);
+    }
+    // The boot ROM is not mapped anymore, there is no cartridge behind it
+    assert_eq!(MMU::read_byte(&gb, 0x0000), 0xFF);
+}
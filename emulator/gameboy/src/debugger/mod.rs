@@ -0,0 +1,257 @@
+use std::{cell::Cell, fmt, str::FromStr};
+
+use crate::{gameboy::GameBoy, mmu::Address};
+
+mod tests;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    A, F, B, C, D, E, H, L, AF, BC, DE, HL, SP, PC
+}
+
+impl Register {
+    pub(crate) fn read(&self, gb: &GameBoy) -> u16 {
+        let regs = &gb.cpu.regs;
+        match self {
+            Register::A => regs.a as u16,
+            Register::F => u8::from(regs.flags.clone()) as u16,
+            Register::B => regs.b as u16,
+            Register::C => regs.c as u16,
+            Register::D => regs.d as u16,
+            Register::E => regs.e as u16,
+            Register::H => regs.h as u16,
+            Register::L => regs.l as u16,
+            Register::AF => regs.get_af(),
+            Register::BC => regs.get_bc(),
+            Register::DE => regs.get_de(),
+            Register::HL => regs.get_hl(),
+            Register::SP => gb.cpu.sp,
+            Register::PC => gb.cpu.pc,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let register = match name.to_uppercase().as_str() {
+            "A" => Register::A,
+            "F" => Register::F,
+            "B" => Register::B,
+            "C" => Register::C,
+            "D" => Register::D,
+            "E" => Register::E,
+            "H" => Register::H,
+            "L" => Register::L,
+            "AF" => Register::AF,
+            "BC" => Register::BC,
+            "DE" => Register::DE,
+            "HL" => Register::HL,
+            "SP" => Register::SP,
+            "PC" => Register::PC,
+            _ => return Err(format!("unknown register {}", name)),
+        };
+        Ok(register)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal, NotEqual, Less, LessOrEqual, Greater, GreaterOrEqual
+}
+
+// Register compared with a value, e.g. A==3F or HL>=C000
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub(crate) fn holds(&self, gb: &GameBoy) -> bool {
+        let register = self.register.read(gb);
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::Less => register < self.value,
+            Comparison::LessOrEqual => register <= self.value,
+            Comparison::Greater => register > self.value,
+            Comparison::GreaterOrEqual => register >= self.value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        // Two character operators go first so "<=" is not taken for "<"
+        let operators = [
+            ("==", Comparison::Equal), ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less), (">", Comparison::Greater),
+        ];
+        for (operator, comparison) in operators {
+            if let Some((register, value)) = condition.split_once(operator) {
+                return Ok(Condition {
+                    register: register.trim().parse()?,
+                    comparison,
+                    value: parse_value(value.trim())?,
+                });
+            }
+        }
+        Err(format!("invalid condition {}", condition))
+    }
+}
+
+// Values are hexadecimal as in the rest of the Game Boy documentation,
+// optionally prefixed with 0x or $
+pub fn parse_value(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid value {}", value))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: Address,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read, Write, ReadWrite
+}
+
+impl FromStr for Access {
+    type Err = String;
+
+    fn from_str(access: &str) -> Result<Self, Self::Err> {
+        match access {
+            "r" => Ok(Access::Read),
+            "w" => Ok(Access::Write),
+            "rw" => Ok(Access::ReadWrite),
+            _ => Err(format!("invalid access {}, expected r, w or rw", access)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub address: Address,
+    pub access: Access,
+}
+
+// Why the execution stopped and gave control back to the debugger
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugStop {
+    // A single instruction was executed
+    Step,
+    // About to execute the instruction at a breakpoint
+    Breakpoint(Address),
+    // About to execute the instruction a run to or step over was waiting for
+    Reached(Address),
+    // The last instruction accessed a watched address, access is Read or Write
+    Watchpoint { address: Address, value: u8, access: Access },
+    FrameLimit,
+}
+
+impl fmt::Display for DebugStop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DebugStop::Step => write!(f, "Step"),
+            DebugStop::Breakpoint(address) => write!(f, "Breakpoint at {:04X}", address),
+            DebugStop::Reached(address) => write!(f, "Reached {:04X}", address),
+            DebugStop::Watchpoint { address, value, access: Access::Write } => write!(f, "Write of {:02X} to {:04X}", value, address),
+            DebugStop::Watchpoint { address, value, .. } => write!(f, "Read of {:02X} from {:04X}", value, address),
+            DebugStop::FrameLimit => write!(f, "Frame limit reached"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    // Run to and step over stop at this address once the stack pointer is
+    // back at or above the given one, so recursive calls do not stop early
+    pub(crate) target: Option<(Address, Address)>,
+    // PC where the execution is paused, its breakpoint is skipped on resume
+    pub(crate) resume_pc: Option<Address>,
+    // Memory accesses only see the GameBoy, so the stop is set through a Cell
+    pub(crate) stop: Cell<Option<DebugStop>>,
+}
+
+impl Debugger {
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        (index < self.breakpoints.len()).then(|| self.breakpoints.remove(index))
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        (index < self.watchpoints.len()).then(|| self.watchpoints.remove(index))
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // Checked by the CPU before fetching every instruction, the instruction
+    // is not executed when the execution has to stop
+    pub(crate) fn should_break(gb: &mut GameBoy) -> bool {
+        let debugger = &mut gb.debugger;
+        if debugger.breakpoints.is_empty() && debugger.target.is_none() {
+            return false;
+        }
+
+        let pc = gb.cpu.pc;
+        if debugger.resume_pc.take() == Some(pc) {
+            return false;
+        }
+
+        let stop = match debugger.target {
+            Some((address, sp)) if pc == address && gb.cpu.sp >= sp => {
+                gb.debugger.target = None;
+                Some(DebugStop::Reached(pc))
+            },
+            _ => gb.debugger.breakpoints.iter()
+                .find(|breakpoint| breakpoint.address == pc && breakpoint.condition.is_none_or(|condition| condition.holds(gb)))
+                .map(|_| DebugStop::Breakpoint(pc)),
+        };
+
+        if stop.is_some() {
+            gb.debugger.stop.set(stop);
+            gb.debugger.resume_pc = Some(pc);
+        }
+        stop.is_some()
+    }
+
+    pub(crate) fn watch_read(gb: &GameBoy, address: Address, value: u8) {
+        Debugger::watch(gb, address, value, Access::Read);
+    }
+
+    pub(crate) fn watch_write(gb: &GameBoy, address: Address, value: u8) {
+        Debugger::watch(gb, address, value, Access::Write);
+    }
+
+    fn watch(gb: &GameBoy, address: Address, value: u8, access: Access) {
+        let watched = gb.debugger.watchpoints.iter()
+            .any(|watchpoint| watchpoint.address == address && (watchpoint.access == access || watchpoint.access == Access::ReadWrite));
+        // The first access of the instruction is the one reported
+        if watched && gb.debugger.stop.get().is_none() {
+            gb.debugger.stop.set(Some(DebugStop::Watchpoint { address, value, access }));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+#[cfg(test)]
+use crate::{Emulation, EmulationEvent, mmu::MMU};
+
+#[cfg(test)]
+use super::{Access, Breakpoint, Comparison, Condition, DebugStop, Register, Watchpoint};
+
+#[cfg(test)]
+fn load_program(emu: &mut Emulation, program: &[u8]) {
+    for (offset, byte) in program.iter().enumerate() {
+        MMU::write_byte(&mut emu.gameboy, 0xC000 + offset as u16, *byte);
+    }
+    emu.gameboy.cpu.pc = 0xC000;
+    emu.gameboy.cpu.sp = 0xDFFF;
+}
+
+// INC A, CALL C010, INC A, JR -2 and at C010: INC B, LD (C100),A, RET
+#[cfg(test)]
+const PROGRAM: [u8; 23] = [
+    0x3C, 0xCD, 0x10, 0xC0, 0x3C, 0x18, 0xFE, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0x04, 0xEA, 0x00, 0xC1, 0xC9, 0, 0,
+];
+
+#[test]
+fn step_instruction_and_over() {
+    let mut emu = Emulation::new(None);
+    load_program(&mut emu, &PROGRAM);
+
+    assert_eq!(emu.step_instruction().unwrap(), DebugStop::Step);
+    assert_eq!(emu.read_register(Register::PC), 0xC001);
+    assert_eq!(emu.read_register(Register::A), 1);
+
+    // The whole call runs and the execution stops after it
+    assert_eq!(emu.step_over(1).unwrap(), DebugStop::Reached(0xC004));
+    assert_eq!(emu.read_register(Register::B), 1);
+    assert_eq!(emu.read_register(Register::SP), 0xDFFF);
+
+    assert_eq!(emu.step_over(1).unwrap(), DebugStop::Step);
+    assert_eq!(emu.read_register(Register::PC), 0xC005);
+}
+
+#[test]
+fn run_to_address() {
+    let mut emu = Emulation::new(None);
+    load_program(&mut emu, &PROGRAM);
+
+    assert_eq!(emu.run_to(0xC014, 1).unwrap(), DebugStop::Reached(0xC014));
+    assert_eq!(emu.read_register(Register::PC), 0xC014);
+    // The JR -2 loop never gets back to the call
+    assert_eq!(emu.run_to(0xC001, 1).unwrap(), DebugStop::FrameLimit);
+}
+
+#[test]
+fn breakpoints_stop_the_frame() {
+    let mut emu = Emulation::new(None);
+    load_program(&mut emu, &PROGRAM);
+    emu.debugger().add_breakpoint(Breakpoint { address: 0xC010, condition: None });
+
+    let step = emu.step().unwrap();
+    assert_eq!(step.events, vec![EmulationEvent::Break(DebugStop::Breakpoint(0xC010))]);
+    assert_eq!(emu.read_register(Register::B), 0);
+    assert_eq!(emu.frames, 0);
+
+    // Resuming executes the instruction under the breakpoint and finishes the frame
+    let step = emu.step().unwrap();
+    assert!(step.events.is_empty());
+    assert_eq!(emu.read_register(Register::B), 1);
+    assert_eq!(emu.frames, 1);
+}
+
+#[test]
+fn conditional_breakpoint() {
+    let mut emu = Emulation::new(None);
+    // INC A, JR -3
+    load_program(&mut emu, &[0x3C, 0x18, 0xFD]);
+    let condition = "A==3".parse::<Condition>().unwrap();
+    assert_eq!(condition, Condition { register: Register::A, comparison: Comparison::Equal, value: 3 });
+    emu.debugger().add_breakpoint(Breakpoint { address: 0xC001, condition: Some(condition) });
+
+    assert_eq!(emu.step().unwrap().events, vec![EmulationEvent::Break(DebugStop::Breakpoint(0xC001))]);
+    assert_eq!(emu.read_register(Register::A), 3);
+
+    assert_eq!(emu.debugger().remove_breakpoint(0).map(|breakpoint| breakpoint.address), Some(0xC001));
+    assert!(emu.step().unwrap().events.is_empty());
+}
+
+#[test]
+fn watchpoints() {
+    let mut emu = Emulation::new(None);
+    load_program(&mut emu, &PROGRAM);
+    emu.debugger().add_watchpoint(Watchpoint { address: 0xC100, access: Access::Write });
+
+    assert_eq!(emu.run_to(0xC004, 1).unwrap(), DebugStop::Watchpoint { address: 0xC100, value: 1, access: Access::Write });
+    assert_eq!(emu.read_register(Register::PC), 0xC014);
+
+    // Reads from the frontend are not reported
+    emu.debugger().add_watchpoint(Watchpoint { address: 0xC101, access: Access::ReadWrite });
+    assert_eq!(emu.read_memory(0xC101), 0);
+    assert_eq!(emu.step_instruction().unwrap(), DebugStop::Step);
+}
+
+#[test]
+fn dma_does_not_trigger_watchpoints() {
+    let mut emu = Emulation::new(None);
+    // LD A,C1, LDH (46),A, JR -2: copies C100-C19F to OAM
+    load_program(&mut emu, &[0x3E, 0xC1, 0xE0, 0x46, 0x18, 0xFE]);
+    MMU::write_byte(&mut emu.gameboy, 0xC100, 0x42);
+    emu.debugger().add_watchpoint(Watchpoint { address: 0xC100, access: Access::Read });
+
+    // The transfer takes 160 machine cycles, JR takes 3
+    for _ in 0..60 {
+        assert_eq!(emu.step_instruction().unwrap(), DebugStop::Step);
+    }
+    assert_eq!(emu.gameboy.ppu.oam[0], 0x42);
+}
+
+#[test]
+fn parse_conditions() {
+    assert_eq!("hl>=c000".parse(), Ok(Condition { register: Register::HL, comparison: Comparison::GreaterOrEqual, value: 0xC000 }));
+    assert_eq!("B < $10".parse(), Ok(Condition { register: Register::B, comparison: Comparison::Less, value: 0x10 }));
+    assert!("X==1".parse::<Condition>().is_err());
+    assert!("A=1".parse::<Condition>().is_err());
+}
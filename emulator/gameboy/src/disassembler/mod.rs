@@ -0,0 +1,256 @@
+use std::fmt;
+
+use crate::cpu::instructions::decode::*;
+use crate::mmu::Address;
+
+mod tests;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
+// Cartridge header, from the entry point jump to the global checksum
+const HEADER_BEGIN: Address = 0x0104;
+const HEADER_END: Address = 0x014F;
+const DATA_BYTES_PER_LINE: usize = 8;
+
+const PREFIX_OPCODE: u8 = 0xCB;
+
+// One instruction, or data that is not executed, rendered in RGBDS syntax
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    pub address: Address,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+impl fmt::Display for DisassemblyLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(f, "{:04X}  {:<9} {}", self.address, bytes.join(" "), self.text)
+    }
+}
+
+// Decodes the instruction at the address, reading its bytes with `read`
+pub(crate) fn disassemble(read: impl Fn(Address) -> u8, address: Address) -> DisassemblyLine {
+    let opcode = read(address);
+    let instruction = if opcode == PREFIX_OPCODE {
+        Instruction::from_byte_prefixed(read(address.wrapping_add(1)))
+    } else {
+        // Every opcode other than the prefix is decoded, undefined ones included
+        Instruction::from_byte_not_prefixed(opcode).unwrap_or(Instruction::ILLEGAL(opcode))
+    };
+
+    let size = u16::from(instruction.size());
+    let bytes: Vec<u8> = (0..size).map(|offset| read(address.wrapping_add(offset))).collect();
+    let text = render(&instruction, address, &bytes);
+
+    DisassemblyLine { address, bytes, text }
+}
+
+// Disassembles a whole bank of a ROM as it is mapped in memory, bank 0 at
+// 0x0000 and any other one at 0x4000. The cartridge header is shown as data
+pub fn disassemble_bank(rom: &[u8], bank: usize) -> Vec<DisassemblyLine> {
+    let (begin, end) = if bank == 0 { (0x0000, 0x4000) } else { (0x4000, 0x8000) };
+    let end = end.min(begin + rom.len().saturating_sub(bank * ROM_BANK_SIZE)) as Address;
+    let read = |address: Address| read_rom(rom, bank, address);
+
+    let mut lines = Vec::new();
+    let mut address = begin as Address;
+    while address < end {
+        let line = if bank == 0 && (HEADER_BEGIN..=HEADER_END).contains(&address) {
+            let length = DATA_BYTES_PER_LINE.min((HEADER_END - address) as usize + 1);
+            data(read, address, length)
+        } else {
+            disassemble(read, address)
+        };
+        address = match address.checked_add(line.bytes.len() as Address) {
+            Some(next) => next,
+            None => break,
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn rom_banks(rom: &[u8]) -> usize {
+    rom.len().div_ceil(ROM_BANK_SIZE)
+}
+
+// Operands past the end of bank 0 come from bank 1, as the CPU sees them
+// with the default mapping. Anything outside the ROM reads as 0xFF
+fn read_rom(rom: &[u8], bank: usize, address: Address) -> u8 {
+    let offset = match address {
+        0x0000..=0x3FFF => address as usize,
+        0x4000..=0x7FFF => bank.max(1) * ROM_BANK_SIZE + address as usize - ROM_BANK_SIZE,
+        _ => return 0xFF,
+    };
+    rom.get(offset).copied().unwrap_or(0xFF)
+}
+
+fn data(read: impl Fn(Address) -> u8, address: Address, length: usize) -> DisassemblyLine {
+    let bytes: Vec<u8> = (0..length as Address).map(|offset| read(address + offset)).collect();
+    let values: Vec<String> = bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+    DisassemblyLine { address, text: format!("DB {}", values.join(", ")), bytes }
+}
+
+fn render(instruction: &Instruction, address: Address, bytes: &[u8]) -> String {
+    let n16 = || format!("${:04X}", (bytes[2] as u16) << 8 | bytes[1] as u16);
+    let e8 = || bytes[1] as i8;
+
+    match instruction {
+        Instruction::NOP => String::from("NOP"),
+        Instruction::HALT => String::from("HALT"),
+        Instruction::STOP => String::from("STOP"),
+        Instruction::SCF => String::from("SCF"),
+        Instruction::DAA => String::from("DAA"),
+        Instruction::CCF => String::from("CCF"),
+        Instruction::CPL => String::from("CPL"),
+        Instruction::ILLEGAL(opcode) => format!("DB ${:02X}", opcode),
+        Instruction::ADD(source) => format!("ADD A, {}", operand(source, bytes)),
+        Instruction::ADC(source) => format!("ADC A, {}", operand(source, bytes)),
+        Instruction::SUB(source) => format!("SUB A, {}", operand(source, bytes)),
+        Instruction::SBC(source) => format!("SBC A, {}", operand(source, bytes)),
+        Instruction::AND(source) => format!("AND A, {}", operand(source, bytes)),
+        Instruction::OR(source) => format!("OR A, {}", operand(source, bytes)),
+        Instruction::CP(source) => format!("CP A, {}", operand(source, bytes)),
+        Instruction::XOR(source) => format!("XOR A, {}", operand(source, bytes)),
+        Instruction::INC(target) => format!("INC {}", register(target)),
+        Instruction::DEC(target) => format!("DEC {}", register(target)),
+        Instruction::ADDSPS8 => format!("ADD SP, {}", e8()),
+        Instruction::ADD16(source) => format!("ADD HL, {}", word_register(source)),
+        Instruction::INC16(target) => format!("INC {}", word_register(target)),
+        Instruction::DEC16(target) => format!("DEC {}", word_register(target)),
+        Instruction::LD(load_type) => match load_type {
+            LoadType::Byte(target, source) => format!("LD {}, {}", register(target), operand(source, bytes)),
+            LoadType::Word(target) => format!("LD {}, {}", word_register(target), n16()),
+            LoadType::AFromIndirect(source) => format!("LD A, {}", indirect(source)),
+            LoadType::IndirectFromA(target) => format!("LD {}, A", indirect(target)),
+            LoadType::AFromDirect => format!("LD A, [{}]", n16()),
+            LoadType::DirectFromA => format!("LD [{}], A", n16()),
+        },
+        Instruction::LDHLSPD8 => format!("LD HL, SP{:+}", e8()),
+        Instruction::LDSPHL => String::from("LD SP, HL"),
+        Instruction::LDFF(load_type) => match load_type {
+            LoadFFType::AtoFFC => String::from("LDH [C], A"),
+            LoadFFType::FFCtoA => String::from("LDH A, [C]"),
+            LoadFFType::AtoFFa8 => format!("LDH [$FF{:02X}], A", bytes[1]),
+            LoadFFType::FFa8toA => format!("LDH A, [$FF{:02X}]", bytes[1]),
+        },
+        Instruction::LDSPA16 => format!("LD [{}], SP", n16()),
+        Instruction::JP(test) => with_condition("JP", test, n16()),
+        Instruction::JR(test) => {
+            let target = address.wrapping_add(2).wrapping_add(e8() as u16);
+            with_condition("JR", test, format!("${:04X}", target))
+        },
+        Instruction::JPHL => String::from("JP HL"),
+        Instruction::CALL(test) => with_condition("CALL", test, n16()),
+        Instruction::RET(test) => match condition(test) {
+            Some(condition) => format!("RET {}", condition),
+            None => String::from("RET"),
+        },
+        Instruction::RST(target) => format!("RST ${:02X}", bit(target) * 8),
+        Instruction::RETI => String::from("RETI"),
+        Instruction::PUSH(target) => format!("PUSH {}", stack_register(target)),
+        Instruction::POP(target) => format!("POP {}", stack_register(target)),
+        Instruction::BIT(BitType::Registers(position, target)) => format!("BIT {}, {}", bit(position), register(target)),
+        Instruction::RL(target) => format!("RL {}", register(target)),
+        Instruction::RLC(target) => format!("RLC {}", register(target)),
+        Instruction::RR(target) => format!("RR {}", register(target)),
+        Instruction::RRC(target) => format!("RRC {}", register(target)),
+        Instruction::RLA => String::from("RLA"),
+        Instruction::RLCA => String::from("RLCA"),
+        Instruction::RRA => String::from("RRA"),
+        Instruction::RRCA => String::from("RRCA"),
+        Instruction::SLA(target) => format!("SLA {}", register(target)),
+        Instruction::SRA(target) => format!("SRA {}", register(target)),
+        Instruction::SRL(target) => format!("SRL {}", register(target)),
+        Instruction::SWAP(target) => format!("SWAP {}", register(target)),
+        Instruction::DI => String::from("DI"),
+        Instruction::EI => String::from("EI"),
+        Instruction::RES(ResSetType::Registers(position, target)) => format!("RES {}, {}", bit(position), register(target)),
+        Instruction::SET(ResSetType::Registers(position, target)) => format!("SET {}, {}", bit(position), register(target)),
+    }
+}
+
+fn with_condition(mnemonic: &str, test: &JumpTest, target: String) -> String {
+    match condition(test) {
+        Some(condition) => format!("{} {}, {}", mnemonic, condition, target),
+        None => format!("{} {}", mnemonic, target),
+    }
+}
+
+fn condition(test: &JumpTest) -> Option<&'static str> {
+    match test {
+        JumpTest::NotZero => Some("NZ"),
+        JumpTest::Zero => Some("Z"),
+        JumpTest::NotCarry => Some("NC"),
+        JumpTest::Carry => Some("C"),
+        JumpTest::Always => None,
+    }
+}
+
+fn register(target: &RegistersIndirect) -> &'static str {
+    match target {
+        RegistersIndirect::A => "A",
+        RegistersIndirect::B => "B",
+        RegistersIndirect::C => "C",
+        RegistersIndirect::D => "D",
+        RegistersIndirect::E => "E",
+        RegistersIndirect::H => "H",
+        RegistersIndirect::L => "L",
+        RegistersIndirect::HLI => "[HL]",
+    }
+}
+
+fn operand(source: &RegistersIndDir, bytes: &[u8]) -> String {
+    match source {
+        RegistersIndDir::A => String::from("A"),
+        RegistersIndDir::B => String::from("B"),
+        RegistersIndDir::C => String::from("C"),
+        RegistersIndDir::D => String::from("D"),
+        RegistersIndDir::E => String::from("E"),
+        RegistersIndDir::H => String::from("H"),
+        RegistersIndDir::L => String::from("L"),
+        RegistersIndDir::HLI => String::from("[HL]"),
+        RegistersIndDir::D8 => format!("${:02X}", bytes[1]),
+    }
+}
+
+fn word_register(target: &WordRegister) -> &'static str {
+    match target {
+        WordRegister::BC => "BC",
+        WordRegister::DE => "DE",
+        WordRegister::HL => "HL",
+        WordRegister::SP => "SP",
+    }
+}
+
+fn stack_register(target: &StackTarget) -> &'static str {
+    match target {
+        StackTarget::BC => "BC",
+        StackTarget::DE => "DE",
+        StackTarget::HL => "HL",
+        StackTarget::AF => "AF",
+    }
+}
+
+fn indirect(source: &LoadIndirectSource) -> &'static str {
+    match source {
+        LoadIndirectSource::BC => "[BC]",
+        LoadIndirectSource::DE => "[DE]",
+        LoadIndirectSource::HLInc => "[HL+]",
+        LoadIndirectSource::HLDec => "[HL-]",
+    }
+}
+
+fn bit(target: &BitTarget) -> u8 {
+    match target {
+        BitTarget::Zero => 0,
+        BitTarget::One => 1,
+        BitTarget::Two => 2,
+        BitTarget::Three => 3,
+        BitTarget::Four => 4,
+        BitTarget::Five => 5,
+        BitTarget::Six => 6,
+        BitTarget::Seven => 7,
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+use crate::{Emulation, mmu::MMU};
+
+#[cfg(test)]
+use super::{disassemble, disassemble_bank, rom_banks, DisassemblyLine, ROM_BANK_SIZE};
+
+#[cfg(test)]
+fn text(program: &[u8], address: u16) -> String {
+    let read = |at: u16| program.get(at.wrapping_sub(address) as usize).copied().unwrap_or(0);
+    disassemble(read, address).text
+}
+
+#[test]
+fn rgbds_syntax() {
+    assert_eq!(text(&[0x00], 0), "NOP");
+    assert_eq!(text(&[0x2A], 0), "LD A, [HL+]");
+    assert_eq!(text(&[0x32], 0), "LD [HL-], A");
+    assert_eq!(text(&[0x36, 0x12], 0), "LD [HL], $12");
+    assert_eq!(text(&[0x01, 0x34, 0x12], 0), "LD BC, $1234");
+    assert_eq!(text(&[0xEA, 0x00, 0xC0], 0), "LD [$C000], A");
+    assert_eq!(text(&[0x08, 0xFE, 0xFF], 0), "LD [$FFFE], SP");
+    assert_eq!(text(&[0xE0, 0x40], 0), "LDH [$FF40], A");
+    assert_eq!(text(&[0xF2], 0), "LDH A, [C]");
+    assert_eq!(text(&[0xF8, 0xFE], 0), "LD HL, SP-2");
+    assert_eq!(text(&[0xE8, 0x05], 0), "ADD SP, 5");
+    assert_eq!(text(&[0x80], 0), "ADD A, B");
+    assert_eq!(text(&[0xFE, 0x90], 0), "CP A, $90");
+    assert_eq!(text(&[0x09], 0), "ADD HL, BC");
+    assert_eq!(text(&[0xC3, 0x50, 0x01], 0), "JP $0150");
+    assert_eq!(text(&[0xC4, 0x00, 0x40], 0), "CALL NZ, $4000");
+    assert_eq!(text(&[0xD8], 0), "RET C");
+    assert_eq!(text(&[0xFF], 0), "RST $38");
+    assert_eq!(text(&[0xE9], 0), "JP HL");
+    assert_eq!(text(&[0xF5], 0), "PUSH AF");
+    assert_eq!(text(&[0xCB, 0x7C], 0), "BIT 7, H");
+    assert_eq!(text(&[0xCB, 0x86], 0), "RES 0, [HL]");
+    assert_eq!(text(&[0xCB, 0x37], 0), "SWAP A");
+    assert_eq!(text(&[0xD3], 0), "DB $D3");
+}
+
+#[test]
+fn relative_jump_targets() {
+    assert_eq!(text(&[0x20, 0xFE], 0x0150), "JR NZ, $0150");
+    assert_eq!(text(&[0x18, 0x10], 0x0150), "JR $0162");
+}
+
+#[test]
+fn rom_banks_and_header() {
+    let mut rom = vec![0x00; ROM_BANK_SIZE * 2];
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x3FFF] = 0xC3;
+    rom[0x4000..0x4002].copy_from_slice(&[0x50, 0x01]);
+    assert_eq!(rom_banks(&rom), 2);
+
+    let bank0 = disassemble_bank(&rom, 0);
+    let entry = bank0.iter().position(|line| line.address == 0x0101).unwrap();
+    assert_eq!(bank0[entry].text, "JP $0150");
+    // The header is data until the first byte after it
+    assert_eq!(bank0[entry + 1], DisassemblyLine { address: 0x0104, bytes: vec![0; 8], text: String::from("DB $00, $00, $00, $00, $00, $00, $00, $00") });
+    assert!(bank0.iter().any(|line| line.address == 0x0150 && line.text == "NOP"));
+    // Operands past the end of bank 0 come from bank 1
+    assert_eq!(bank0.last().unwrap().text, "JP $0150");
+
+    let bank1 = disassemble_bank(&rom, 1);
+    assert_eq!((bank1[0].address, bank1[0].text.as_str()), (0x4000, "LD D, B"));
+    assert_eq!(bank1.last().unwrap().address, 0x7FFF);
+    assert!(disassemble_bank(&rom, 2).is_empty());
+}
+
+#[test]
+fn live_disassembly() {
+    let mut emu = Emulation::new(None);
+    for (offset, byte) in [0x3E, 0x01, 0xCB, 0x37, 0x76].iter().enumerate() {
+        MMU::write_byte(&mut emu.gameboy, 0xC000 + offset as u16, *byte);
+    }
+
+    let lines: Vec<String> = emu.disassemble(0xC000, 3).iter().map(|line| line.to_string()).collect();
+    assert_eq!(lines, vec!["C000  3E 01     LD A, $01", "C002  CB 37     SWAP A", "C004  76        HALT"]);
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use crate::mmu::Address;
+
+#[derive(Debug)]
+pub enum GameBoyError {
+    // The opcode is not defined on the SM83
+    IllegalOpcode { pc: Address, opcode: u8 },
+    InvalidCartridge(String),
+    InvalidBootROM(String),
+    UnsupportedMapper(u8),
+    // The save state was made with another game
+    SaveStateMismatch,
+    // The movie was recorded with another game
+    MovieMismatch,
+    InvalidSaveState(String),
+    InvalidMovie(String),
+    NothingToRewind,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for GameBoyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameBoyError::IllegalOpcode { pc, opcode } => write!(f, "Illegal opcode {:02X} at {:04X}", opcode, pc),
+            GameBoyError::InvalidCartridge(reason) => write!(f, "Invalid cartridge: {}", reason),
+            GameBoyError::InvalidBootROM(reason) => write!(f, "Invalid boot ROM: {}", reason),
+            GameBoyError::UnsupportedMapper(ctype) => write!(f, "Unsupported cartridge type {:02X}", ctype),
+            GameBoyError::SaveStateMismatch => write!(f, "Save state belongs to another game"),
+            GameBoyError::MovieMismatch => write!(f, "Movie was recorded with another game"),
+            GameBoyError::InvalidSaveState(reason) => write!(f, "Invalid save state: {}", reason),
+            GameBoyError::InvalidMovie(reason) => write!(f, "Invalid movie: {}", reason),
+            GameBoyError::NothingToRewind => write!(f, "Nothing to rewind"),
+            GameBoyError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GameBoyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GameBoyError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl std::convert::From<std::io::Error> for GameBoyError {
+    fn from(error: std::io::Error) -> Self {
+        GameBoyError::Io(error)
+    }
+}
+
+// Frontends working with io::Error can still use `?`
+impl std::convert::From<GameBoyError> for std::io::Error {
+    fn from(error: GameBoyError) -> Self {
+        match error {
+            GameBoyError::Io(error) => error,
+            error => std::io::Error::new(std::io::ErrorKind::InvalidData, error),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{fmt, str::FromStr};
+
+use crate::RGBAFrame;
+
+type Pixel = [u8; 4];
+
+// Upscaling applied to the frames before they are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    // Pixels are left as they are, the frontend scales them
+    Nearest,
+    // https://www.scale2x.it/algorithm
+    Scale2x,
+    Scale3x,
+    // Scale2x comparing colors by similarity and blending the corners
+    // instead of copying them, in the spirit of hq2x
+    HQ2x,
+    // Every pixel is 3x3 with darker gaps between them, as on the DMG LCD
+    LCDGrid,
+}
+
+const FILTERS: [Filter; 5] = [Filter::Nearest, Filter::Scale2x, Filter::Scale3x, Filter::HQ2x, Filter::LCDGrid];
+
+// Luma difference that still counts as the same color for HQ2x
+const SIMILAR_LUMA: i32 = 48;
+// Brightness kept by the gaps of the LCD grid, out of 256
+const GRID_BRIGHTNESS: u16 = 192;
+
+impl Filter {
+    pub fn scale(&self) -> u32 {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x | Filter::HQ2x => 2,
+            Filter::Scale3x | Filter::LCDGrid => 3,
+        }
+    }
+
+    // The next filter, the last one goes back to the first
+    pub fn next(&self) -> Filter {
+        let index = FILTERS.iter().position(|filter| filter == self).unwrap();
+        FILTERS[(index + 1) % FILTERS.len()]
+    }
+
+    pub fn apply(&self, frame: &RGBAFrame) -> RGBAFrame {
+        let scale = self.scale() as usize;
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let out_width = width * scale;
+        let mut buffer = vec![0; out_width * height * scale * 4];
+
+        // Neighbours past the edges repeat the edge pixel
+        let pixel = |x: isize, y: isize| -> Pixel {
+            let x = x.clamp(0, width as isize - 1) as usize;
+            let y = y.clamp(0, height as isize - 1) as usize;
+            let offset = (y * width + x) * 4;
+            frame.buffer[offset..offset + 4].try_into().unwrap()
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = (x as isize, y as isize);
+                let block = match self {
+                    Filter::Nearest => vec![pixel(px, py)],
+                    Filter::Scale2x => Filter::scale2x(pixel(px, py), pixel(px, py - 1), pixel(px + 1, py), pixel(px - 1, py), pixel(px, py + 1), |a, b| a == b, |_, corner| corner),
+                    Filter::HQ2x => Filter::scale2x(pixel(px, py), pixel(px, py - 1), pixel(px + 1, py), pixel(px - 1, py), pixel(px, py + 1), similar, |center, corner| blend(center, corner, 1, 3)),
+                    Filter::Scale3x => {
+                        let neighbours: [Pixel; 9] = std::array::from_fn(|index| pixel(px + index as isize % 3 - 1, py + index as isize / 3 - 1));
+                        Filter::scale3x(neighbours)
+                    },
+                    Filter::LCDGrid => {
+                        let center = pixel(px, py);
+                        let gap = blend(center, [0, 0, 0, center[3]], GRID_BRIGHTNESS, 256 - GRID_BRIGHTNESS);
+                        (0..9).map(|index| if index % 3 == 2 || index / 3 == 2 { gap } else { center }).collect()
+                    },
+                };
+
+                for (index, color) in block.iter().enumerate() {
+                    let (bx, by) = (x * scale + index % scale, y * scale + index / scale);
+                    let offset = (by * out_width + bx) * 4;
+                    buffer[offset..offset + 4].copy_from_slice(color);
+                }
+            }
+        }
+
+        RGBAFrame { width: frame.width * scale as u32, height: frame.height * scale as u32, buffer }
+    }
+
+    // Each quarter of the center takes the color of the two neighbours next
+    // to it when they match and the opposite ones do not
+    fn scale2x(center: Pixel, up: Pixel, right: Pixel, left: Pixel, down: Pixel, same: impl Fn(Pixel, Pixel) -> bool, corner: impl Fn(Pixel, Pixel) -> Pixel) -> Vec<Pixel> {
+        let quarter = |first: Pixel, second: Pixel, first_opposite: Pixel, second_opposite: Pixel| {
+            if same(first, second) && !same(first, first_opposite) && !same(second, second_opposite) {
+                corner(center, first)
+            } else {
+                center
+            }
+        };
+        vec![
+            quarter(left, up, down, right),
+            quarter(up, right, left, down),
+            quarter(down, left, right, up),
+            quarter(right, down, up, left),
+        ]
+    }
+
+    // Neighbours from the top left to the bottom right, 4 is the center
+    fn scale3x(pixels: [Pixel; 9]) -> Vec<Pixel> {
+        let [a, b, c, d, e, f, g, h, i] = pixels;
+        let (db, bf, dh, hf) = (d == b && b != f && d != h, b == f && b != d && f != h, d == h && d != b && h != f, h == f && d != h && b != f);
+        vec![
+            if db { d } else { e },
+            if (db && e != c) || (bf && e != a) { b } else { e },
+            if bf { f } else { e },
+            if (db && e != g) || (dh && e != a) { d } else { e },
+            e,
+            if (bf && e != i) || (hf && e != c) { f } else { e },
+            if dh { d } else { e },
+            if (dh && e != i) || (hf && e != g) { h } else { e },
+            if hf { f } else { e },
+        ]
+    }
+}
+
+fn luma(pixel: Pixel) -> i32 {
+    (pixel[0] as i32 * 299 + pixel[1] as i32 * 587 + pixel[2] as i32 * 114) / 1000
+}
+
+fn similar(a: Pixel, b: Pixel) -> bool {
+    (luma(a) - luma(b)).abs() < SIMILAR_LUMA
+}
+
+// Weighted average of two colors
+fn blend(a: Pixel, b: Pixel, weight_a: u16, weight_b: u16) -> Pixel {
+    std::array::from_fn(|channel| ((a[channel] as u16 * weight_a + b[channel] as u16 * weight_b) / (weight_a + weight_b)) as u8)
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Filter::Nearest => "nearest",
+            Filter::Scale2x => "scale2x",
+            Filter::Scale3x => "scale3x",
+            Filter::HQ2x => "hq2x",
+            Filter::LCDGrid => "lcd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        FILTERS.iter()
+            .find(|filter| filter.to_string() == name.to_lowercase())
+            .copied()
+            .ok_or(format!("unknown filter {}, expected nearest, scale2x, scale3x, hq2x or lcd", name))
+    }
+}
+
+// Filter of the frontend, with the ghosting of the slow DMG LCD: pixels
+// take a while to change so sprites flickering every other frame look
+// transparent instead of blinking
+pub struct ScreenFilter {
+    pub filter: Filter,
+    pub ghosting: bool,
+    // Last frame shown, before upscaling
+    previous: Option<RGBAFrame>,
+}
+
+impl ScreenFilter {
+    pub fn new(filter: Filter, ghosting: bool) -> Self {
+        ScreenFilter { filter, ghosting, previous: None }
+    }
+
+    pub fn apply(&mut self, frame: &RGBAFrame) -> RGBAFrame {
+        if !self.ghosting {
+            self.previous = None;
+            return self.filter.apply(frame);
+        }
+
+        // Half of what was on screen remains, the size changes when the
+        // SGB border comes and goes
+        let blended = match &self.previous {
+            Some(previous) if previous.width == frame.width && previous.height == frame.height => {
+                let buffer = frame.buffer.chunks(4).zip(previous.buffer.chunks(4))
+                    .flat_map(|(current, previous)| blend(current.try_into().unwrap(), previous.try_into().unwrap(), 1, 1))
+                    .collect();
+                RGBAFrame { width: frame.width, height: frame.height, buffer }
+            },
+            _ => frame.clone(),
+        };
+        let upscaled = self.filter.apply(&blended);
+        self.previous = Some(blended);
+        upscaled
+    }
+}
+
+impl Default for ScreenFilter {
+    fn default() -> Self {
+        ScreenFilter::new(Filter::Nearest, false)
+    }
+}
+
+mod tests;
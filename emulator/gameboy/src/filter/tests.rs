@@ -0,0 +1,70 @@
+#[cfg(test)]
+use crate::RGBAFrame;
+
+#[cfg(test)]
+use super::{Filter, ScreenFilter};
+
+#[cfg(test)]
+const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+#[cfg(test)]
+const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+#[cfg(test)]
+fn frame(width: u32, height: u32, pixels: &[[u8; 4]]) -> RGBAFrame {
+    RGBAFrame { width, height, buffer: pixels.concat() }
+}
+
+#[cfg(test)]
+fn pixel(frame: &RGBAFrame, x: usize, y: usize) -> [u8; 4] {
+    let offset = (y * frame.width as usize + x) * 4;
+    frame.buffer[offset..offset + 4].try_into().unwrap()
+}
+
+#[test]
+fn filter_sizes() {
+    let source = frame(2, 1, &[WHITE, BLACK]);
+    for (filter, scale) in [(Filter::Nearest, 1), (Filter::Scale2x, 2), (Filter::HQ2x, 2), (Filter::Scale3x, 3), (Filter::LCDGrid, 3)] {
+        let upscaled = filter.apply(&source);
+        assert_eq!((upscaled.width, upscaled.height), (2 * scale, scale));
+        assert_eq!(upscaled.buffer.len(), (4 * scale * scale * 2) as usize);
+        assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+    }
+    assert!("xbr".parse::<Filter>().is_err());
+    assert_eq!(Filter::LCDGrid.next(), Filter::Nearest);
+}
+
+#[test]
+fn scale2x_rounds_diagonals() {
+    // The white pixel has black on its left and below, its bottom left
+    // quarter turns black
+    let source = frame(2, 2, &[BLACK, WHITE, BLACK, BLACK]);
+    let upscaled = Filter::Scale2x.apply(&source);
+    assert_eq!(pixel(&upscaled, 2, 1), BLACK);
+    assert_eq!(pixel(&upscaled, 3, 1), WHITE);
+    assert_eq!(pixel(&upscaled, 2, 0), WHITE);
+
+    // Flat areas stay the same
+    let flat = Filter::Scale3x.apply(&frame(1, 1, &[WHITE]));
+    assert!(flat.buffer.chunks(4).all(|color| color == WHITE));
+}
+
+#[test]
+fn lcd_grid_darkens_the_gaps() {
+    let upscaled = Filter::LCDGrid.apply(&frame(1, 1, &[WHITE]));
+    assert_eq!(pixel(&upscaled, 0, 0), WHITE);
+    assert_eq!(pixel(&upscaled, 2, 0), [0xBF, 0xBF, 0xBF, 0xFF]);
+    assert_eq!(pixel(&upscaled, 1, 2), [0xBF, 0xBF, 0xBF, 0xFF]);
+}
+
+#[test]
+fn ghosting_blends_with_the_last_frame() {
+    let mut filter = ScreenFilter::new(Filter::Nearest, true);
+    assert_eq!(filter.apply(&frame(1, 1, &[WHITE])).buffer, WHITE);
+    // A sprite flickering every other frame settles in between
+    assert_eq!(filter.apply(&frame(1, 1, &[BLACK])).buffer, [0x7F, 0x7F, 0x7F, 0xFF]);
+    // A new size starts over
+    assert_eq!(filter.apply(&frame(2, 1, &[BLACK, BLACK])).buffer, [BLACK, BLACK].concat());
+
+    filter.ghosting = false;
+    assert_eq!(filter.apply(&frame(1, 1, &[WHITE])).buffer, WHITE);
+}
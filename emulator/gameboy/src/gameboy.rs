@@ -1,51 +1,101 @@
-use std::io::Error;
 use std::fmt;
 
-use crate::GameBoyFrame;
+use crate::{ColorFrame, EmulationEvent, GameBoyFrame, RGBAFrame, palette::DMGPalettes};
 
 use super::cartridge::Cartridge;
-use super::cpu::cpu::{CPU, ClockCycles};
+use super::model::Model;
+use super::rom::BootROM;
+use super::debugger::Debugger;
+use super::trace::Tracer;
+use super::error::GameBoyError;
+use super::link::{LinkCable, Disconnected};
+use super::cpu::cpu::{CPU, ClockCycles, MachineCycles};
+use super::io::dma::DMA;
 use super::io::io::IO;
 use super::io::lcd::LCD;
+use super::io::sgb::SGB;
+use super::io::serial::Serial;
+use super::io::timers::Timers;
 use super::mmu::MMU;
 use super::ppu::PPU;
 
 pub struct GameBoy {
+    pub(crate) model: Model,
     pub(crate) cpu: CPU,
     pub(crate) mmu: MMU,
     pub(crate) ppu: PPU,
     pub(crate) io: IO,
     pub(crate) cartridge: Option<Cartridge>,
-    pub(crate) serial: Option<u8>
+    pub(crate) serial: Option<u8>,
+    pub(crate) link_cable: Box<dyn LinkCable>,
+    // Events for the frontend, drained every frame
+    pub(crate) events: Vec<EmulationEvent>,
+    // Illegal opcodes return an error instead of locking up the CPU
+    pub(crate) strict: bool,
+    pub(crate) debugger: Debugger,
+    pub(crate) tracer: Option<Tracer>,
+    // LY always reads 0x90, as in the logs of Gameboy Doctor
+    pub(crate) doctor: bool,
 }
 
 impl GameBoy {
     pub fn new(cartridge: Option<Cartridge>) -> Self {
+        GameBoy::with_model(cartridge, Model::DMG, Some(BootROM::dmg()))
+    }
+
+    // Without a boot ROM the GameBoy starts at the cartridge entry point
+    pub(crate) fn with_model(cartridge: Option<Cartridge>, model: Model, bootrom: Option<BootROM>) -> Self {
+        let skip_boot = bootrom.is_none();
         let io = IO::new();
-        let mmu = MMU::new();
+        let mmu = MMU::new(bootrom);
         let cpu = CPU::new();
         let ppu = PPU::new();
 
-        GameBoy { cpu, mmu, ppu, io, cartridge, serial: None }
+        let mut gameboy = GameBoy { model, cpu, mmu, ppu, io, cartridge, serial: None, link_cable: Box::new(Disconnected), events: Vec::new(), strict: false, debugger: Debugger::default(), tracer: None, doctor: false };
+        // The CGB boot ROM runs in CGB mode whatever the cartridge
+        gameboy.io.cgb_mode = model == Model::CGB;
+        if skip_boot {
+            gameboy.skip_boot();
+        }
+        gameboy
     }
     
-    pub(crate) fn tick(&mut self) -> Result<ClockCycles, Error> {
-        let cycles = CPU::step(self)? as ClockCycles;
-
-        // if self.cpu.pc == 0x100 {
-        //     return Err(Error::new(ErrorKind::Other, "test"));
-        // }
-        
-        if let Some(data) = CPU::send_serial(self){
-            self.serial = Some(data);
-            IO::ack_sent_serial(self);
-        }else{
-            self.serial = None;
-        }
+    // Starts at the cartridge entry point with the state the boot ROM of the
+    // model leaves
+    pub(crate) fn skip_boot(&mut self) {
+        CPU::post_boot(self);
+        IO::post_boot(self);
+    }
+    
+    pub(crate) fn tick(&mut self) -> Result<ClockCycles, GameBoyError> {
+        self.serial = None;
+        // Accesses made outside the CPU, like the frontend peeking memory,
+        // do not stop the execution
+        self.debugger.stop.set(None);
+
+        // Frames are counted in LCD time, CPU clocks go twice as fast in double speed
+        let cycles = CPU::step(self)?;
+        Ok(if self.io.double_speed { cycles / 2 } else { cycles })
+    }
 
-        LCD::tick(self, cycles);
+    // The CPU drives the clock, every machine cycle it spends advances the
+    // rest of the system
+    pub(crate) fn cycle(&mut self) {
+        let cycles = ClockCycles::from(MachineCycles::One);
+        // In double speed the timers, serial and OAM DMA follow the CPU but
+        // the LCD does not, a machine cycle only lasts half the time
+        let elapsed = if self.io.double_speed { cycles / 2 } else { cycles };
+        self.cpu.cycles += cycles;
 
-        Ok(cycles)
+        // Timers and LCD are halted along with the CPU in STOP mode
+        if self.cpu.is_stopped {
+            return;
+        }
+
+        Timers::tick(self, cycles);
+        DMA::tick(self);
+        LCD::tick(self, elapsed);
+        Serial::tick(self, cycles);
     }
 
     pub(crate) fn read_serial(&self) -> Option<u8> {
@@ -56,6 +106,22 @@ impl GameBoy {
         LCD::screen_buffer(self)
     }
 
+    pub(crate) fn color_frame(&self) -> Option<ColorFrame> {
+        LCD::color_screen_buffer(self)
+    }
+
+    pub(crate) fn sgb_frame(&self) -> Option<ColorFrame> {
+        SGB::screen_buffer(self)
+    }
+
+    // The SGB screen, the CGB colors or the shades through the palettes
+    pub(crate) fn rgba_frame(&self, palettes: &DMGPalettes) -> RGBAFrame {
+        match (self.sgb_frame(), self.color_frame()) {
+            (Some(colors), _) | (None, Some(colors)) => colors.to_rgba(),
+            _ => self.frame().to_rgba(palettes),
+        }
+    }
+
     pub(crate) fn tiledata(&self) -> GameBoyFrame {
         LCD::tiledata_buffer(self)
     }
@@ -90,9 +156,9 @@ impl fmt::Display for GameBoy {
             self.cpu.sp, 
             self.cpu.pc,
             MMU::read_byte(self, self.cpu.pc), 
-            MMU::read_byte(self,self.cpu.pc+1), 
-            MMU::read_byte(self,self.cpu.pc+2), 
-            MMU::read_byte(self,self.cpu.pc+3)
+            MMU::read_byte(self, self.cpu.pc.wrapping_add(1)), 
+            MMU::read_byte(self, self.cpu.pc.wrapping_add(2)), 
+            MMU::read_byte(self, self.cpu.pc.wrapping_add(3))
             )
     }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use crate::{mmu::{Address, MMU, OAM_SIZE, ERAM_BEGIN, WRAM_BEGIN}, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
+
+// https://gbdev.io/pandocs/OAM_DMA_Transfer.html
+pub(crate) struct DMA {
+    // Last value written to FF46, the upper byte of the source address
+    pub(crate) source: u8,
+    // Next OAM byte to copy while a transfer is running
+    index: Option<usize>,
+    // The transfer begins one machine cycle after the write
+    starting: bool,
+}
+
+impl DMA {
+    pub(crate) fn new() -> Self {
+        DMA { source: 0, index: None, starting: false }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.source);
+        state.write_bool(self.index.is_some());
+        state.write_u8(self.index.unwrap_or(0) as u8);
+        state.write_bool(self.starting);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.source = state.read_u8()?;
+        let active = state.read_bool()?;
+        let index = state.read_u8()? as usize;
+        self.index = if active && index < OAM_SIZE { Some(index) } else { None };
+        self.starting = state.read_bool()?;
+        Ok(())
+    }
+
+    pub(crate) fn read(gb: &GameBoy) -> u8 {
+        gb.io.dma.source
+    }
+
+    pub(crate) fn start(gb: &mut GameBoy, value: u8) {
+        gb.io.dma.source = value;
+        gb.io.dma.starting = true;
+    }
+
+    pub(crate) fn is_active(gb: &GameBoy) -> bool {
+        gb.io.dma.index.is_some()
+    }
+
+    // One byte is copied every machine cycle
+    pub(crate) fn tick(gb: &mut GameBoy) {
+        if let Some(index) = gb.io.dma.index {
+            let mut address = ((gb.io.dma.source as Address) << 8) | index as Address;
+            // Sources above WRAM read from its echo
+            if address >= ERAM_BEGIN {
+                address = address - ERAM_BEGIN + WRAM_BEGIN;
+            }
+
+            gb.ppu.oam[index] = MMU::read_byte(gb, address);
+
+            gb.io.dma.index = if index + 1 < OAM_SIZE { Some(index + 1) } else { None };
+        }
+
+        if gb.io.dma.starting {
+            gb.io.dma.starting = false;
+            gb.io.dma.index = Some(0);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{cpu::cpu::CPU, mmu::{Address, MMU, VRAM_BEGIN}, ppu::PPU, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
+
+use super::lcd::{LCD, LCDControl};
+
+pub(crate) const HDMA1_ADDRESS: Address = 0xFF51;
+pub(crate) const HDMA2_ADDRESS: Address = 0xFF52;
+pub(crate) const HDMA3_ADDRESS: Address = 0xFF53;
+pub(crate) const HDMA4_ADDRESS: Address = 0xFF54;
+pub(crate) const HDMA5_ADDRESS: Address = 0xFF55;
+
+const BLOCK_SIZE: u16 = 0x10;
+// Machine cycles a block takes in normal speed, twice as many in double speed
+const BLOCK_MACHINE_CYCLES: usize = 8;
+
+// CGB VRAM DMA, copies blocks of 16 bytes to the current VRAM bank
+// https://gbdev.io/pandocs/CGB_Registers.html#lcd-vram-dma-transfers
+pub(crate) struct HDMA {
+    source: Address,
+    // Offset in VRAM
+    destination: Address,
+    // Blocks left to copy, kept after a cancel for HDMA5 to report them
+    blocks: u8,
+    active: bool,
+    // HBlank DMA copies a block every HBlank, general purpose DMA all of them at once
+    hblank: bool,
+    // A block is due, the CPU waits until it is copied
+    pending: bool,
+}
+
+impl HDMA {
+    pub(crate) fn new() -> Self {
+        HDMA { source: 0, destination: 0, blocks: 0, active: false, hblank: false, pending: false }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.source);
+        state.write_u16(self.destination);
+        state.write_u8(self.blocks);
+        state.write_bool(self.active);
+        state.write_bool(self.hblank);
+        state.write_bool(self.pending);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.source = state.read_u16()?;
+        self.destination = state.read_u16()?;
+        self.blocks = state.read_u8()?;
+        self.active = state.read_bool()?;
+        self.hblank = state.read_bool()?;
+        self.pending = state.read_bool()?;
+        Ok(())
+    }
+
+    pub(crate) fn read_byte(gb: &GameBoy, address: Address) -> u8 {
+        let hdma = &gb.io.hdma;
+        match address {
+            // Bit 7 is clear while a transfer is running, the rest is the
+            // number of blocks left minus one
+            HDMA5_ADDRESS if hdma.active => (hdma.blocks - 1) & 0x7F,
+            HDMA5_ADDRESS if hdma.blocks > 0 => 0x80 | (hdma.blocks - 1),
+            // Source and destination are write only
+            _ => 0xFF,
+        }
+    }
+
+    pub(crate) fn write_byte(gb: &mut GameBoy, address: Address, value: u8) {
+        let hdma = &mut gb.io.hdma;
+        match address {
+            HDMA1_ADDRESS => hdma.source = (hdma.source & 0x00FF) | (value as Address) << 8,
+            // The lower 4 bits are ignored, blocks are aligned
+            HDMA2_ADDRESS => hdma.source = (hdma.source & 0xFF00) | (value & 0xF0) as Address,
+            // The destination is always in VRAM
+            HDMA3_ADDRESS => hdma.destination = (hdma.destination & 0x00FF) | ((value & 0x1F) as Address) << 8,
+            HDMA4_ADDRESS => hdma.destination = (hdma.destination & 0xFF00) | (value & 0xF0) as Address,
+            _ => HDMA::start(gb, value),
+        }
+    }
+
+    fn start(gb: &mut GameBoy, value: u8) {
+        let lcd_on = LCD::read_control(gb, LCDControl::Power);
+        let hdma = &mut gb.io.hdma;
+        let hblank = value & 0x80 != 0;
+
+        // Clearing bit 7 during an HBlank DMA stops it
+        if hdma.active && hdma.hblank && !hblank {
+            hdma.active = false;
+            hdma.pending = false;
+            return;
+        }
+
+        hdma.blocks = (value & 0x7F) + 1;
+        hdma.active = true;
+        hdma.hblank = hblank;
+        // With the LCD off there is no HBlank, the first block is copied right away
+        hdma.pending = !hblank || !lcd_on;
+    }
+
+    // Called by the LCD when it enters HBlank
+    pub(crate) fn hblank(gb: &mut GameBoy) {
+        let hdma = &mut gb.io.hdma;
+        if hdma.active && hdma.hblank {
+            hdma.pending = true;
+        }
+    }
+
+    pub(crate) fn is_pending(gb: &GameBoy) -> bool {
+        gb.io.hdma.pending
+    }
+
+    // Copies one block while the CPU is halted, a general purpose DMA stays
+    // pending until every block is copied
+    pub(crate) fn transfer_block(gb: &mut GameBoy) {
+        let (source, destination) = (gb.io.hdma.source, gb.io.hdma.destination);
+        for offset in 0..BLOCK_SIZE {
+            let value = MMU::read_byte(gb, source.wrapping_add(offset));
+            PPU::write_vram(gb, VRAM_BEGIN + ((destination + offset) & 0x1FFF), value);
+        }
+
+        let machine_cycles = if gb.io.double_speed { BLOCK_MACHINE_CYCLES * 2 } else { BLOCK_MACHINE_CYCLES };
+        for _ in 0..machine_cycles {
+            CPU::internal_cycle(gb);
+        }
+
+        let hdma = &mut gb.io.hdma;
+        hdma.source = source.wrapping_add(BLOCK_SIZE);
+        hdma.destination = (destination + BLOCK_SIZE) & 0x1FF0;
+        hdma.blocks -= 1;
+        hdma.active = hdma.blocks > 0;
+        hdma.pending = hdma.active && !hdma.hblank;
+    }
+}
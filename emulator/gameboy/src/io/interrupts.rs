@@ -1,4 +1,4 @@
-use crate::{mmu::Address, gameboy::GameBoy};
+use crate::{mmu::Address, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
 
 const VBLANK_INT_HANDLER: Address = 0x0040;
 const LCD_INT_HANDLER: Address = 0x0048;
@@ -17,6 +17,17 @@ impl Interrupts {
         Self { interrupt_enable: 0x0, interrupt_flag: 0xe0 }
     }
 
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.interrupt_enable);
+        state.write_u8(self.interrupt_flag);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.interrupt_enable = state.read_u8()?;
+        self.interrupt_flag = state.read_u8()?;
+        Ok(())
+    }
+
     pub(crate) fn write_enable(gb: &mut GameBoy, value: u8) {
         gb.io.interrupts.interrupt_enable = value;
     }
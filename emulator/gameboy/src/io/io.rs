@@ -1,6 +1,6 @@
-use crate::{mmu::{Address, IO_SIZE, IO_BEGIN, MMU}, gameboy::GameBoy};
+use crate::{mmu::{Address, IO_SIZE, IO_BEGIN, INTERRUPT_ENABLE_ADDRESS, MMU}, model::Model, ppu::PPU, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
 
-use super::{interrupts::{Interruption, Interrupts}, lcd::LCD, timers::Timers, joypad::Joypad};
+use super::{interrupts::Interrupts, lcd::{LCD, LCD_OAMDMA_ADDRESS}, timers::Timers, joypad::Joypad, dma::DMA, hdma::{HDMA, HDMA1_ADDRESS, HDMA5_ADDRESS}, serial::Serial, palettes::{ColorPalettes, BCPS_ADDRESS, OCPD_ADDRESS}, sgb::SGB};
 
 pub(crate) const JOYPAD_INPUT_ADDRESS: Address = 0xFF00;
 pub(crate) const SERIAL_DATA_ADDRESS: Address = 0xFF01;
@@ -14,16 +14,47 @@ pub(crate) const TAC_ADDRESS: Address = 0xFF07;
 pub(crate) const LCD_BEGIN: Address = 0xFF40;
 pub(crate) const LCD_END: Address = 0xFF4B;
 
+pub(crate) const KEY0_ADDRESS: Address = 0xFF4C;
+pub(crate) const KEY1_ADDRESS: Address = 0xFF4D;
+pub(crate) const VBK_ADDRESS: Address = 0xFF4F;
+
 pub(crate) const BOOT_SWITCH_ADDRESS: Address = 0xFF50;
 
+pub(crate) const SVBK_ADDRESS: Address = 0xFF70;
+
 pub(crate) const INTERRUPT_FLAG_ADDRESS: Address = 0xFF0F;
 
+// Hardware registers as the DMG boot ROM leaves them, see IO::set_register.
+// The CGB registers read 0xFF when CGB mode is off
+// https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers
+const POST_BOOT_REGISTERS: [(Address, u8); 51] = [
+    (0xFF00, 0xCF), (0xFF01, 0x00), (0xFF02, 0x7E), (0xFF05, 0x00), (0xFF06, 0x00), (0xFF07, 0xF8),
+    (0xFF0F, 0xE1), (0xFF10, 0x80), (0xFF11, 0xBF), (0xFF12, 0xF3), (0xFF13, 0xFF), (0xFF14, 0xBF),
+    (0xFF16, 0x3F), (0xFF17, 0x00), (0xFF18, 0xFF), (0xFF19, 0xBF), (0xFF1A, 0x7F), (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F), (0xFF1D, 0xFF), (0xFF1E, 0xBF), (0xFF20, 0xFF), (0xFF21, 0x00), (0xFF22, 0x00),
+    (0xFF23, 0xBF), (0xFF24, 0x77), (0xFF25, 0xF3), (0xFF26, 0xF1), (0xFF40, 0x91), (0xFF41, 0x85),
+    (0xFF42, 0x00), (0xFF43, 0x00), (0xFF45, 0x00), (0xFF46, 0xFF), (0xFF47, 0xFC), (0xFF4A, 0x00),
+    (0xFF4B, 0x00), (0xFF4D, 0xFF), (0xFF4F, 0xFF), (0xFF51, 0xFF), (0xFF52, 0xFF), (0xFF53, 0xFF),
+    (0xFF54, 0xFF), (0xFF55, 0xFF), (0xFF56, 0xFF), (0xFF68, 0xFF), (0xFF69, 0xFF), (0xFF6A, 0xFF),
+    (0xFF6B, 0xFF), (0xFF70, 0xFF), (0xFFFF, 0x00),
+];
 
 pub(crate) struct IO {
     pub(crate) interrupts: Interrupts,
     pub(crate) lcd: LCD,
     pub(crate) timers: Timers,
     pub(crate) joypad: Joypad,
+    pub(crate) dma: DMA,
+    pub(crate) hdma: HDMA,
+    pub(crate) serial: Serial,
+    pub(crate) palettes: ColorPalettes,
+    pub(crate) sgb: SGB,
+    // CGB features are enabled, a CGB falls back to the DMG ones for
+    // cartridges that do not support them
+    pub(crate) cgb_mode: bool,
+    // CGB speed switch (KEY1)
+    pub(crate) double_speed: bool,
+    speed_switch_armed: bool,
     data: [u8; IO_SIZE],
 }
 
@@ -34,17 +65,65 @@ impl IO {
              lcd: LCD::new(),
              timers: Timers::new(),
              joypad: Joypad::new(),
+             dma: DMA::new(),
+             hdma: HDMA::new(),
+             serial: Serial::new(),
+             palettes: ColorPalettes::new(),
+             sgb: SGB::new(),
+             cgb_mode: false,
+             double_speed: false,
+             speed_switch_armed: false,
              data:[0; IO_SIZE] 
         }
     }
 
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        self.interrupts.save_state(state);
+        self.lcd.save_state(state);
+        self.timers.save_state(state);
+        self.joypad.save_state(state);
+        self.dma.save_state(state);
+        self.hdma.save_state(state);
+        self.serial.save_state(state);
+        self.palettes.save_state(state);
+        self.sgb.save_state(state);
+        state.write_bool(self.cgb_mode);
+        state.write_bool(self.double_speed);
+        state.write_bool(self.speed_switch_armed);
+        state.write_bytes(&self.data);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.interrupts.load_state(state)?;
+        self.lcd.load_state(state)?;
+        self.timers.load_state(state)?;
+        self.joypad.load_state(state)?;
+        self.dma.load_state(state)?;
+        self.hdma.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.palettes.load_state(state)?;
+        self.sgb.load_state(state)?;
+        self.cgb_mode = state.read_bool()?;
+        self.double_speed = state.read_bool()?;
+        self.speed_switch_armed = state.read_bool()?;
+        state.read_bytes(&mut self.data)
+    }
+
     pub(crate) fn read_byte(gb: &GameBoy, address: Address) -> u8 {
         match address {
             JOYPAD_INPUT_ADDRESS => Joypad::read(gb),
+            SERIAL_DATA_ADDRESS => Serial::read_data(gb),
+            SERIAL_CONTROL_ADDRESS => Serial::read_control(gb),
+            LCD_OAMDMA_ADDRESS => DMA::read(gb),
             LCD_BEGIN ..= LCD_END => LCD::read_byte(gb, address),
             INTERRUPT_FLAG_ADDRESS => Interrupts::read_flag(gb),
             // DIV value is 8 upper bits
-            DIV_ADDRESS => IO::get_div_register(gb),
+            DIV_ADDRESS => Timers::read_div(gb),
+            KEY1_ADDRESS if gb.io.cgb_mode => IO::read_key1(gb),
+            VBK_ADDRESS if gb.io.cgb_mode => PPU::read_vram_bank(gb),
+            HDMA1_ADDRESS ..= HDMA5_ADDRESS if gb.io.cgb_mode => HDMA::read_byte(gb, address),
+            SVBK_ADDRESS if gb.io.cgb_mode => MMU::read_wram_bank(gb),
+            BCPS_ADDRESS ..= OCPD_ADDRESS if gb.io.cgb_mode => ColorPalettes::read_byte(gb, address),
             _ => gb.io.data[(address - IO_BEGIN) as usize]
         }
     }
@@ -52,10 +131,28 @@ impl IO {
     pub(crate) fn write_byte(gb: &mut GameBoy, address: Address, value: u8) {
         match address {
             JOYPAD_INPUT_ADDRESS => Joypad::write(gb, value),
-            DIV_ADDRESS => {
-                // Writing DIV reset it
-                gb.io.data[(DIV_ADDRESS - IO_BEGIN) as usize] = 0;
+            SERIAL_DATA_ADDRESS => Serial::write_data(gb, value),
+            SERIAL_CONTROL_ADDRESS => Serial::write_control(gb, value),
+            // Writing DIV reset it
+            DIV_ADDRESS => Timers::reset_div(gb),
+            TAC_ADDRESS => Timers::write_tac(gb, value),
+            TIMA_ADDRESS => {
+                // Writing TIMA while it is waiting for the reload cancels it
+                gb.io.data[(address - IO_BEGIN) as usize] = value;
+                gb.io.timers.tima_overflow = false;
             },
+            LCD_OAMDMA_ADDRESS => DMA::start(gb, value),
+            KEY1_ADDRESS if gb.io.cgb_mode => {
+                gb.io.speed_switch_armed = (value & 0b1) != 0;
+            },
+            // The CGB boot ROM selects the DMG compatibility mode before unmapping itself
+            KEY0_ADDRESS if gb.model == Model::CGB && MMU::is_boot_rom_mapped(gb) => {
+                gb.io.cgb_mode = value & 0b100 == 0;
+            },
+            VBK_ADDRESS if gb.io.cgb_mode => PPU::write_vram_bank(gb, value),
+            HDMA1_ADDRESS ..= HDMA5_ADDRESS if gb.io.cgb_mode => HDMA::write_byte(gb, address, value),
+            SVBK_ADDRESS if gb.io.cgb_mode => MMU::write_wram_bank(gb, value),
+            BCPS_ADDRESS ..= OCPD_ADDRESS if gb.io.cgb_mode => ColorPalettes::write_byte(gb, address, value),
             LCD_BEGIN ..= LCD_END => LCD::write_byte(gb, address, value),
             BOOT_SWITCH_ADDRESS => {
                 gb.io.data[(address - IO_BEGIN) as usize] = value;
@@ -70,26 +167,60 @@ impl IO {
         }
     }
 
-    pub(crate) fn serial_control_clear(gb: &mut GameBoy) {
-        // Turn off bit 7
-        gb.io.data[(SERIAL_CONTROL_ADDRESS - IO_BEGIN) as usize] = gb.io.data[(SERIAL_CONTROL_ADDRESS - IO_BEGIN) as usize] & 0b01111111;
+    pub(crate) fn post_boot(gb: &mut GameBoy) {
+        for (address, value) in POST_BOOT_REGISTERS.iter().chain(gb.model.post_boot_io_registers()) {
+            IO::set_register(gb, *address, *value);
+        }
+        gb.io.timers.system_counter = gb.model.post_boot_system_counter(gb.cartridge.as_ref());
+        if gb.model == Model::CGB {
+            gb.io.cgb_mode = gb.cartridge.as_ref().is_some_and(|cartridge| cartridge.supports_cgb());
+            ColorPalettes::post_boot(gb);
+        }
+        // The last thing the boot ROM does is unmapping itself
+        IO::write_byte(gb, BOOT_SWITCH_ADDRESS, 0x01);
+    }
+
+    // Stores the register in its component without the write handlers, which
+    // would send SGB packets, start serial transfers or increment TIMA
+    fn set_register(gb: &mut GameBoy, address: Address, value: u8) {
+        match address {
+            JOYPAD_INPUT_ADDRESS => gb.io.joypad.register = 0b1100_1111 | (value & 0x30),
+            SERIAL_DATA_ADDRESS => Serial::write_data(gb, value),
+            SERIAL_CONTROL_ADDRESS => gb.io.serial.control = value & 0b1000_0001,
+            INTERRUPT_FLAG_ADDRESS => Interrupts::write_flag(gb, value),
+            INTERRUPT_ENABLE_ADDRESS => Interrupts::write_enable(gb, value),
+            LCD_OAMDMA_ADDRESS => gb.io.dma.source = value,
+            // Plain stores, nothing happens until the PPU reads them
+            LCD_BEGIN ..= LCD_END => LCD::write_byte(gb, address, value),
+            _ => gb.io.data[(address - IO_BEGIN) as usize] = value,
+        }
     }
 
     pub(crate) fn get_tac_register(gb: &GameBoy) -> u8 {
         gb.io.data[(TAC_ADDRESS - IO_BEGIN) as usize]
     }
 
-    pub(crate) fn get_div_register(gb: &GameBoy) -> u8 {
-        gb.io.data[(DIV_ADDRESS - IO_BEGIN) as usize]
+    pub(crate) fn set_tac_register(gb: &mut GameBoy, value: u8) {
+        gb.io.data[(TAC_ADDRESS - IO_BEGIN) as usize] = value;
     }
 
     pub(crate) fn get_tma_register(gb: &GameBoy) -> u8 {
         gb.io.data[(TMA_ADDRESS - IO_BEGIN) as usize]
     }
 
-    pub(crate) fn inc_div(gb: &mut GameBoy) {
-        let div = gb.io.data[(DIV_ADDRESS - IO_BEGIN) as usize];
-        gb.io.data[(DIV_ADDRESS - IO_BEGIN) as usize] = div.wrapping_add(1);
+    // Bit 7 is the current speed and bit 0 arms the switch for the next STOP
+    fn read_key1(gb: &GameBoy) -> u8 {
+        ((gb.io.double_speed as u8) << 7) | 0b0111_1110 | (gb.io.speed_switch_armed as u8)
+    }
+
+    // Only a CGB in CGB mode can switch its speed, STOP always stops a DMG
+    pub(crate) fn speed_switch_armed(gb: &GameBoy) -> bool {
+        gb.io.cgb_mode && gb.io.speed_switch_armed
+    }
+
+    pub(crate) fn switch_speed(gb: &mut GameBoy) {
+        gb.io.double_speed = !gb.io.double_speed;
+        gb.io.speed_switch_armed = false;
     }
 
     pub(crate) fn inc_tima(gb: &mut GameBoy) -> bool {
@@ -103,28 +234,4 @@ impl IO {
         let tma: u8 = IO::get_tma_register(gb);
         gb.io.data[(TIMA_ADDRESS - IO_BEGIN) as usize] = tma;
     }
-
-    pub(crate) fn ack_sent_serial(gb: &mut GameBoy){
-        Interrupts::turnon(gb, Interruption::Serial);
-        IO::serial_control_clear(gb);
-    }    
-}
-
-#[derive(Debug)]
-pub(crate) enum SerialTransferMode {
-    NoTransfer,
-    TransferExternalClock,
-    TransferInternalClock
 }
-
-impl SerialTransferMode {
-    pub(crate) fn parse_from_byte(byte: u8) -> SerialTransferMode {
-        if byte == 0x81 {
-            SerialTransferMode::TransferInternalClock
-        }else if byte == 0x80 {
-            SerialTransferMode::TransferExternalClock
-        }else{
-            SerialTransferMode::NoTransfer
-        }
-    }
-}
\ No newline at end of file
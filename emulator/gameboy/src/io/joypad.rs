@@ -1,10 +1,10 @@
-use crate::{gameboy::GameBoy, Button};
+use crate::{gameboy::GameBoy, io::sgb::SGB, error::GameBoyError, savestate::{StateReader, StateWriter}, Button};
 
 #[derive(Debug)]
 pub(crate) struct Joypad {
     // 0x20 => arrow selector (bit 5 of 0xFF00)
     // 0x10 => buttons selector (bit 4 of 0xFF00)
-    register: u8,
+    pub(crate) register: u8,
     state: JoypadState,
 }
 
@@ -38,34 +38,103 @@ impl Joypad {
         }
     }
 
+    // Pressed buttons belong to the host, only the selection is restored
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.register = state.read_u8()?;
+        Ok(())
+    }
+
     // Depending on selector we return a set of button states as u8
-    // http://imrannazar.com/GameBoy-Emulation-in-JavaScript:-Input
+    // https://gbdev.io/pandocs/Joypad_Input.html
     pub(crate) fn read(gb: &GameBoy) -> u8 {
         let jp = &gb.io.joypad;
         let selector = jp.register & 0x30;
-        let mut result = selector | 0b1100_0000;
-        // Buttons states are negated because 0 is interpreted as pressed and 1 as released
-        if selector & 0x20 != 0 {
-            let buttons: u8 =   ((!jp.state.down as u8) << 3) + 
-                                ((!jp.state.up as u8) << 2) + 
-                                ((!jp.state.left as u8) << 1) + 
-                                ((!jp.state.right as u8));
-            result |= buttons & 0x0F;      
-        }else if selector & 0x10 != 0 {
-            let buttons: u8 =   ((!jp.state.start as u8) << 3) + 
-                                ((!jp.state.select as u8) << 2) + 
-                                ((!jp.state.b as u8) << 1) + 
-                                ((!jp.state.a as u8));
-            result |= buttons & 0x0F;
-        }else if selector == 0 {
-            result |= 0x0F;
+        let mut result = selector | 0b1100_0000 | 0x0F;
+        // With several SGB joypads, deselecting both lines reads the current
+        // one, only the first is connected to the host
+        // https://gbdev.io/pandocs/SGB_Command_Multiplayer.html
+        let sgb = &gb.io.sgb;
+        if sgb.players > 1 && selector == 0x30 {
+            return result - sgb.player;
+        }
+        if sgb.player != 0 {
+            return result;
+        }
+        // Selectors and buttons states are negated because 0 is interpreted as selected/pressed
+        if selector & 0x10 == 0 {
+            let buttons: u8 =   ((jp.state.down as u8) << 3) + 
+                                ((jp.state.up as u8) << 2) + 
+                                ((jp.state.left as u8) << 1) + 
+                                (jp.state.right as u8);
+            result &= !buttons;      
+        }
+        if selector & 0x20 == 0 {
+            let buttons: u8 =   ((jp.state.start as u8) << 3) + 
+                                ((jp.state.select as u8) << 2) + 
+                                ((jp.state.b as u8) << 1) + 
+                                (jp.state.a as u8);
+            result &= !buttons;
         }
 
         result
     }
 
+    // Some selected button is pressed, used to leave STOP mode
+    pub(crate) fn some_line_low(gb: &GameBoy) -> bool {
+        Joypad::read(gb) & 0x0F != 0x0F
+    }
+
     pub(crate) fn write(gb: &mut GameBoy, value: u8) {
         gb.io.joypad.register = 0b1100_1111 | (value & 0x30);
+        if SGB::is_sgb(gb) {
+            SGB::write_joypad(gb, value & 0x30);
+        }
+    }
+
+    // Pressed buttons as a mask: A, B, Select, Start, Right, Left, Up, Down
+    // from bit 0 to 7, the format of the movie files
+    pub(crate) fn buttons(gb: &GameBoy) -> u8 {
+        let state = &gb.io.joypad.state;
+        (state.a as u8) |
+        (state.b as u8) << 1 |
+        (state.select as u8) << 2 |
+        (state.start as u8) << 3 |
+        (state.right as u8) << 4 |
+        (state.left as u8) << 5 |
+        (state.up as u8) << 6 |
+        (state.down as u8) << 7
+    }
+
+    // Returns true if a selected line went low, which requests the joypad
+    // interrupt
+    // https://gbdev.io/pandocs/Interrupt_Sources.html#int-60--joypad-interrupt
+    pub(crate) fn set_buttons(gb: &mut GameBoy, buttons: u8) -> bool {
+        let lines = Joypad::read(gb);
+        let state = &mut gb.io.joypad.state;
+        state.a = buttons & 0b00000001 > 0;
+        state.b = buttons & 0b00000010 > 0;
+        state.select = buttons & 0b00000100 > 0;
+        state.start = buttons & 0b00001000 > 0;
+        state.right = buttons & 0b00010000 > 0;
+        state.left = buttons & 0b00100000 > 0;
+        state.up = buttons & 0b01000000 > 0;
+        state.down = buttons & 0b10000000 > 0;
+        Joypad::line_went_low(gb, lines)
+    }
+
+    // Same as set_buttons for a single button
+    pub(crate) fn press(gb: &mut GameBoy, b: Button) -> bool {
+        let lines = Joypad::read(gb);
+        Joypad::button_pressed(gb, b);
+        Joypad::line_went_low(gb, lines)
+    }
+
+    fn line_went_low(gb: &GameBoy, lines: u8) -> bool {
+        lines & !Joypad::read(gb) & 0x0F != 0
     }
 
     pub(crate) fn button_pressed(gb: &mut GameBoy, b: Button) {
@@ -1,6 +1,6 @@
-use crate::{mmu::{Address, VRAM_BEGIN, MMU}, cpu::cpu::ClockCycles, gameboy::GameBoy, ppu::{PPU, BGMAP0_ADDRESS, BGMAP1_ADDRESS, TilePixelValue}, SCREEN_WIDTH, SCREEN_HEIGHT, TILEDATA_HEIGHT, TILEDATA_WIDTH, BACKGROUND_HEIGHT, BACKGROUND_WIDTH, ColoredPixel, GameBoyFrame};
+use crate::{error::GameBoyError, savestate::{StateReader, StateWriter}, mmu::{Address, VRAM_BEGIN, MMU}, cpu::cpu::ClockCycles, gameboy::GameBoy, ppu::{PPU, BGMAP0_ADDRESS, BGMAP1_ADDRESS, TILES_PER_BANK, TilePixelValue}, SCREEN_WIDTH, SCREEN_HEIGHT, TILEDATA_HEIGHT, TILEDATA_WIDTH, BACKGROUND_HEIGHT, BACKGROUND_WIDTH, ColoredPixel, ColorFrame, GameBoyFrame, PixelLayer};
 
-use super::interrupts::{Interrupts, Interruption};
+use super::{interrupts::{Interrupts, Interruption}, hdma::HDMA, palettes::ColorPalettes, sgb::SGB};
 
 pub(crate) const TILE_SIZE: u32 = 8;
 
@@ -26,6 +26,8 @@ pub(crate) const LCD_OBP1_ADDRESS: Address = 0xFF49;
 pub(crate) const LCD_WY_ADDRESS: Address = 0xFF4A;
 pub(crate) const LCD_WX_ADDRESS: Address = 0xFF4B;
 
+const OBJECTS_PER_LINE: usize = 10;
+
 pub(crate) const CLOCKS_SEARCHING_OAM: u16 = 80;
 pub(crate) const CLOCKS_TRANSFERING: u16 = 172;
 pub(crate) const CLOCKS_HBLANK: u16 = 204;
@@ -36,18 +38,52 @@ pub(crate) struct LCD {
     clock: u16,
     mode: LCDMode,
     scanline: u8,
+    lyc: u8,
+    // Interrupt sources selected in STAT
+    stat: u8,
+    // The STAT interrupt is requested on a rising edge of this line
+    stat_line: bool,
     scy: u8,
     scx: u8,
+    wy: u8,
+    wx: u8,
+    // Line of the window to draw next, it only moves on lines showing it
+    window_line: u8,
     bgpalette: Palette,
+    obp0: Palette,
+    obp1: Palette,
     // Renders
     screen: GameBoyFrame,
+    // Only drawn in CGB mode
+    color_screen: ColorFrame,
     tiledata: GameBoyFrame,
     background: GameBoyFrame
 }
 
 impl GameBoyFrame {
     pub(crate) fn new(width: u32, height: u32, buffer: Vec<ColoredPixel>) -> Self {
-        GameBoyFrame { width, height, buffer }
+        let layers = vec![PixelLayer::Background; buffer.len()];
+        GameBoyFrame { width, height, buffer, layers }
+    }
+}
+
+impl std::convert::From<PixelLayer> for u8 {
+    fn from(layer: PixelLayer) -> Self {
+        match layer {
+            PixelLayer::Background => 0,
+            PixelLayer::Object0 => 1,
+            PixelLayer::Object1 => 2,
+        }
+    }
+}
+
+impl std::convert::From<u8> for PixelLayer {
+    fn from(byte: u8) -> Self {
+        match byte {
+            1 => PixelLayer::Object0,
+            2 => PixelLayer::Object1,
+            _ => PixelLayer::Background,
+        }
     }
 }
 
@@ -81,6 +117,28 @@ pub(crate) enum LCDMode {
     SearchingOAM, Transfering, HBlank, VBlank 
 }
 
+impl std::convert::From<LCDMode> for u8 {
+    fn from(mode: LCDMode) -> Self {
+        match mode {
+            LCDMode::HBlank => 0,
+            LCDMode::VBlank => 1,
+            LCDMode::SearchingOAM => 2,
+            LCDMode::Transfering => 3,
+        }
+    }
+}
+
+impl std::convert::From<u8> for LCDMode {
+    fn from(byte: u8) -> Self {
+        match byte & 0b11 {
+            0 => LCDMode::HBlank,
+            1 => LCDMode::VBlank,
+            2 => LCDMode::SearchingOAM,
+            _ => LCDMode::Transfering,
+        }
+    }
+}
+
 pub(crate) enum LCDControl {
     Power, 
     WindowTileMap, 
@@ -133,16 +191,78 @@ impl LCD {
             clock: 0, 
             mode: LCDMode::SearchingOAM , 
             scanline: 0, 
+            lyc: 0,
+            stat: 0,
+            stat_line: false,
             scy: 0, 
             scx: 0, 
+            wy: 0,
+            wx: 0,
+            window_line: 0,
             bgpalette: Palette::from(0), 
+            obp0: Palette::from(0),
+            obp1: Palette::from(0),
             screen: GameBoyFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT, vec![ColoredPixel::Black; (SCREEN_WIDTH*SCREEN_HEIGHT) as usize]),
+            color_screen: ColorFrame { width: SCREEN_WIDTH, height: SCREEN_HEIGHT, buffer: vec![0; (SCREEN_WIDTH*SCREEN_HEIGHT) as usize] },
             // For debug
             tiledata: GameBoyFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT, vec![ColoredPixel::Black; (TILEDATA_WIDTH*TILEDATA_HEIGHT) as usize]),
             background: GameBoyFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT, vec![ColoredPixel::Black; (BACKGROUND_WIDTH*BACKGROUND_HEIGHT) as usize]),
         }
     }
 
+    // Debug renders are drawn again on the next VBlank
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.control);
+        state.write_u16(self.clock);
+        state.write_u8(u8::from(self.mode.clone()));
+        state.write_u8(self.scanline);
+        state.write_u8(self.lyc);
+        state.write_u8(self.stat);
+        state.write_bool(self.stat_line);
+        state.write_u8(self.scy);
+        state.write_u8(self.scx);
+        state.write_u8(self.wy);
+        state.write_u8(self.wx);
+        state.write_u8(self.window_line);
+        state.write_u8(u8::from(self.bgpalette));
+        state.write_u8(u8::from(self.obp0));
+        state.write_u8(u8::from(self.obp1));
+        // The layer of a pixel is kept above its shade
+        for (pixel, layer) in self.screen.buffer.iter().zip(&self.screen.layers) {
+            state.write_u8(u8::from(*layer) << 2 | u8::from(*pixel));
+        }
+        for color in &self.color_screen.buffer {
+            state.write_u16(*color);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.control = state.read_u8()?;
+        self.clock = state.read_u16()?;
+        self.mode = LCDMode::from(state.read_u8()?);
+        self.scanline = state.read_u8()?;
+        self.lyc = state.read_u8()?;
+        self.stat = state.read_u8()?;
+        self.stat_line = state.read_bool()?;
+        self.scy = state.read_u8()?;
+        self.scx = state.read_u8()?;
+        self.wy = state.read_u8()?;
+        self.wx = state.read_u8()?;
+        self.window_line = state.read_u8()?;
+        self.bgpalette = Palette::from(state.read_u8()?);
+        self.obp0 = Palette::from(state.read_u8()?);
+        self.obp1 = Palette::from(state.read_u8()?);
+        for (pixel, layer) in self.screen.buffer.iter_mut().zip(self.screen.layers.iter_mut()) {
+            let byte = state.read_u8()?;
+            *pixel = ColoredPixel::from(byte);
+            *layer = PixelLayer::from(byte >> 2);
+        }
+        for color in self.color_screen.buffer.iter_mut() {
+            *color = state.read_u16()?;
+        }
+        Ok(())
+    }
+
     // https://gbdev.io/pandocs/STAT.html#stat-modes
     pub(crate) fn tick(gb: &mut GameBoy, cycles: ClockCycles) {
         gb.io.lcd.clock = gb.io.lcd.clock.wrapping_add(cycles);
@@ -159,6 +279,7 @@ impl LCD {
                     LCD::reset_clock(gb);
                     LCD::start_mode(gb, LCDMode::HBlank);
                     LCD::render_scanline(gb);
+                    HDMA::hblank(gb);
                 }
             },
             LCDMode::HBlank => {
@@ -166,9 +287,12 @@ impl LCD {
                     LCD::reset_clock(gb);
                     LCD::next_scanline(gb);
 
-                    if LCD::read_scanline(gb) == 143 {
+                    if LCD::read_scanline(gb) == SCREEN_HEIGHT as u8 {
                         Interrupts::turnon(gb, Interruption::VBlank);
                         LCD::start_mode(gb, LCDMode::VBlank);
+                        if SGB::is_sgb(gb) {
+                            SGB::render(gb);
+                        }
                         // Debug
                         LCD::render_tiledata(gb);
                         LCD::render_background(gb);
@@ -189,67 +313,224 @@ impl LCD {
                 }
             },
         }
+
+        LCD::update_stat_line(gb);
+    }
+
+    // https://gbdev.io/pandocs/STAT.html#ff41--stat-lcd-status
+    fn update_stat_line(gb: &mut GameBoy) {
+        let stat = gb.io.lcd.stat;
+        let line = (stat & 0b01000000 > 0 && LCD::coincidence(gb))
+            || match LCD::mode(gb) {
+                LCDMode::HBlank => stat & 0b00001000 > 0,
+                LCDMode::VBlank => stat & 0b00010000 > 0,
+                LCDMode::SearchingOAM => stat & 0b00100000 > 0,
+                LCDMode::Transfering => false,
+            };
+
+        if line && !gb.io.lcd.stat_line {
+            Interrupts::turnon(gb, Interruption::LCDStat);
+        }
+        gb.io.lcd.stat_line = line;
     }
 
+    fn coincidence(gb: &GameBoy) -> bool {
+        gb.io.lcd.scanline == gb.io.lcd.lyc
+    }
+
+    fn read_status(gb: &GameBoy) -> u8 {
+        // Bit 7 is unused and always reads 1
+        0b10000000 | gb.io.lcd.stat | (LCD::coincidence(gb) as u8) << 2 | u8::from(LCD::mode(gb))
+    }
+
+    // Outside CGB mode LCDC bit 0 turns the background and the window off,
+    // they show color 0 of BGP and objects always go over them
+    // https://gbdev.io/pandocs/LCDC.html#lcdc0--bg-and-window-enablepriority
     pub(crate) fn render_scanline(gb: &mut GameBoy) {
-        let bgenabled = LCD::read_control(gb, LCDControl::BGEnabled);
-        // Where is our tile map defined?
-        let background_tile_map = LCD::background_tile_map(gb);
-        //let bgaddr = LCD::read_control(gb, LCDControl::BGandWindowTileSet);
+        if gb.io.cgb_mode {
+            return LCD::render_color_scanline(gb);
+        }
 
-        let lcd = &mut gb.io.lcd;
-        let ppu = &gb.ppu;
+        let line_offset = gb.io.lcd.scanline as usize * SCREEN_WIDTH as usize;
+        let enabled = LCD::read_control(gb, LCDControl::BGEnabled);
+        let window = enabled && LCD::window_visible(gb);
+        let mut background = [TilePixelValue::Zero; SCREEN_WIDTH as usize];
 
-        let mut scan_line: [TilePixelValue; SCREEN_WIDTH as usize] = [Default::default(); SCREEN_WIDTH as usize];
-        
-        if bgenabled {
-            // The x index of the current tile
-            let mut tile_x_index = lcd.scx / 8;
-
-            // The current scan line's y-offset in the entire background space is a combination
-            // of both the line inside the view port we're currently on and the amount of the view port is scrolled
-            let tile_y_index = lcd.scanline.wrapping_add(lcd.scy);
-            //println!("{}", lcd.scanline);
-            // The current tile we're on is equal to the total y offset broken up into 8 pixel chunks
-            // and multipled by the width of the entire background (i.e. 32 tiles)
-            let tile_offset = (tile_y_index as u16 / 8) * 32u16;
-
-            // Munge this so that the beginning of VRAM is index 0
-            let tile_map_begin = background_tile_map - VRAM_BEGIN;
-            // Where we are in the tile map is the beginning of the tile map
-            // plus the current tile's offset
-            let tile_map_offset = (tile_map_begin + tile_offset) as usize;
-
-            // When line and scrollY are zero we just start at the top of the tile
-            // If they're non-zero we must index into the tile cycling through 0 - 7
-            let row_y_offset = tile_y_index % 8;
-            let mut pixel_x_index = lcd.scx % 8;
-
-            let mut canvas_buffer_offset = lcd.scanline as usize * SCREEN_WIDTH as usize;
-            // Start at the beginning of the line and go pixel by pixel
-            for line_x in 0..SCREEN_WIDTH as usize {
-                // Grab the tile index specified in the tile map
-                let tile_index = ppu.vram[tile_map_offset + tile_x_index as usize];
-
-                let tile_value = ppu.tile_set[tile_index as usize][row_y_offset as usize]
-                    [pixel_x_index as usize];
-                let color: ColoredPixel = lcd.bgpalette.apply(tile_value);
-
-                lcd.screen.buffer[canvas_buffer_offset] = color;
-                canvas_buffer_offset += 1;
-                scan_line[line_x] = tile_value;
-                // Loop through the 8 pixels within the tile
-                pixel_x_index = (pixel_x_index + 1) % 8;
-
-                // Check if we've fully looped through the tile
-                if pixel_x_index == 0 {
-                    // Now increase the tile x_offset by 1
-                    tile_x_index = tile_x_index + 1;
+        if enabled {
+            for (line_x, value) in background.iter_mut().enumerate() {
+                let (map_index, x, y) = LCD::background_position(gb, line_x, window);
+                let tile_index = LCD::background_tile_index(gb, gb.ppu.vram[0][map_index]);
+                *value = gb.ppu.tile_set[tile_index][y % 8][x % 8];
+            }
+        }
+        for (line_x, value) in background.iter().enumerate() {
+            gb.io.lcd.screen.buffer[line_offset + line_x] = gb.io.lcd.bgpalette.apply(*value);
+            gb.io.lcd.screen.layers[line_offset + line_x] = PixelLayer::Background;
+        }
+
+        if window {
+            gb.io.lcd.window_line = gb.io.lcd.window_line.wrapping_add(1);
+        }
+        if LCD::read_control(gb, LCDControl::SpritesEnabled) {
+            LCD::render_objects(gb, &background);
+        }
+    }
+
+    // Up to 10 objects per line, the one with the smallest X is drawn over
+    // the others and OAM order breaks ties. Background colors 1-3 hide the
+    // objects asking for the background priority, bit 4 picks OBP0 or OBP1
+    // https://gbdev.io/pandocs/OAM.html#drawing-priority
+    fn render_objects(gb: &mut GameBoy, background: &[TilePixelValue]) {
+        let line_offset = gb.io.lcd.scanline as usize * SCREEN_WIDTH as usize;
+        let mut objects = LCD::line_objects(gb);
+        objects.sort_by_key(|object| object[1]);
+
+        for (line_x, background_value) in background.iter().enumerate() {
+            let pixel = objects.iter().find_map(|object| {
+                let (tile, row, column) = LCD::object_position(gb, object, line_x)?;
+                let value = gb.ppu.tile_set[tile][row][column];
+                (!matches!(value, TilePixelValue::Zero)).then_some((value, object[3]))
+            });
+
+            if let Some((value, attributes)) = pixel {
+                if attributes & 0b1000_0000 > 0 && !matches!(background_value, TilePixelValue::Zero) {
+                    continue;
                 }
+                let (palette, layer) = if attributes & 0b0001_0000 > 0 {
+                    (gb.io.lcd.obp1, PixelLayer::Object1)
+                } else {
+                    (gb.io.lcd.obp0, PixelLayer::Object0)
+                };
+                gb.io.lcd.screen.buffer[line_offset + line_x] = palette.apply(value);
+                gb.io.lcd.screen.layers[line_offset + line_x] = layer;
+            }
+        }
+    }
+
+    // In CGB mode every tile of the map has attributes in VRAM bank 1 and the
+    // background is always drawn, LCDC bit 0 only matters for objects
+    // https://gbdev.io/pandocs/Tile_Maps.html#bg-map-attributes-cgb-mode-only
+    fn render_color_scanline(gb: &mut GameBoy) {
+        let line_offset = gb.io.lcd.scanline as usize * SCREEN_WIDTH as usize;
+        let window = LCD::window_visible(gb);
+        // Color and priority of every background pixel, objects go over them
+        let mut background = [(0, false); SCREEN_WIDTH as usize];
+
+        for (line_x, pixel) in background.iter_mut().enumerate() {
+            let (map_index, x, y) = LCD::background_position(gb, line_x, window);
+            let attributes = gb.ppu.vram[1][map_index];
+
+            let bank = ((attributes >> 3) & 0b1) as usize;
+            let row = if attributes & 0b0100_0000 > 0 { 7 - y % 8 } else { y % 8 };
+            let column = if attributes & 0b0010_0000 > 0 { 7 - x % 8 } else { x % 8 };
+            let tile_index = LCD::background_tile_index(gb, gb.ppu.vram[0][map_index]);
+            let tile_value = gb.ppu.tile_set[bank * TILES_PER_BANK + tile_index][row][column] as u8;
+
+            let color = ColorPalettes::background_color(gb, attributes & 0b111, tile_value);
+            gb.io.lcd.color_screen.buffer[line_offset + line_x] = color;
+            gb.io.lcd.screen.buffer[line_offset + line_x] = ColoredPixel::from(tile_value);
+            gb.io.lcd.screen.layers[line_offset + line_x] = PixelLayer::Background;
+            *pixel = (tile_value, attributes & 0b1000_0000 > 0);
+        }
+
+        if window {
+            gb.io.lcd.window_line = gb.io.lcd.window_line.wrapping_add(1);
+        }
+        if LCD::read_control(gb, LCDControl::SpritesEnabled) {
+            LCD::render_color_objects(gb, &background);
+        }
+    }
 
+    // Up to 10 objects per line, the first ones in OAM are drawn over the
+    // others. A background color other than 0 hides an object when either
+    // of them asks for the background priority, unless LCDC bit 0 is off
+    // https://gbdev.io/pandocs/OAM.html
+    fn render_color_objects(gb: &mut GameBoy, background: &[(u8, bool)]) {
+        let line_offset = gb.io.lcd.scanline as usize * SCREEN_WIDTH as usize;
+        let background_priority = LCD::read_control(gb, LCDControl::BGEnabled);
+        let objects = LCD::line_objects(gb);
+
+        for (line_x, (background_color, background_attribute)) in background.iter().enumerate() {
+            let pixel = objects.iter().find_map(|object| {
+                let (tile, row, column) = LCD::object_position(gb, object, line_x)?;
+                let bank = ((object[3] >> 3) & 0b1) as usize;
+                let value = gb.ppu.tile_set[bank * TILES_PER_BANK + tile][row][column] as u8;
+                (value != 0).then_some((value, object[3]))
+            });
+
+            if let Some((value, attributes)) = pixel {
+                let hidden = background_priority && *background_color != 0 && (*background_attribute || attributes & 0b1000_0000 > 0);
+                if !hidden {
+                    gb.io.lcd.color_screen.buffer[line_offset + line_x] = ColorPalettes::object_color(gb, attributes & 0b111, value);
+                    gb.io.lcd.screen.buffer[line_offset + line_x] = ColoredPixel::from(value);
+                }
             }
         }
-        
+    }
+
+    // The window starts at WX - 7 on the lines from WY
+    fn window_visible(gb: &GameBoy) -> bool {
+        LCD::read_control(gb, LCDControl::WindowEnable) && gb.io.lcd.scanline >= gb.io.lcd.wy && gb.io.lcd.wx <= 166
+    }
+
+    // Index in VRAM of the map entry under a pixel of the line, with the
+    // pixel coordinates in the background or in the window
+    fn background_position(gb: &GameBoy, line_x: usize, window: bool) -> (usize, usize, usize) {
+        let window_x = gb.io.lcd.wx as usize;
+        let (tile_map, x, y) = if window && line_x + 7 >= window_x {
+            (LCD::window_tile_map(gb), line_x + 7 - window_x, gb.io.lcd.window_line as usize)
+        } else {
+            let x = (line_x + gb.io.lcd.scx as usize) % BACKGROUND_WIDTH as usize;
+            (LCD::background_tile_map(gb), x, gb.io.lcd.scanline.wrapping_add(gb.io.lcd.scy) as usize)
+        };
+        ((tile_map - VRAM_BEGIN) as usize + (y / 8) * BACKGROUND_COLS + x / 8, x, y)
+    }
+
+    // The first 10 objects of OAM on the current line, as Y, X, tile and attributes
+    fn line_objects(gb: &GameBoy) -> Vec<[u8; 4]> {
+        let scanline = gb.io.lcd.scanline as i16;
+        let height = LCD::object_height(gb);
+        gb.ppu.oam.chunks(4)
+            .map(|object| [object[0], object[1], object[2], object[3]])
+            .filter(|object| (0..height).contains(&(scanline + 16 - object[0] as i16)))
+            .take(OBJECTS_PER_LINE)
+            .collect()
+    }
+
+    // Tile, row and column of an object under a pixel of the line, after the flips
+    fn object_position(gb: &GameBoy, object: &[u8; 4], line_x: usize) -> Option<(usize, usize, usize)> {
+        let [y, x, tile, attributes] = *object;
+        let height = LCD::object_height(gb);
+        let column = line_x as i16 + 8 - x as i16;
+        if !(0..8).contains(&column) {
+            return None;
+        }
+        let mut row = gb.io.lcd.scanline as i16 + 16 - y as i16;
+        if attributes & 0b0100_0000 > 0 {
+            row = height - 1 - row;
+        }
+        let column = if attributes & 0b0010_0000 > 0 { 7 - column } else { column };
+        // The lowest bit of the tile is ignored for 8x16 objects
+        let tile = if height == 16 { tile & 0xFE } else { tile } as usize + row as usize / 8;
+        Some((tile, row as usize % 8, column as usize))
+    }
+
+    fn object_height(gb: &GameBoy) -> i16 {
+        if LCD::read_control(gb, LCDControl::SpriteSize) { 16 } else { 8 }
+    }
+
+    // With LCDC bit 4 off tiles 0-127 come from 0x9000 and 128-255 from 0x8800
+    // https://gbdev.io/pandocs/Tile_Data.html
+    fn background_tile_index(gb: &GameBoy, tile: u8) -> usize {
+        if LCD::read_control(gb, LCDControl::BGandWindowTileSet) {
+            tile as usize
+        } else {
+            (256 + tile as i8 as isize) as usize
+        }
+    }
+
+    pub(crate) fn color_screen_buffer(gb: &GameBoy) -> Option<ColorFrame> {
+        gb.io.cgb_mode.then(|| gb.io.lcd.color_screen.clone())
     }
 
     pub(crate) fn screen_buffer(gb: &GameBoy) -> GameBoyFrame {
@@ -336,6 +617,7 @@ impl LCD {
 
     pub(crate) fn reset_scanline(gb: &mut GameBoy) {
         gb.io.lcd.scanline = 0;
+        gb.io.lcd.window_line = 0;
     }
 
     pub(crate) fn background_tile_map(gb: &GameBoy) -> Address {
@@ -348,6 +630,14 @@ impl LCD {
         }
     }
 
+    pub(crate) fn window_tile_map(gb: &GameBoy) -> Address {
+        if LCD::read_control(gb, LCDControl::WindowTileMap) {
+            BGMAP1_ADDRESS
+        } else {
+            BGMAP0_ADDRESS
+        }
+    }
+
     pub(crate) fn read_control(gb: &GameBoy, parameter: LCDControl) -> bool {
         match parameter {
             LCDControl::Power               => (gb.io.lcd.control & 0b10000000) > 0, 
@@ -376,11 +666,17 @@ impl LCD {
 
     pub(crate) fn read_byte(gb: &GameBoy, address: Address) -> u8 {
         match address {
-            LCD_LY_ADDRESS => { gb.io.lcd.scanline },
+            LCD_LY_ADDRESS => if gb.doctor { 0x90 } else { gb.io.lcd.scanline },
+            LCD_LYC_ADDRESS => { gb.io.lcd.lyc },
+            LCD_STATUS_ADDRESS => { LCD::read_status(gb) },
             LCD_SCY_ADDRESS => { gb.io.lcd.scy },
             LCD_SCX_ADDRESS => { gb.io.lcd.scx },
+            LCD_WY_ADDRESS => { gb.io.lcd.wy },
+            LCD_WX_ADDRESS => { gb.io.lcd.wx },
             LCD_CONTROL_ADDRESS => { gb.io.lcd.control },
             LCD_BGPALETTE_ADDRESS => { u8::from(gb.io.lcd.bgpalette) },
+            LCD_OBP0_ADDRESS => { u8::from(gb.io.lcd.obp0) },
+            LCD_OBP1_ADDRESS => { u8::from(gb.io.lcd.obp1) },
             _ => { 0 }
         }
     }
@@ -388,10 +684,17 @@ impl LCD {
     pub(crate) fn write_byte(gb: &mut GameBoy, address: Address, value: u8) {
         match address {
             LCD_LY_ADDRESS => { gb.io.lcd.scanline = value },
+            LCD_LYC_ADDRESS => { gb.io.lcd.lyc = value },
+            // Mode and coincidence bits are read only
+            LCD_STATUS_ADDRESS => { gb.io.lcd.stat = value & 0b01111000 },
             LCD_SCY_ADDRESS => { gb.io.lcd.scy = value },
             LCD_SCX_ADDRESS => { gb.io.lcd.scx = value },
+            LCD_WY_ADDRESS => { gb.io.lcd.wy = value },
+            LCD_WX_ADDRESS => { gb.io.lcd.wx = value },
             LCD_CONTROL_ADDRESS => { gb.io.lcd.control = value },
             LCD_BGPALETTE_ADDRESS => { gb.io.lcd.bgpalette = Palette::from(value) },
+            LCD_OBP0_ADDRESS => { gb.io.lcd.obp0 = Palette::from(value) },
+            LCD_OBP1_ADDRESS => { gb.io.lcd.obp1 = Palette::from(value) },
             _ => {}
         }
     }
@@ -2,4 +2,11 @@ pub(crate) mod io;
 pub(crate) mod interrupts;
 pub mod lcd;
 pub(crate) mod timers;
-pub(crate) mod joypad;
\ No newline at end of file
+pub(crate) mod joypad;
+pub(crate) mod dma;
+pub(crate) mod hdma;
+pub(crate) mod serial;
+pub(crate) mod palettes;
+pub(crate) mod sgb;
+
+mod tests;
\ No newline at end of file
@@ -0,0 +1,102 @@
+use crate::{mmu::Address, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
+
+use super::lcd::{LCD, LCDControl, LCDMode};
+
+pub(crate) const BCPS_ADDRESS: Address = 0xFF68;
+pub(crate) const BCPD_ADDRESS: Address = 0xFF69;
+pub(crate) const OCPS_ADDRESS: Address = 0xFF6A;
+pub(crate) const OCPD_ADDRESS: Address = 0xFF6B;
+
+// 8 palettes of 4 colors, 2 bytes per color
+const PALETTE_RAM_SIZE: usize = 64;
+
+const AUTO_INCREMENT: u8 = 0b1000_0000;
+const INDEX_MASK: u8 = 0b0011_1111;
+
+// CGB color palette RAM for the background and the objects
+// https://gbdev.io/pandocs/Palettes.html#lcd-color-palettes-cgb-only
+pub(crate) struct ColorPalettes {
+    background: [u8; PALETTE_RAM_SIZE],
+    objects: [u8; PALETTE_RAM_SIZE],
+    // BCPS and OCPS, the byte accessed through the data register
+    background_index: u8,
+    objects_index: u8,
+}
+
+impl ColorPalettes {
+    pub(crate) fn new() -> Self {
+        ColorPalettes { background: [0; PALETTE_RAM_SIZE], objects: [0; PALETTE_RAM_SIZE], background_index: 0, objects_index: 0 }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.background);
+        state.write_bytes(&self.objects);
+        state.write_u8(self.background_index);
+        state.write_u8(self.objects_index);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        state.read_bytes(&mut self.background)?;
+        state.read_bytes(&mut self.objects)?;
+        self.background_index = state.read_u8()?;
+        self.objects_index = state.read_u8()?;
+        Ok(())
+    }
+
+    // The CGB boot ROM leaves every background color white
+    pub(crate) fn post_boot(gb: &mut GameBoy) {
+        gb.io.palettes.background = [0xFF; PALETTE_RAM_SIZE];
+    }
+
+    pub(crate) fn read_byte(gb: &GameBoy, address: Address) -> u8 {
+        let palettes = &gb.io.palettes;
+        match address {
+            // Bit 6 is unused and always reads 1
+            BCPS_ADDRESS => palettes.background_index | 0b0100_0000,
+            OCPS_ADDRESS => palettes.objects_index | 0b0100_0000,
+            _ if ColorPalettes::locked(gb) => 0xFF,
+            BCPD_ADDRESS => palettes.background[(palettes.background_index & INDEX_MASK) as usize],
+            _ => palettes.objects[(palettes.objects_index & INDEX_MASK) as usize],
+        }
+    }
+
+    pub(crate) fn write_byte(gb: &mut GameBoy, address: Address, value: u8) {
+        let locked = ColorPalettes::locked(gb);
+        let palettes = &mut gb.io.palettes;
+        match address {
+            BCPS_ADDRESS => palettes.background_index = value & (AUTO_INCREMENT | INDEX_MASK),
+            OCPS_ADDRESS => palettes.objects_index = value & (AUTO_INCREMENT | INDEX_MASK),
+            BCPD_ADDRESS => ColorPalettes::write_data(&mut palettes.background, &mut palettes.background_index, value, locked),
+            _ => ColorPalettes::write_data(&mut palettes.objects, &mut palettes.objects_index, value, locked),
+        }
+    }
+
+    fn write_data(ram: &mut [u8; PALETTE_RAM_SIZE], index: &mut u8, value: u8, locked: bool) {
+        // The index still moves forward when the write is ignored
+        if !locked {
+            ram[(*index & INDEX_MASK) as usize] = value;
+        }
+        if *index & AUTO_INCREMENT != 0 {
+            *index = AUTO_INCREMENT | ((*index + 1) & INDEX_MASK);
+        }
+    }
+
+    pub(crate) fn background_color(gb: &GameBoy, palette: u8, color: u8) -> u16 {
+        ColorPalettes::color(&gb.io.palettes.background, palette, color)
+    }
+
+    pub(crate) fn object_color(gb: &GameBoy, palette: u8, color: u8) -> u16 {
+        ColorPalettes::color(&gb.io.palettes.objects, palette, color)
+    }
+
+    // 15-bit color of a palette, red in the lowest bits
+    fn color(ram: &[u8; PALETTE_RAM_SIZE], palette: u8, color: u8) -> u16 {
+        let offset = (palette as usize & 0b111) * 8 + color as usize * 2;
+        u16::from_le_bytes([ram[offset], ram[offset + 1]]) & 0x7FFF
+    }
+
+    // The PPU reads the palettes while drawing a line
+    fn locked(gb: &GameBoy) -> bool {
+        matches!(LCD::mode(gb), LCDMode::Transfering) && LCD::read_control(gb, LCDControl::Power)
+    }
+}
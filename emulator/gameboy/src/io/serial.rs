@@ -0,0 +1,135 @@
+use crate::{cpu::cpu::ClockCycles, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
+
+use super::interrupts::{Interruption, Interrupts};
+
+// Internal clock runs at 8192 Hz
+pub(crate) const CLOCKS_PER_BIT: ClockCycles = 512;
+
+// https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+pub(crate) struct Serial {
+    data: u8,
+    pub(crate) control: u8,
+    // Byte received from the partner, shifted in one bit at a time
+    incoming: u8,
+    bits_left: u8,
+    clock: ClockCycles,
+    // Our clock waits until the partner's byte is known
+    waiting: bool,
+}
+
+impl Serial {
+    pub(crate) fn new() -> Self {
+        Serial { data: 0, control: 0, incoming: 0xFF, bits_left: 0, clock: 0, waiting: false }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.data);
+        state.write_u8(self.control);
+        state.write_u8(self.incoming);
+        state.write_u8(self.bits_left);
+        state.write_u16(self.clock);
+        state.write_bool(self.waiting);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.data = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.incoming = state.read_u8()?;
+        self.bits_left = state.read_u8()?;
+        self.clock = state.read_u16()?;
+        self.waiting = state.read_bool()?;
+        Ok(())
+    }
+
+    pub(crate) fn read_data(gb: &GameBoy) -> u8 {
+        gb.io.serial.data
+    }
+
+    pub(crate) fn write_data(gb: &mut GameBoy, value: u8) {
+        gb.io.serial.data = value;
+    }
+
+    pub(crate) fn read_control(gb: &GameBoy) -> u8 {
+        // Unused bits read as 1
+        gb.io.serial.control | 0b01111110
+    }
+
+    pub(crate) fn write_control(gb: &mut GameBoy, value: u8) {
+        gb.io.serial.control = value & 0b10000001;
+
+        if !Serial::transfer_requested(gb) {
+            return;
+        }
+
+        // Reported as output, tests print their results through serial
+        gb.serial = Some(gb.io.serial.data);
+
+        if Serial::internal_clock(gb) {
+            let outgoing = gb.io.serial.data;
+            match gb.link_cable.exchange(outgoing) {
+                Some(incoming) => Serial::start(gb, incoming),
+                None => gb.io.serial.waiting = true,
+            }
+        }
+    }
+
+    fn start(gb: &mut GameBoy, incoming: u8) {
+        let serial = &mut gb.io.serial;
+        serial.incoming = incoming;
+        serial.bits_left = 8;
+        serial.clock = 0;
+        serial.waiting = false;
+    }
+
+    pub(crate) fn tick(gb: &mut GameBoy, cycles: ClockCycles) {
+        if !Serial::transfer_requested(gb) {
+            return;
+        }
+
+        if Serial::internal_clock(gb) {
+            if gb.io.serial.waiting {
+                match gb.link_cable.receive() {
+                    Some(incoming) => Serial::start(gb, incoming),
+                    None => return,
+                }
+            }
+            gb.io.serial.clock += cycles;
+            while gb.io.serial.clock >= CLOCKS_PER_BIT && gb.io.serial.bits_left > 0 {
+                gb.io.serial.clock -= CLOCKS_PER_BIT;
+                Serial::shift(gb);
+            }
+        } else {
+            // The partner drives the clock, the whole byte arrives at once
+            let outgoing = gb.io.serial.data;
+            if let Some(incoming) = gb.link_cable.poll(outgoing) {
+                gb.io.serial.data = incoming;
+                Serial::finish(gb);
+            }
+        }
+    }
+
+    // Our most significant bit goes out while the partner's comes in
+    fn shift(gb: &mut GameBoy) {
+        let serial = &mut gb.io.serial;
+        serial.data = (serial.data << 1) | (serial.incoming >> 7);
+        serial.incoming <<= 1;
+        serial.bits_left -= 1;
+
+        if serial.bits_left == 0 {
+            Serial::finish(gb);
+        }
+    }
+
+    fn finish(gb: &mut GameBoy) {
+        gb.io.serial.control &= 0b01111111;
+        Interrupts::turnon(gb, Interruption::Serial);
+    }
+
+    fn transfer_requested(gb: &GameBoy) -> bool {
+        gb.io.serial.control & 0b10000000 > 0
+    }
+
+    fn internal_clock(gb: &GameBoy) -> bool {
+        gb.io.serial.control & 0b00000001 > 0
+    }
+}
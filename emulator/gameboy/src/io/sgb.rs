@@ -0,0 +1,497 @@
+use crate::{mmu::VRAM_BEGIN, model::Model, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}, ColorFrame, SCREEN_WIDTH, SCREEN_HEIGHT, SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT};
+
+use super::lcd::{LCD, LCDControl, SCREEN_COLS, SCREEN_ROWS};
+
+const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+
+// Commands, the first byte of a packet is the command times 8 plus the
+// number of packets
+// https://gbdev.io/pandocs/SGB_Command_Summary.html
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const ATTR_TRN: u8 = 0x15;
+const ATTR_SET: u8 = 0x16;
+const MASK_EN: u8 = 0x17;
+
+// VRAM transfers copy 256 tiles of the displayed background
+const TRANSFER_SIZE: usize = 0x1000;
+
+const SYSTEM_PALETTES: usize = 512;
+const ATTRIBUTE_FILES: usize = 45;
+// 4 cells per byte
+const ATTRIBUTE_FILE_SIZE: usize = SCREEN_COLS * SCREEN_ROWS / 4;
+
+// 256 SNES tiles of 4 bits per pixel
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_TILES_SIZE: usize = 256 * BORDER_TILE_SIZE;
+// 32x32 entries of 2 bytes, followed by the colors of palettes 4 to 7
+const BORDER_MAP_SIZE: usize = 0x800;
+const BORDER_COLORS: usize = 64;
+
+// The Game Boy screen is centered in the SNES one
+const GAME_X: usize = 48;
+const GAME_Y: usize = 40;
+
+// Colors the SGB uses until the game sends its own
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+// What MASK_EN shows instead of the game
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mask {
+    None = 0,
+    // The last frame stays on screen
+    Freeze = 1,
+    Black = 2,
+    Color0 = 3,
+}
+
+impl From<u8> for Mask {
+    fn from(value: u8) -> Self {
+        match value & 0b11 {
+            1 => Mask::Freeze,
+            2 => Mask::Black,
+            3 => Mask::Color0,
+            _ => Mask::None,
+        }
+    }
+}
+
+// Super Game Boy, the SNES side receives commands as packets pulsed on P14
+// and P15 and colors the game screen inside a border
+// https://gbdev.io/pandocs/SGB_Functions.html
+pub(crate) struct SGB {
+    // P14 and P15 as last written
+    lines: u8,
+    // A reset pulse started a packet
+    receiving: bool,
+    packet: [u8; PACKET_SIZE],
+    bits: usize,
+    // Packets of the current command
+    command: Vec<u8>,
+    // MLT_REQ, 1, 2 or 4 joypads and the one being read
+    pub(crate) players: u8,
+    pub(crate) player: u8,
+    // Palettes on screen, color 0 is shared
+    palettes: [[u16; 4]; 4],
+    // Palette of every 8x8 cell of the game screen
+    attributes: [u8; SCREEN_COLS * SCREEN_ROWS],
+    // Sent with PAL_TRN and ATTR_TRN, then picked with PAL_SET and ATTR_SET
+    system_palettes: Vec<u16>,
+    attribute_files: Vec<u8>,
+    mask: Mask,
+    border_tiles: Vec<u8>,
+    border_map: Vec<u8>,
+    border_colors: [u16; BORDER_COLORS],
+    // Drawn again on the next VBlank
+    screen: ColorFrame,
+}
+
+impl SGB {
+    pub(crate) fn new() -> Self {
+        SGB {
+            lines: 0x30,
+            receiving: false,
+            packet: [0; PACKET_SIZE],
+            bits: 0,
+            command: Vec::new(),
+            players: 1,
+            player: 0,
+            palettes: [DEFAULT_PALETTE; 4],
+            attributes: [0; SCREEN_COLS * SCREEN_ROWS],
+            system_palettes: vec![0; SYSTEM_PALETTES * 4],
+            attribute_files: vec![0; ATTRIBUTE_FILES * ATTRIBUTE_FILE_SIZE],
+            mask: Mask::None,
+            border_tiles: vec![0; BORDER_TILES_SIZE],
+            border_map: vec![0; BORDER_MAP_SIZE],
+            border_colors: [0; BORDER_COLORS],
+            screen: ColorFrame { width: SGB_SCREEN_WIDTH, height: SGB_SCREEN_HEIGHT, buffer: vec![0; (SGB_SCREEN_WIDTH*SGB_SCREEN_HEIGHT) as usize] },
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.lines);
+        state.write_bool(self.receiving);
+        state.write_bytes(&self.packet);
+        state.write_u8(self.bits as u8);
+        state.write_u8(self.command.len() as u8 / PACKET_SIZE as u8);
+        state.write_bytes(&self.command);
+        state.write_u8(self.players);
+        state.write_u8(self.player);
+        for color in self.palettes.iter().flatten() {
+            state.write_u16(*color);
+        }
+        state.write_bytes(&self.attributes);
+        for color in &self.system_palettes {
+            state.write_u16(*color);
+        }
+        state.write_bytes(&self.attribute_files);
+        state.write_u8(self.mask as u8);
+        state.write_bytes(&self.border_tiles);
+        state.write_bytes(&self.border_map);
+        for color in &self.border_colors {
+            state.write_u16(*color);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.lines = state.read_u8()?;
+        self.receiving = state.read_bool()?;
+        state.read_bytes(&mut self.packet)?;
+        self.bits = state.read_u8()? as usize;
+        self.command = vec![0; state.read_u8()? as usize * PACKET_SIZE];
+        state.read_bytes(&mut self.command)?;
+        self.players = state.read_u8()?;
+        self.player = state.read_u8()?;
+        for color in self.palettes.iter_mut().flatten() {
+            *color = state.read_u16()?;
+        }
+        state.read_bytes(&mut self.attributes)?;
+        for color in self.system_palettes.iter_mut() {
+            *color = state.read_u16()?;
+        }
+        state.read_bytes(&mut self.attribute_files)?;
+        self.mask = Mask::from(state.read_u8()?);
+        state.read_bytes(&mut self.border_tiles)?;
+        state.read_bytes(&mut self.border_map)?;
+        for color in self.border_colors.iter_mut() {
+            *color = state.read_u16()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_sgb(gb: &GameBoy) -> bool {
+        matches!(gb.model, Model::SGB | Model::SGB2)
+    }
+
+    // The SGB BIOS only listens to cartridges that declare SGB support
+    fn enabled(gb: &GameBoy) -> bool {
+        gb.cartridge.as_ref().is_some_and(|cartridge| cartridge.supports_sgb())
+    }
+
+    // Both lines low resets, then every bit is a pulse on one line with both
+    // lines high in between: P14 low sends a 0, P15 low a 1. A packet is 128
+    // bits, lowest first, and a 0 stop bit
+    // https://gbdev.io/pandocs/SGB_Command_Packet.html
+    pub(crate) fn write_joypad(gb: &mut GameBoy, lines: u8) {
+        let sgb = &mut gb.io.sgb;
+        let previous = sgb.lines;
+        sgb.lines = lines;
+
+        // Releasing P15 selects the next joypad
+        if sgb.players > 1 && previous & 0x20 == 0 && lines & 0x20 != 0 {
+            sgb.player = (sgb.player + 1) % sgb.players;
+        }
+
+        match lines {
+            0x00 => {
+                sgb.receiving = true;
+                sgb.packet = [0; PACKET_SIZE];
+                sgb.bits = 0;
+            },
+            0x10 | 0x20 if sgb.receiving && previous == 0x30 => {
+                let bit = lines == 0x10;
+                if sgb.bits < PACKET_BITS {
+                    sgb.packet[sgb.bits / 8] |= (bit as u8) << (sgb.bits % 8);
+                    sgb.bits += 1;
+                } else {
+                    sgb.receiving = false;
+                    if !bit {
+                        SGB::receive_packet(gb);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Commands longer than a packet are run once the last one arrives
+    fn receive_packet(gb: &mut GameBoy) {
+        let sgb = &mut gb.io.sgb;
+        sgb.command.extend_from_slice(&sgb.packet);
+        let packets = (sgb.command[0] & 0b111).max(1) as usize;
+        if sgb.command.len() < packets * PACKET_SIZE {
+            return;
+        }
+
+        let data = std::mem::take(&mut sgb.command);
+        if SGB::enabled(gb) {
+            SGB::run_command(gb, &data);
+        }
+    }
+
+    fn run_command(gb: &mut GameBoy, data: &[u8]) {
+        match data[0] >> 3 {
+            PAL01 => SGB::set_palettes(gb, 0, 1, data),
+            PAL23 => SGB::set_palettes(gb, 2, 3, data),
+            PAL03 => SGB::set_palettes(gb, 0, 3, data),
+            PAL12 => SGB::set_palettes(gb, 1, 2, data),
+            ATTR_BLK => SGB::attribute_blocks(gb, data),
+            ATTR_LIN => SGB::attribute_lines(gb, data),
+            ATTR_DIV => SGB::attribute_division(gb, data),
+            ATTR_CHR => SGB::attribute_cells(gb, data),
+            PAL_SET => SGB::set_system_palettes(gb, data),
+            PAL_TRN => {
+                let transfer = SGB::vram_transfer(gb);
+                for (color, bytes) in gb.io.sgb.system_palettes.iter_mut().zip(transfer.chunks(2)) {
+                    *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+            },
+            MLT_REQ => {
+                let sgb = &mut gb.io.sgb;
+                sgb.players = match data[1] & 0b11 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                sgb.player = 0;
+            },
+            CHR_TRN => {
+                let transfer = SGB::vram_transfer(gb);
+                // Tiles 0x00 to 0x7F or 0x80 to 0xFF
+                let offset = (data[1] & 0b1) as usize * TRANSFER_SIZE;
+                gb.io.sgb.border_tiles[offset..offset + TRANSFER_SIZE].copy_from_slice(&transfer);
+            },
+            PCT_TRN => {
+                let transfer = SGB::vram_transfer(gb);
+                let sgb = &mut gb.io.sgb;
+                sgb.border_map.copy_from_slice(&transfer[..BORDER_MAP_SIZE]);
+                for (index, color) in sgb.border_colors.iter_mut().enumerate() {
+                    let offset = BORDER_MAP_SIZE + index * 2;
+                    *color = u16::from_le_bytes([transfer[offset], transfer[offset + 1]]);
+                }
+            },
+            ATTR_TRN => {
+                let transfer = SGB::vram_transfer(gb);
+                let size = gb.io.sgb.attribute_files.len();
+                gb.io.sgb.attribute_files.copy_from_slice(&transfer[..size]);
+            },
+            ATTR_SET => {
+                SGB::apply_attribute_file(gb, data[1] & 0b11_1111);
+                if data[1] & 0b0100_0000 != 0 {
+                    gb.io.sgb.mask = Mask::None;
+                }
+            },
+            MASK_EN => gb.io.sgb.mask = Mask::from(data[1]),
+            // Sound, SNES programs and the header sent by the boot ROM are ignored
+            _ => {},
+        }
+    }
+
+    // Two palettes from 7 colors, color 0 is set for all of them
+    // https://gbdev.io/pandocs/SGB_Command_Palettes.html
+    fn set_palettes(gb: &mut GameBoy, first: usize, second: usize, data: &[u8]) {
+        let colors: [u16; 7] = std::array::from_fn(|index| u16::from_le_bytes([data[1 + index * 2], data[2 + index * 2]]) & 0x7FFF);
+        let palettes = &mut gb.io.sgb.palettes;
+        for palette in palettes.iter_mut() {
+            palette[0] = colors[0];
+        }
+        palettes[first][1..].copy_from_slice(&colors[1..4]);
+        palettes[second][1..].copy_from_slice(&colors[4..]);
+    }
+
+    // Four of the system palettes, and optionally an attribute file
+    fn set_system_palettes(gb: &mut GameBoy, data: &[u8]) {
+        let sgb = &mut gb.io.sgb;
+        for palette in 0..4 {
+            let number = u16::from_le_bytes([data[1 + palette * 2], data[2 + palette * 2]]) as usize % SYSTEM_PALETTES;
+            sgb.palettes[palette].copy_from_slice(&sgb.system_palettes[number * 4..number * 4 + 4]);
+        }
+        // Color 0 of the first palette is used for all of them
+        let color0 = sgb.palettes[0][0];
+        for palette in sgb.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+
+        if data[9] & 0b1000_0000 != 0 {
+            SGB::apply_attribute_file(gb, data[9] & 0b11_1111);
+        }
+        if data[9] & 0b0100_0000 != 0 {
+            gb.io.sgb.mask = Mask::None;
+        }
+    }
+
+    fn apply_attribute_file(gb: &mut GameBoy, number: u8) {
+        let sgb = &mut gb.io.sgb;
+        let number = number as usize % ATTRIBUTE_FILES;
+        let file = &sgb.attribute_files[number * ATTRIBUTE_FILE_SIZE..(number + 1) * ATTRIBUTE_FILE_SIZE];
+        for (cell, palette) in sgb.attributes.iter_mut().enumerate() {
+            *palette = (file[cell / 4] >> (6 - (cell % 4) * 2)) & 0b11;
+        }
+    }
+
+    // Each data set colors the inside, the border and the outside of a
+    // rectangle of cells
+    // https://gbdev.io/pandocs/SGB_Command_Attribute.html#sgb-command-04--attr_blk
+    fn attribute_blocks(gb: &mut GameBoy, data: &[u8]) {
+        let sets = (data[1] as usize).min((data.len() - 2) / 6);
+        for set in data[2..].chunks(6).take(sets) {
+            let (mut control, palettes) = (set[0] & 0b111, set[1]);
+            let (left, top, right, bottom) = (set[2] as usize, set[3] as usize, set[4] as usize, set[5] as usize);
+            let (inside, mut border, outside) = (palettes & 0b11, (palettes >> 2) & 0b11, (palettes >> 4) & 0b11);
+            // Changing only the inside or only the outside changes the border too
+            if control == 0b001 {
+                control = 0b011;
+                border = inside;
+            } else if control == 0b100 {
+                control = 0b110;
+                border = outside;
+            }
+
+            for y in 0..SCREEN_ROWS {
+                for x in 0..SCREEN_COLS {
+                    let within = (left..=right).contains(&x) && (top..=bottom).contains(&y);
+                    let on_border = within && (x == left || x == right || y == top || y == bottom);
+                    let palette = if on_border {
+                        (control & 0b010 != 0).then_some(border)
+                    } else if within {
+                        (control & 0b001 != 0).then_some(inside)
+                    } else {
+                        (control & 0b100 != 0).then_some(outside)
+                    };
+                    if let Some(palette) = palette {
+                        gb.io.sgb.attributes[y * SCREEN_COLS + x] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    // Whole rows or columns of cells
+    fn attribute_lines(gb: &mut GameBoy, data: &[u8]) {
+        let lines = (data[1] as usize).min(data.len() - 2);
+        for line in &data[2..2 + lines] {
+            let (number, palette) = ((line & 0b1_1111) as usize, (line >> 5) & 0b11);
+            let attributes = &mut gb.io.sgb.attributes;
+            if line & 0b1000_0000 != 0 {
+                if number < SCREEN_ROWS {
+                    attributes[number * SCREEN_COLS..(number + 1) * SCREEN_COLS].fill(palette);
+                }
+            } else if number < SCREEN_COLS {
+                for y in 0..SCREEN_ROWS {
+                    attributes[y * SCREEN_COLS + number] = palette;
+                }
+            }
+        }
+    }
+
+    // Splits the screen in two with a line of cells between them
+    fn attribute_division(gb: &mut GameBoy, data: &[u8]) {
+        let (after, before, on_line) = (data[1] & 0b11, (data[1] >> 2) & 0b11, (data[1] >> 4) & 0b11);
+        let horizontal = data[1] & 0b0100_0000 != 0;
+        let line = data[2] as usize;
+        for y in 0..SCREEN_ROWS {
+            for x in 0..SCREEN_COLS {
+                let position = if horizontal { y } else { x };
+                gb.io.sgb.attributes[y * SCREEN_COLS + x] = match position.cmp(&line) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    // Palettes of consecutive cells, 2 bits each from the highest ones
+    fn attribute_cells(gb: &mut GameBoy, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize % SCREEN_COLS, data[2] as usize % SCREEN_ROWS);
+        let cells = (u16::from_le_bytes([data[3], data[4]]) as usize).min((data.len() - 6) * 4);
+        let vertical = data[5] & 0b1 != 0;
+        for cell in 0..cells {
+            gb.io.sgb.attributes[y * SCREEN_COLS + x] = (data[6 + cell / 4] >> (6 - (cell % 4) * 2)) & 0b11;
+            if vertical {
+                y = (y + 1) % SCREEN_ROWS;
+                if y == 0 {
+                    x = (x + 1) % SCREEN_COLS;
+                }
+            } else {
+                x = (x + 1) % SCREEN_COLS;
+                if x == 0 {
+                    y = (y + 1) % SCREEN_ROWS;
+                }
+            }
+        }
+    }
+
+    // The SNES copies the first 256 tiles the background shows, 20 per row
+    // https://gbdev.io/pandocs/SGB_VRAM_Transfer.html
+    fn vram_transfer(gb: &GameBoy) -> Vec<u8> {
+        let tile_map = (LCD::background_tile_map(gb) - VRAM_BEGIN) as usize;
+        let unsigned = LCD::read_control(gb, LCDControl::BGandWindowTileSet);
+        let vram = &gb.ppu.vram[0];
+
+        let mut transfer = Vec::with_capacity(TRANSFER_SIZE);
+        for tile in 0..TRANSFER_SIZE / 16 {
+            let index = vram[tile_map + (tile / SCREEN_COLS) * 32 + tile % SCREEN_COLS];
+            let offset = if unsigned { index as usize * 16 } else { (0x1000 + (index as i8 as isize) * 16) as usize };
+            transfer.extend_from_slice(&vram[offset..offset + 16]);
+        }
+        transfer
+    }
+
+    // Called by the LCD when it enters VBlank
+    pub(crate) fn render(gb: &mut GameBoy) {
+        let sgb = &gb.io.sgb;
+        if sgb.mask == Mask::Freeze {
+            return;
+        }
+
+        let game = LCD::screen_buffer(gb);
+        let backdrop = sgb.palettes[0][0];
+        let mut buffer = vec![backdrop; (SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT) as usize];
+
+        for y in 0..SCREEN_HEIGHT as usize {
+            for x in 0..SCREEN_WIDTH as usize {
+                let shade = u8::from(game.buffer[y * SCREEN_WIDTH as usize + x]) as usize;
+                let palette = sgb.attributes[(y / 8) * SCREEN_COLS + x / 8] as usize;
+                buffer[(GAME_Y + y) * SGB_SCREEN_WIDTH as usize + GAME_X + x] = match sgb.mask {
+                    Mask::Black => 0,
+                    Mask::Color0 => backdrop,
+                    _ => sgb.palettes[palette][shade],
+                };
+            }
+        }
+
+        // Color 0 of the border is transparent
+        for y in 0..SGB_SCREEN_HEIGHT as usize {
+            for x in 0..SGB_SCREEN_WIDTH as usize {
+                let entry_offset = ((y / 8) * 32 + x / 8) * 2;
+                let entry = u16::from_le_bytes([sgb.border_map[entry_offset], sgb.border_map[entry_offset + 1]]);
+                let tile = (entry & 0xFF) as usize;
+                let row = if entry & 0x8000 != 0 { 7 - y % 8 } else { y % 8 };
+                let column = if entry & 0x4000 != 0 { 7 - x % 8 } else { x % 8 };
+                let color = SGB::border_pixel(&sgb.border_tiles[tile * BORDER_TILE_SIZE..(tile + 1) * BORDER_TILE_SIZE], row, column);
+                if color != 0 {
+                    // Palettes 4 to 7
+                    let palette = ((entry >> 10) & 0b11) as usize;
+                    buffer[y * SGB_SCREEN_WIDTH as usize + x] = sgb.border_colors[palette * 16 + color] & 0x7FFF;
+                }
+            }
+        }
+
+        gb.io.sgb.screen.buffer = buffer;
+    }
+
+    // SNES tiles have 4 bit planes, the first two interleaved by row and the
+    // last two after them
+    fn border_pixel(tile: &[u8], row: usize, column: usize) -> usize {
+        let bit = 7 - column;
+        let plane = |offset: usize| ((tile[offset] >> bit) & 0b1) as usize;
+        plane(row * 2) | plane(row * 2 + 1) << 1 | plane(16 + row * 2) << 2 | plane(16 + row * 2 + 1) << 3
+    }
+
+    pub(crate) fn screen_buffer(gb: &GameBoy) -> Option<ColorFrame> {
+        SGB::is_sgb(gb).then(|| gb.io.sgb.screen.clone())
+    }
+}
@@ -0,0 +1,485 @@
+#[cfg(test)]
+use crate::{Button, ColoredPixel, PixelLayer, cartridge::Cartridge, gameboy::GameBoy, mmu::MMU, model::Model, palette::{DMGPalettes, ShadePalette}};
+
+#[cfg(test)]
+use super::{joypad::Joypad, lcd::LCD, sgb::SGB};
+
+// ROM only cartridge with the given bytes of the header
+#[cfg(test)]
+fn test_cartridge(name: &str, header: &[(usize, u8)]) -> Cartridge {
+    let path = std::env::temp_dir().join(format!("io_{}_{}.gb", name, std::process::id()));
+    let mut rom = vec![0; 0x8000];
+    for (address, value) in header {
+        rom[*address] = *value;
+    }
+    std::fs::write(&path, rom).unwrap();
+    let cartridge = Cartridge::new(path.clone()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    cartridge
+}
+
+#[cfg(test)]
+fn cgb(name: &str) -> GameBoy {
+    GameBoy::with_model(Some(test_cartridge(name, &[(0x0143, 0x80)])), Model::CGB, None)
+}
+
+// A line is selected when its bit is 0: bit 4 for the directions and bit 5
+// for the buttons, pressed buttons pull their bit low
+// https://gbdev.io/pandocs/Joypad_Input.html
+#[test]
+fn joypad_select_lines() {
+    let mut gb = GameBoy::new(None);
+    Joypad::button_pressed(&mut gb, Button::Up);
+    Joypad::button_pressed(&mut gb, Button::A);
+
+    MMU::write_byte(&mut gb, 0xFF00, 0x20);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xEB);
+    MMU::write_byte(&mut gb, 0xFF00, 0x10);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xDE);
+    // Both lines read the buttons of both, none reads nothing
+    MMU::write_byte(&mut gb, 0xFF00, 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xCA);
+    MMU::write_byte(&mut gb, 0xFF00, 0x30);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xFF);
+}
+
+// Only a selected line going low requests the joypad interrupt
+#[test]
+fn joypad_interrupt() {
+    let mut gb = GameBoy::with_model(None, Model::DMG, None);
+    MMU::write_byte(&mut gb, 0xFF00, 0x10);
+    assert!(!Joypad::press(&mut gb, Button::Up));
+    assert!(Joypad::press(&mut gb, Button::A));
+    assert!(!Joypad::press(&mut gb, Button::A));
+
+    // Right and A share a line, which is already low
+    MMU::write_byte(&mut gb, 0xFF00, 0x00);
+    assert!(!Joypad::press(&mut gb, Button::Right));
+    assert!(Joypad::press(&mut gb, Button::B));
+
+    // Movies set all the buttons at once
+    assert!(!Joypad::set_buttons(&mut gb, 0b0000_0001));
+    assert!(Joypad::set_buttons(&mut gb, 0b0000_1001));
+}
+
+#[test]
+fn cgb_mode_needs_a_cgb_cartridge() {
+    assert!(cgb("cgb_mode").io.cgb_mode);
+
+    let gb = GameBoy::with_model(Some(test_cartridge("dmg_cartridge", &[])), Model::CGB, None);
+    assert!(!gb.io.cgb_mode);
+    // CGB registers are not there in compatibility mode
+    assert_eq!(MMU::read_byte(&gb, 0xFF4D), 0xFF);
+    assert_eq!(MMU::read_byte(&gb, 0xFF70), 0xFF);
+}
+
+// A, B, DIV, SC, NR52 and DMA as the boot ROM of the model leaves them
+// https://gbdev.io/pandocs/Power_Up_Sequence.html
+#[cfg(test)]
+fn post_boot_registers(gb: &GameBoy) -> [u8; 6] {
+    let io = [0xFF04, 0xFF02, 0xFF26, 0xFF46].map(|address| MMU::read_byte(gb, address));
+    [gb.cpu.regs.a, gb.cpu.regs.b, io[0], io[1], io[2], io[3]]
+}
+
+// KEY1, VBK, HDMA5 and SVBK
+#[cfg(test)]
+fn post_boot_cgb_registers(gb: &GameBoy) -> [u8; 4] {
+    [0xFF4D, 0xFF4F, 0xFF55, 0xFF70].map(|address| MMU::read_byte(gb, address))
+}
+
+#[test]
+fn post_boot_dmg0() {
+    let gb = GameBoy::with_model(None, Model::DMG0, None);
+    assert_eq!(post_boot_registers(&gb), [0x01, 0xFF, 0x18, 0x7E, 0xF1, 0xFF]);
+    assert_eq!(post_boot_cgb_registers(&gb), [0xFF; 4]);
+}
+
+#[test]
+fn post_boot_mgb() {
+    let gb = GameBoy::with_model(None, Model::MGB, None);
+    assert_eq!(post_boot_registers(&gb), [0xFF, 0x00, 0xAB, 0x7E, 0xF1, 0xFF]);
+    assert_eq!(post_boot_cgb_registers(&gb), [0xFF; 4]);
+}
+
+#[test]
+fn post_boot_sgb() {
+    let gb = GameBoy::with_model(None, Model::SGB, None);
+    assert_eq!(post_boot_registers(&gb), [0x01, 0x00, 0xD8, 0x7E, 0xF0, 0xFF]);
+    assert_eq!(post_boot_cgb_registers(&gb), [0xFF; 4]);
+}
+
+#[test]
+fn post_boot_cgb() {
+    let gb = cgb("post_boot_cgb");
+    assert_eq!(post_boot_registers(&gb), [0x11, 0x00, 0x1E, 0x7F, 0xF1, 0x00]);
+    // Normal speed, VRAM bank 0, no HDMA and WRAM bank 1
+    assert_eq!(post_boot_cgb_registers(&gb), [0x7E, 0xFE, 0xFF, 0xF8]);
+    assert_eq!((gb.cpu.regs.d, gb.cpu.regs.e, gb.cpu.regs.get_hl()), (0xFF, 0x56, 0x000D));
+}
+
+#[test]
+fn post_boot_cgb_compatibility() {
+    // Nintendo game whose title bytes add up to 0x58
+    let cartridge = test_cartridge("post_boot_compatibility", &[(0x0134, 0x50), (0x0135, 0x08), (0x014B, 0x01)]);
+    let gb = GameBoy::with_model(Some(cartridge), Model::CGB, None);
+    assert_eq!(post_boot_registers(&gb), [0x11, 0x58, 0x26, 0x7F, 0xF1, 0x00]);
+    assert_eq!(post_boot_cgb_registers(&gb), [0xFF; 4]);
+    assert_eq!((gb.cpu.regs.d, gb.cpu.regs.e, gb.cpu.regs.get_hl()), (0x00, 0x08, 0x991A));
+
+    // Other licensees leave B at 0
+    let cartridge = test_cartridge("post_boot_licensee", &[(0x0134, 0x50), (0x0135, 0x08), (0x014B, 0x02)]);
+    let gb = GameBoy::with_model(Some(cartridge), Model::CGB, None);
+    assert_eq!((gb.cpu.regs.b, gb.cpu.regs.get_hl()), (0x00, 0x007C));
+}
+
+#[test]
+fn wram_banks() {
+    let mut gb = cgb("wram_banks");
+    for bank in 0..8 {
+        MMU::write_byte(&mut gb, 0xFF70, bank);
+        MMU::write_byte(&mut gb, 0xD000, 0x10 + bank);
+    }
+    MMU::write_byte(&mut gb, 0xC000, 0xAA);
+
+    // Bank 0 selects bank 1, which got overwritten
+    MMU::write_byte(&mut gb, 0xFF70, 0);
+    assert_eq!(MMU::read_byte(&gb, 0xD000), 0x11);
+    MMU::write_byte(&mut gb, 0xFF70, 5);
+    assert_eq!(MMU::read_byte(&gb, 0xFF70), 0xFD);
+    assert_eq!(MMU::read_byte(&gb, 0xD000), 0x15);
+    assert_eq!(MMU::read_byte(&gb, 0xF000), 0x15);
+    assert_eq!(MMU::read_byte(&gb, 0xC000), 0xAA);
+}
+
+#[test]
+fn vram_banks() {
+    let mut gb = cgb("vram_banks");
+    MMU::write_byte(&mut gb, 0x8000, 0xFF);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x01);
+    assert_eq!(MMU::read_byte(&gb, 0xFF4F), 0xFF);
+    assert_eq!(MMU::read_byte(&gb, 0x8000), 0x00);
+    MMU::write_byte(&mut gb, 0x8001, 0xFF);
+
+    assert_eq!(gb.ppu.tile_set[0][0][0] as u8, 1);
+    assert_eq!(gb.ppu.tile_set[384][0][0] as u8, 2);
+}
+
+#[test]
+fn palette_auto_increment() {
+    let mut gb = cgb("palettes");
+    // The LCD is off, the palettes are always accessible
+    MMU::write_byte(&mut gb, 0xFF40, 0x00);
+    MMU::write_byte(&mut gb, 0xFF68, 0x80 | 0x3E);
+    for value in [0x12, 0x34, 0x56] {
+        MMU::write_byte(&mut gb, 0xFF69, value);
+    }
+    // The index wraps around after the last byte
+    assert_eq!(MMU::read_byte(&gb, 0xFF68), 0xC1);
+
+    MMU::write_byte(&mut gb, 0xFF68, 0x3E);
+    assert_eq!(MMU::read_byte(&gb, 0xFF69), 0x12);
+    MMU::write_byte(&mut gb, 0xFF68, 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF69), 0x56);
+}
+
+#[test]
+fn color_scanline() {
+    let mut gb = cgb("color_scanline");
+    // LCD off and tiles from 0x8000
+    MMU::write_byte(&mut gb, 0xFF40, 0x10);
+    // Color 3 of palette 2 is pure red
+    MMU::write_byte(&mut gb, 0xFF68, 0x80 | (2 * 8 + 3 * 2));
+    MMU::write_byte(&mut gb, 0xFF69, 0x1F);
+    MMU::write_byte(&mut gb, 0xFF69, 0x00);
+
+    // Tile 1 of bank 1 has its first column on color 3
+    MMU::write_byte(&mut gb, 0xFF4F, 0x01);
+    MMU::write_byte(&mut gb, 0x8010, 0x80);
+    MMU::write_byte(&mut gb, 0x8011, 0x80);
+    // Palette 2 from bank 1, flipped horizontally
+    MMU::write_byte(&mut gb, 0x9800, 0b0010_1010);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x00);
+    MMU::write_byte(&mut gb, 0x9800, 0x01);
+
+    LCD::render_scanline(&mut gb);
+    let frame = gb.color_frame().unwrap();
+    assert_eq!(&frame.buffer[6..8], &[0x7FFF, 0x001F]);
+    assert_eq!(&frame.to_rgb()[21..24], &[0xFF, 0x00, 0x00]);
+}
+
+// Color 1 of the given palette, written while the LCD is off
+#[cfg(test)]
+fn write_color(gb: &mut GameBoy, specification: u16, palette: u8, color: u16) {
+    MMU::write_byte(gb, specification, 0x80 | (palette * 8 + 2));
+    MMU::write_byte(gb, specification + 1, color as u8);
+    MMU::write_byte(gb, specification + 1, (color >> 8) as u8);
+}
+
+#[test]
+fn color_window_and_signed_tiles() {
+    let mut gb = cgb("color_window");
+    MMU::write_byte(&mut gb, 0xFF40, 0x00);
+    write_color(&mut gb, 0xFF68, 0, 0x001F);
+    write_color(&mut gb, 0xFF68, 1, 0x03E0);
+
+    // With LCDC bit 4 off tile 0xFF is at 0x8FF0 and tile 1 at 0x9010
+    MMU::write_byte(&mut gb, 0x8FF0, 0xFF);
+    MMU::write_byte(&mut gb, 0x9010, 0xFF);
+    MMU::write_byte(&mut gb, 0x9800, 0xFF);
+    // The window uses the second map with palette 1, from x = 8
+    MMU::write_byte(&mut gb, 0x9C00, 0x01);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x01);
+    MMU::write_byte(&mut gb, 0x9C00, 0x01);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x00);
+    MMU::write_byte(&mut gb, 0xFF4A, 0x00);
+    MMU::write_byte(&mut gb, 0xFF4B, 15);
+    MMU::write_byte(&mut gb, 0xFF40, 0b0110_0000);
+
+    LCD::render_scanline(&mut gb);
+    let frame = gb.color_frame().unwrap();
+    assert_eq!(&frame.buffer[0..2], &[0x001F, 0x001F]);
+    assert_eq!(&frame.buffer[8..10], &[0x03E0, 0x03E0]);
+}
+
+#[test]
+fn color_objects() {
+    let mut gb = cgb("color_objects");
+    MMU::write_byte(&mut gb, 0xFF40, 0x00);
+    write_color(&mut gb, 0xFF6A, 3, 0x7C00);
+
+    // Tile 2 of bank 1 is all color 1
+    MMU::write_byte(&mut gb, 0xFF4F, 0x01);
+    MMU::write_byte(&mut gb, 0x8020, 0xFF);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x00);
+    // Background tile 1 with color 1 on its first 4 columns and the
+    // priority attribute
+    MMU::write_byte(&mut gb, 0x8010, 0xF0);
+    MMU::write_byte(&mut gb, 0x9800, 0x01);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x01);
+    MMU::write_byte(&mut gb, 0x9800, 0b1000_0000);
+    MMU::write_byte(&mut gb, 0xFF4F, 0x00);
+
+    // Object at x = 2 with palette 3 from bank 1
+    for (offset, value) in [16, 10, 2, 0b0000_1011].into_iter().enumerate() {
+        MMU::write_byte(&mut gb, 0xFE00 + offset as u16, value);
+    }
+    MMU::write_byte(&mut gb, 0xFF40, 0b0001_0011);
+
+    LCD::render_scanline(&mut gb);
+    let frame = gb.color_frame().unwrap();
+    // Hidden by the background until its color 0
+    assert_ne!(frame.buffer[2], 0x7C00);
+    assert_eq!(&frame.buffer[4..10], &[0x7C00; 6]);
+    assert_ne!(frame.buffer[10], 0x7C00);
+
+    // LCDC bit 0 off puts objects over everything
+    MMU::write_byte(&mut gb, 0xFF40, 0b0001_0010);
+    LCD::render_scanline(&mut gb);
+    assert_eq!(gb.color_frame().unwrap().buffer[2], 0x7C00);
+}
+
+// Attribute bit 4 picks OBP1, and on DMG the object with the smallest X
+// is drawn over the ones before it in OAM
+#[test]
+fn objects_use_their_own_palette() {
+    let mut gb = GameBoy::with_model(Some(test_cartridge("dmg_objects", &[])), Model::DMG, None);
+    MMU::write_byte(&mut gb, 0xFF40, 0x00);
+    MMU::write_byte(&mut gb, 0xFF47, 0xE4);
+    // Color 1 is light gray in OBP0 and dark gray in OBP1
+    MMU::write_byte(&mut gb, 0xFF48, 0x04);
+    MMU::write_byte(&mut gb, 0xFF49, 0x08);
+    assert_eq!((MMU::read_byte(&gb, 0xFF48), MMU::read_byte(&gb, 0xFF49)), (0x04, 0x08));
+
+    // Tile 1 has its first row on color 1, OBP1 at x = 4 then OBP0 at x = 0
+    MMU::write_byte(&mut gb, 0x8010, 0xFF);
+    for (offset, value) in [16, 12, 1, 0b0001_0000, 16, 8, 1, 0].into_iter().enumerate() {
+        MMU::write_byte(&mut gb, 0xFE00 + offset as u16, value);
+    }
+    MMU::write_byte(&mut gb, 0xFF40, 0b0001_0011);
+
+    LCD::render_scanline(&mut gb);
+    let frame = gb.frame();
+    assert_eq!(&frame.buffer[0..8], &[ColoredPixel::LightGray; 8]);
+    assert_eq!(&frame.layers[0..8], &[PixelLayer::Object0; 8]);
+    assert_eq!(&frame.buffer[8..12], &[ColoredPixel::DarkGray; 4]);
+    assert_eq!(&frame.layers[8..12], &[PixelLayer::Object1; 4]);
+    assert_eq!((frame.buffer[12], frame.layers[12]), (ColoredPixel::White, PixelLayer::Background));
+
+    let palettes = DMGPalettes { background: ShadePalette::GREYSCALE, obj0: ShadePalette::DMG, obj1: ShadePalette::POCKET };
+    let rgba = gb.rgba_frame(&palettes);
+    assert_eq!(&rgba.buffer[0..4], &[0x88, 0xC0, 0x70, 0xFF]);
+    assert_eq!(&rgba.buffer[32..36], &[0x4D, 0x53, 0x3C, 0xFF]);
+    assert_eq!(&rgba.buffer[48..52], &[0xFF, 0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn double_speed_halves_the_lcd_clock() {
+    let mut gb = cgb("double_speed");
+    gb.cpu.pc = 0xC000;
+    // STOP with KEY1 armed switches the speed
+    MMU::write_byte(&mut gb, 0xC000, 0x10);
+    MMU::write_byte(&mut gb, 0xFF4D, 0x01);
+    gb.tick().unwrap();
+    assert_eq!(MMU::read_byte(&gb, 0xFF4D), 0xFE);
+
+    // NOP takes one machine cycle, which lasts 2 clocks of the LCD instead of 4
+    assert_eq!(gb.tick().unwrap(), 2);
+}
+
+#[cfg(test)]
+fn program_hdma(gb: &mut GameBoy, source: u16, destination: u16) {
+    for offset in 0..0x40 {
+        MMU::write_byte(gb, source + offset, offset as u8 + 1);
+    }
+    MMU::write_byte(gb, 0xFF51, (source >> 8) as u8);
+    MMU::write_byte(gb, 0xFF52, source as u8);
+    MMU::write_byte(gb, 0xFF53, (destination >> 8) as u8);
+    MMU::write_byte(gb, 0xFF54, destination as u8);
+}
+
+#[test]
+fn general_purpose_dma_halts_the_cpu() {
+    let mut gb = cgb("gdma");
+    gb.cpu.pc = 0xD000;
+    program_hdma(&mut gb, 0xC000, 0x8800);
+    MMU::write_byte(&mut gb, 0xFF55, 0x01);
+
+    // Every block takes 8 machine cycles without executing instructions
+    assert_eq!(gb.tick().unwrap(), 32);
+    assert_eq!(MMU::read_byte(&gb, 0xFF55), 0x00);
+    assert_eq!(gb.tick().unwrap(), 32);
+    assert_eq!(gb.cpu.pc, 0xD000);
+    assert_eq!(MMU::read_byte(&gb, 0xFF55), 0xFF);
+
+    assert_eq!(MMU::read_byte(&gb, 0x8800), 0x01);
+    assert_eq!(MMU::read_byte(&gb, 0x881F), 0x20);
+    assert_eq!(MMU::read_byte(&gb, 0x8820), 0x00);
+}
+
+#[test]
+fn hblank_dma_copies_a_block_per_line() {
+    let mut gb = cgb("hdma");
+    gb.cpu.pc = 0xD000;
+    program_hdma(&mut gb, 0xC000, 0x9000);
+    MMU::write_byte(&mut gb, 0xFF55, 0x83);
+    assert_eq!(MMU::read_byte(&gb, 0xFF55), 0x03);
+
+    // Lines are 456 clocks long, NOPs take 4
+    for _ in 0..456 / 4 + 8 {
+        gb.tick().unwrap();
+    }
+    assert_eq!(MMU::read_byte(&gb, 0x900F), 0x10);
+    assert_eq!(MMU::read_byte(&gb, 0x9010), 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF55), 0x02);
+
+    // Clearing bit 7 stops the transfer and reports the blocks left
+    MMU::write_byte(&mut gb, 0xFF55, 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF55), 0x82);
+    for _ in 0..456 / 4 + 8 {
+        gb.tick().unwrap();
+    }
+    assert_eq!(MMU::read_byte(&gb, 0x9010), 0x00);
+}
+
+#[cfg(test)]
+fn sgb(name: &str) -> GameBoy {
+    GameBoy::with_model(Some(test_cartridge(name, &[(0x0146, 0x03), (0x014B, 0x33)])), Model::SGB, None)
+}
+
+// Reset pulse, 128 bits and the stop bit, with both lines high in between
+#[cfg(test)]
+fn send_packet(gb: &mut GameBoy, packet: [u8; 16]) {
+    MMU::write_byte(gb, 0xFF00, 0x00);
+    send_bits(gb, packet);
+}
+
+#[cfg(test)]
+fn send_bits(gb: &mut GameBoy, packet: [u8; 16]) {
+    MMU::write_byte(gb, 0xFF00, 0x30);
+    for bit in 0..128 {
+        let one = packet[bit / 8] & (1 << (bit % 8)) != 0;
+        MMU::write_byte(gb, 0xFF00, if one { 0x10 } else { 0x20 });
+        MMU::write_byte(gb, 0xFF00, 0x30);
+    }
+    MMU::write_byte(gb, 0xFF00, 0x20);
+    MMU::write_byte(gb, 0xFF00, 0x30);
+}
+
+// The screen starts black, every pixel uses color 3 of its cell palette
+#[cfg(test)]
+fn sgb_pixel(gb: &mut GameBoy, x: usize, y: usize) -> u16 {
+    SGB::render(gb);
+    SGB::screen_buffer(gb).unwrap().buffer[y * 256 + x]
+}
+
+#[cfg(test)]
+const PAL01_PACKET: [u8; 16] = [0x01, 0x00, 0x00, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33, 0x44, 0x44, 0x55, 0x55, 0x66, 0x66, 0x00];
+
+#[test]
+fn sgb_palettes() {
+    let mut gb = sgb("sgb_palettes");
+    send_packet(&mut gb, PAL01_PACKET);
+
+    // Game area, centered in the SNES screen
+    assert_eq!(sgb_pixel(&mut gb, 48, 40), 0x3333);
+    assert_eq!(sgb_pixel(&mut gb, 207, 183), 0x3333);
+    // Without a border the backdrop is color 0
+    assert_eq!(sgb_pixel(&mut gb, 0, 0), 0x0000);
+}
+
+#[test]
+fn sgb_commands_need_an_sgb_cartridge() {
+    let mut gb = GameBoy::with_model(Some(test_cartridge("sgb_dmg_cartridge", &[])), Model::SGB, None);
+    send_packet(&mut gb, PAL01_PACKET);
+    assert_eq!(sgb_pixel(&mut gb, 48, 40), 0x2866);
+
+    // Other models have no SGB screen
+    assert!(SGB::screen_buffer(&cgb("sgb_on_cgb")).is_none());
+}
+
+// P1 is left at 0xCF, with both lines low as in a reset pulse, and the other
+// registers are stored without starting anything either
+#[test]
+fn post_boot_has_no_side_effects() {
+    let mut gb = sgb("post_boot_side_effects");
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xCF);
+    assert_eq!(MMU::read_byte(&gb, 0xFF0F), 0xE1);
+    assert_eq!(MMU::read_byte(&gb, 0xFF02), 0x7E);
+    assert_eq!(MMU::read_byte(&gb, 0xFF05), 0x00);
+    assert_eq!(MMU::read_byte(&gb, 0xFF40), 0x91);
+    assert!(gb.serial.is_none());
+
+    // Without a reset pulse of its own the packet is ignored
+    send_bits(&mut gb, PAL01_PACKET);
+    assert_eq!(sgb_pixel(&mut gb, 48, 40), 0x2866);
+}
+
+#[test]
+fn sgb_attribute_block() {
+    let mut gb = sgb("sgb_attribute_block");
+    send_packet(&mut gb, PAL01_PACKET);
+    // Cells 2,3 to 5,6 inside and on the border use palette 1
+    send_packet(&mut gb, [0x21, 0x01, 0x03, 0x05, 0x02, 0x03, 0x05, 0x06, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    assert_eq!(sgb_pixel(&mut gb, 48 + 2 * 8, 40 + 3 * 8), 0x6666);
+    assert_eq!(sgb_pixel(&mut gb, 48 + 5 * 8 + 7, 40 + 6 * 8 + 7), 0x6666);
+    assert_eq!(sgb_pixel(&mut gb, 48 + 6 * 8, 40 + 6 * 8), 0x3333);
+}
+
+#[test]
+fn sgb_multiplayer() {
+    let mut gb = sgb("sgb_multiplayer");
+    MMU::write_byte(&mut gb, 0xFF00, 0x30);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00), 0xFF);
+
+    // MLT_REQ for two joypads
+    send_packet(&mut gb, [0x89, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00) & 0x0F, 0x0F);
+    // Releasing P15 moves to the next joypad
+    MMU::write_byte(&mut gb, 0xFF00, 0x10);
+    MMU::write_byte(&mut gb, 0xFF00, 0x30);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00) & 0x0F, 0x0E);
+    MMU::write_byte(&mut gb, 0xFF00, 0x10);
+    MMU::write_byte(&mut gb, 0xFF00, 0x30);
+    assert_eq!(MMU::read_byte(&gb, 0xFF00) & 0x0F, 0x0F);
+}
@@ -1,43 +1,87 @@
-use crate::gameboy::GameBoy;
+use crate::{cpu::cpu::{ClockCycles, MachineCycles}, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
 
 use super::{io::IO, interrupts::{Interruption, Interrupts}};
 
+// https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
 pub(crate) struct Timers {
-    pub(super) div_counter: u8,
-    // Because the max frecuency is 1024 => 10 bits
-    pub(super) tima_counter: u16, 
+    // Incremented every clock, DIV is its upper byte
+    pub(super) system_counter: u16,
+    // TIMA overflowed in the last machine cycle, it is reloaded on the next one
+    pub(super) tima_overflow: bool,
 }
 
 impl Timers {
     pub(crate) fn new() -> Self {
-        Timers { div_counter: 0, tima_counter: 0 }
+        Timers { system_counter: 0, tima_overflow: false }
     }
-    
-    pub(crate) fn tick(gb: &mut GameBoy, cycles: u8) {
 
-        let (new_div, div_overflow) = gb.io.timers.div_counter.overflowing_add(cycles);
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.system_counter);
+        state.write_bool(self.tima_overflow);
+    }
 
-        if div_overflow {
-            gb.io.timers.div_counter = new_div;
-            IO::inc_div(gb);
-        }
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.system_counter = state.read_u16()?;
+        self.tima_overflow = state.read_bool()?;
+        Ok(())
+    }
 
-        if Timers::timer_enabled(gb) {
-            gb.io.timers.tima_counter = gb.io.timers.tima_counter.wrapping_add(cycles as u16);
+    pub(crate) fn tick(gb: &mut GameBoy, cycles: ClockCycles) {
+        let machine_cycle = ClockCycles::from(MachineCycles::One);
 
-            while gb.io.timers.tima_counter >= Timers::timer_frecuency(gb) {
-                let tima_overflow = IO::inc_tima(gb);
-                if tima_overflow {
-                    Interrupts::turnon(gb, Interruption::Timer);
-                    IO::reset_tima(gb);
-                }
-                gb.io.timers.tima_counter -= Timers::timer_frecuency(gb);
+        for _ in 0..cycles / machine_cycle {
+            if gb.io.timers.tima_overflow {
+                gb.io.timers.tima_overflow = false;
+                IO::reset_tima(gb);
+                Interrupts::turnon(gb, Interruption::Timer);
             }
+
+            let counter = gb.io.timers.system_counter.wrapping_add(machine_cycle);
+            Timers::set_system_counter(gb, counter);
+        }
+    }
+
+    pub(crate) fn read_div(gb: &GameBoy) -> u8 {
+        (gb.io.timers.system_counter >> 8) as u8
+    }
+
+    pub(crate) fn reset_div(gb: &mut GameBoy) {
+        Timers::set_system_counter(gb, 0);
+    }
+
+    // Changing TAC can also produce a falling edge and increment TIMA
+    pub(crate) fn write_tac(gb: &mut GameBoy, value: u8) {
+        let signal = Timers::timer_signal(gb);
+        IO::set_tac_register(gb, value);
+        if signal && !Timers::timer_signal(gb) {
+            Timers::inc_tima(gb);
         }
     }
-    
+
+    // TIMA is incremented on the falling edge of the selected counter bit
+    fn set_system_counter(gb: &mut GameBoy, value: u16) {
+        let signal = Timers::timer_signal(gb);
+        gb.io.timers.system_counter = value;
+        if signal && !Timers::timer_signal(gb) {
+            Timers::inc_tima(gb);
+        }
+    }
+
+    fn timer_signal(gb: &GameBoy) -> bool {
+        // The selected bit toggles at twice the timer frecuency
+        let bit = Timers::timer_frecuency(gb) >> 1;
+        Timers::timer_enabled(gb) && (gb.io.timers.system_counter & bit) > 0
+    }
+
+    fn inc_tima(gb: &mut GameBoy) {
+        // TIMA reads 0 until it is reloaded from TMA
+        if IO::inc_tima(gb) {
+            gb.io.timers.tima_overflow = true;
+        }
+    }
+
     pub(crate) fn timer_enabled(gb: &GameBoy) -> bool {
-        // if bit 2 is high, timer is enabled 
+        // if bit 2 is high, timer is enabled
         IO::get_tac_register(gb) & 0b00000100 > 0
     }
 
@@ -53,4 +97,4 @@ impl Timers {
             256
         }
     }
-}
\ No newline at end of file
+}
@@ -1,21 +1,50 @@
 pub mod cartridge;
+pub mod debugger;
+pub mod disassembler;
+pub mod error;
+pub mod filter;
+pub mod link;
+pub mod model;
+pub mod movie;
+pub mod palette;
+pub mod rewind;
+pub mod rom;
+pub mod runner;
+pub mod trace;
 pub(crate) mod io;
 pub(crate) mod gameboy;
 mod ppu;
-mod rom;
 mod cpu;
 mod mmu;
+mod savestate;
 
-use std::io::Error;
+use std::{fs::File, io::{BufWriter, Error}, path::Path};
 
 use cartridge::Cartridge;
+use cpu::instructions::decode::Instruction;
+use debugger::{DebugStop, Debugger, Register};
+use disassembler::DisassemblyLine;
+use error::GameBoyError;
 use gameboy::GameBoy;
+use link::LinkCable;
+use mmu::{Address, MMU};
+use model::Model;
+use movie::{Movie, MovieStart};
+use palette::{DMGPalettes, ShadePalette};
+use filter::{Filter, ScreenFilter};
+use rom::BootROM;
+use trace::{TraceFilter, TraceSink, Tracer};
+use rewind::{RewindBuffer, REWIND_CAPACITY, REWIND_INTERVAL};
 use io::{interrupts::{Interruption, Interrupts}, joypad::Joypad};
 use wasm_bindgen::prelude::*;
 
 pub const SCREEN_WIDTH: u32 = 160;
 pub const SCREEN_HEIGHT: u32 = 144;
 
+// Super Game Boy screen, the game is drawn inside a border
+pub const SGB_SCREEN_WIDTH: u32 = 256;
+pub const SGB_SCREEN_HEIGHT: u32 = 224;
+
 pub const BACKGROUND_WIDTH: u32 = 256;
 pub const BACKGROUND_HEIGHT: u32 = 256;
 
@@ -26,10 +55,37 @@ pub const CPU_CLOCK_HZ: usize = 4_194_304;
 pub const FPS: f32 = 59.7;
 pub const CPU_CYCLES_PER_FRAME: usize = (CPU_CLOCK_HZ as f32 / FPS) as usize;
 
+pub const SAVE_STATE_SLOTS: usize = 9;
+
 pub struct EmulationStep {
     pub framebuffer: GameBoyFrame,
+    // Colors of the screen, only for CGB games on a CGB
+    pub color_framebuffer: Option<ColorFrame>,
+    // Colored screen and border of a Super Game Boy
+    pub sgb_framebuffer: Option<ColorFrame>,
+    // What the screen shows, ready to be copied to a texture
+    pub rgba_framebuffer: RGBAFrame,
     pub tiledata: GameBoyFrame,
     pub background: GameBoyFrame,
+    // What happened during the frame that the frontend may want to report
+    pub events: Vec<EmulationEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulationEvent {
+    // An undefined opcode locked up the CPU, only a power cycle recovers it
+    Locked { pc: u16, opcode: u8 },
+    // The frame was cut short by a breakpoint or watchpoint
+    Break(DebugStop),
+}
+
+impl std::fmt::Display for EmulationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EmulationEvent::Locked { pc, opcode } => write!(f, "CPU locked up by illegal opcode {:02X} at {:04X}", opcode, pc),
+            EmulationEvent::Break(stop) => write!(f, "{}", stop),
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -47,16 +103,132 @@ pub enum ColoredPixel {
   Black = 3
 }
 
+// Palette register a DMG pixel was drawn with, BGP for the background and
+// the window, OBP0 or OBP1 for objects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelLayer {
+  Background,
+  Object0,
+  Object1
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameBoyFrame {
   pub width: u32,
   pub height: u32,
-  pub buffer: Vec<ColoredPixel>
+  pub buffer: Vec<ColoredPixel>,
+  pub layers: Vec<PixelLayer>
+}
+
+impl GameBoyFrame {
+  // CRC-32 of the pixels, regression tests compare frames with it
+  pub fn checksum(&self) -> u32 {
+      let pixels: Vec<u8> = self.buffer.iter().map(|pixel| u8::from(*pixel)).collect();
+      cartridge::crc32(&pixels)
+  }
+
+  // Every pixel takes the palette of the layer it was drawn on
+  pub fn to_rgba(&self, palettes: &DMGPalettes) -> RGBAFrame {
+      let buffer = self.buffer.iter().zip(&self.layers)
+          .flat_map(|(pixel, layer)| palettes.layer(*layer).rgba(*pixel))
+          .collect();
+      RGBAFrame { width: self.width, height: self.height, buffer }
+  }
+
+  // Grayscale PNG with the same shades as the desktop screen
+  pub fn save_png(&self, path: &Path) -> Result<(), Error> {
+      let file = File::create(path)?;
+      let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+      encoder.set_color(png::ColorType::Grayscale);
+      encoder.set_depth(png::BitDepth::Eight);
+
+      let data: Vec<u8> = self.buffer.iter().map(|pixel| match pixel {
+          ColoredPixel::White => 255,
+          ColoredPixel::LightGray => 192,
+          ColoredPixel::DarkGray => 96,
+          ColoredPixel::Black => 0,
+      }).collect();
+
+      let mut writer = encoder.write_header().map_err(Error::other)?;
+      writer.write_image_data(&data).map_err(Error::other)
+  }
+
+  // Any PNG is accepted, every pixel takes the nearest of the four shades so
+  // reference images from other emulators can be used
+  pub fn load_png(path: &Path) -> Result<GameBoyFrame, Error> {
+      let mut decoder = png::Decoder::new(File::open(path)?);
+      decoder.set_transformations(png::Transformations::normalize_to_color8());
+      let mut reader = decoder.read_info().map_err(Error::other)?;
+      let mut data = vec![0; reader.output_buffer_size()];
+      let info = reader.next_frame(&mut data).map_err(Error::other)?;
+
+      let channels = info.color_type.samples();
+      let buffer = data[..info.buffer_size()].chunks(channels).map(|pixel| {
+          // Alpha is ignored
+          let color = &pixel[..if channels >= 3 { 3 } else { 1 }];
+          let luminance = color.iter().map(|c| *c as u32).sum::<u32>() / color.len() as u32;
+          match luminance {
+              212..=255 => ColoredPixel::White,
+              128..=211 => ColoredPixel::LightGray,
+              48..=127 => ColoredPixel::DarkGray,
+              _ => ColoredPixel::Black,
+          }
+      }).collect();
+
+      Ok(GameBoyFrame::new(info.width, info.height, buffer))
+  }
+}
+
+
+// Screen of a CGB in color mode, with the 15-bit colors of its palettes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColorFrame {
+  pub width: u32,
+  pub height: u32,
+  // Red in bits 0-4, green in 5-9 and blue in 10-14
+  pub buffer: Vec<u16>,
 }
 
+impl ColorFrame {
+  // Three bytes per pixel, every channel stretched to 8 bits
+  pub fn to_rgb(&self) -> Vec<u8> {
+      let channel = |color: u16, shift: u16| {
+          let value = ((color >> shift) & 0x1F) as u8;
+          (value << 3) | (value >> 2)
+      };
+      self.buffer.iter().flat_map(|color| [channel(*color, 0), channel(*color, 5), channel(*color, 10)]).collect()
+  }
+
+  pub fn to_rgba(&self) -> RGBAFrame {
+      let buffer = self.to_rgb().chunks(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0xFF]).collect();
+      RGBAFrame { width: self.width, height: self.height, buffer }
+  }
+}
+
+// Four bytes per pixel: red, green, blue and alpha
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RGBAFrame {
+  pub width: u32,
+  pub height: u32,
+  pub buffer: Vec<u8>,
+}
 
 pub struct Emulation {
   pub(crate) gameboy: GameBoy,
+  pub(crate) rewind: RewindBuffer,
+  // Frames emulated so far, rewinding moves it back
+  pub(crate) frames: u64,
+  // Cycles of the current frame, frames can be left halfway by the debugger
+  pub(crate) frame_cycles: usize,
+  pub(crate) recording: Option<Movie>,
+  // Movie being played and the next frame to replay
+  pub(crate) playback: Option<(Movie, usize)>,
+  // Power on starts at 0x0100 instead of running the boot ROM
+  pub(crate) skip_boot: bool,
+  // Boot ROM run at power on, None starts after it
+  pub(crate) boot_rom: Option<BootROM>,
+  // Colors of the shades of DMG games
+  pub(crate) palettes: DMGPalettes,
   pub running: bool,
   pub total_cycles: u64
 }
@@ -64,7 +236,11 @@ pub struct Emulation {
 #[wasm_bindgen]
 pub struct EmulationWasm {
   pub(crate) gameboy: GameBoy,
-  pub(crate) screenbuffer: Vec<ColoredPixel>,
+  // RGBA pixels of the last frame, after the filter
+  pub(crate) screenbuffer: RGBAFrame,
+  pub(crate) palettes: DMGPalettes,
+  pub(crate) filter: ScreenFilter,
+  pub(crate) slots: [Option<Vec<u8>>; SAVE_STATE_SLOTS],
   pub total_cycles: u64
 }
 
@@ -73,6 +249,14 @@ impl Emulation {
       let gameboy = GameBoy::new(cartridge);
       Emulation { 
           gameboy,
+          rewind: RewindBuffer::new(REWIND_CAPACITY),
+          frames: 0,
+          frame_cycles: 0,
+          recording: None,
+          playback: None,
+          skip_boot: false,
+          boot_rom: Some(BootROM::dmg()),
+          palettes: DMGPalettes::default(),
           running: false,
           total_cycles: 0
       }
@@ -82,60 +266,382 @@ impl Emulation {
     self.running = true;
   }
 
-  pub fn step(&mut self) -> Result<EmulationStep,Error> {
+  // Runs until the end of the frame, or until the debugger stops the
+  // execution. The next call finishes the interrupted frame
+  pub fn step(&mut self) -> Result<EmulationStep, GameBoyError> {
+      loop {
+          let frame_done = self.tick()?;
+          if let Some(stop) = self.gameboy.debugger.stop.take() {
+              self.gameboy.events.push(EmulationEvent::Break(stop));
+              break;
+          }
+          if frame_done {
+              break;
+          }
+      }
+
+      let mut step = self.current_step();
+      step.events = std::mem::take(&mut self.gameboy.events);
+      Ok(step)
+  }
+
+  // Executes one CPU step, returns whether it completed a frame
+  fn tick(&mut self) -> Result<bool, GameBoyError> {
+      // A movie started halfway through a frame has an input for the rest of it
+      let movie_start = self.recording.as_ref().is_some_and(|movie| movie.inputs.is_empty())
+          || matches!(self.playback, Some((_, 0)));
+      if self.frame_cycles == 0 || movie_start {
+          self.start_frame();
+      }
 
-      let mut frame_cycles = 0;           
-      
-      while frame_cycles < CPU_CYCLES_PER_FRAME {
-          let gb_step_res = self.gameboy.tick();
+      let cycles = self.gameboy.tick()?;
+      self.frame_cycles += cycles as usize;
+      self.total_cycles += cycles as u64;
 
-          match gb_step_res {
-              Ok(cycles) => {
-                  let executed_cycles = u64::from(cycles);
-                  frame_cycles += executed_cycles as usize;
-                  self.total_cycles += executed_cycles;
-                  
-              },
-              Err(error) => {
-                  return Err(error)
-              }
+      if self.frame_cycles < CPU_CYCLES_PER_FRAME {
+          return Ok(false);
+      }
+
+      self.frame_cycles = 0;
+      self.frames += 1;
+      if self.frames.is_multiple_of(REWIND_INTERVAL) {
+          self.rewind.push(self.frames, savestate::save(&self.gameboy));
+      }
+      Ok(true)
+  }
+
+  // Input is sampled once per frame, movies replay it at the same points
+  fn start_frame(&mut self) {
+      if let Some((movie, frame)) = &mut self.playback {
+          let buttons = movie.inputs[*frame];
+          *frame += 1;
+          if *frame == movie.inputs.len() {
+              self.playback = None;
+          }
+          if Joypad::set_buttons(&mut self.gameboy, buttons) {
+              Interrupts::turnon(&mut self.gameboy, Interruption::Joypad);
+          }
+      }
+      if let Some(movie) = &mut self.recording {
+          movie.inputs.push(Joypad::buttons(&self.gameboy));
+      }
+  }
+
+  pub fn debugger(&mut self) -> &mut Debugger {
+      &mut self.gameboy.debugger
+  }
+
+  // Executes a single instruction, even if there is a breakpoint on it
+  pub fn step_instruction(&mut self) -> Result<DebugStop, GameBoyError> {
+      self.gameboy.debugger.resume_pc = Some(self.gameboy.cpu.pc);
+      self.tick()?;
+      let stop = self.gameboy.debugger.stop.take().unwrap_or(DebugStop::Step);
+      self.gameboy.debugger.resume_pc = Some(self.gameboy.cpu.pc);
+      Ok(stop)
+  }
+
+  // Runs a CALL or RST until it returns, other instructions are just stepped
+  pub fn step_over(&mut self, max_frames: u64) -> Result<DebugStop, GameBoyError> {
+      let pc = self.gameboy.cpu.pc;
+      match Instruction::from_byte_not_prefixed(MMU::read_byte(&self.gameboy, pc)) {
+          Some(instruction @ (Instruction::CALL(_) | Instruction::RST(_))) => {
+              let return_address = pc.wrapping_add(u16::from(instruction.size()));
+              self.run_until((return_address, self.gameboy.cpu.sp), max_frames)
+          },
+          _ => self.step_instruction(),
+      }
+  }
+
+  // Runs until the PC reaches the address, or a breakpoint or watchpoint stops it before
+  pub fn run_to(&mut self, address: Address, max_frames: u64) -> Result<DebugStop, GameBoyError> {
+      self.run_until((address, 0), max_frames)
+  }
+
+  fn run_until(&mut self, target: (Address, Address), max_frames: u64) -> Result<DebugStop, GameBoyError> {
+      let max_cycles = max_frames * CPU_CYCLES_PER_FRAME as u64;
+      let start = self.total_cycles;
+      self.gameboy.debugger.target = Some(target);
+      self.gameboy.debugger.resume_pc = Some(self.gameboy.cpu.pc);
+
+      let stop = loop {
+          if self.total_cycles - start >= max_cycles {
+              break Ok(DebugStop::FrameLimit);
+          }
+          if let Err(error) = self.tick() {
+              break Err(error);
           }
+          if let Some(stop) = self.gameboy.debugger.stop.take() {
+              break Ok(stop);
+          }
+      };
+
+      self.gameboy.debugger.target = None;
+      self.gameboy.debugger.resume_pc = Some(self.gameboy.cpu.pc);
+      stop
+  }
+
+  pub fn read_register(&self, register: Register) -> u16 {
+      register.read(&self.gameboy)
+  }
+
+  // Reads memory as the CPU sees it, without triggering watchpoints
+  pub fn read_memory(&self, address: Address) -> u8 {
+      MMU::read_byte(&self.gameboy, address)
+  }
+
+  // Instructions from the address on, as the CPU would decode them now
+  pub fn disassemble(&self, address: Address, count: usize) -> Vec<DisassemblyLine> {
+      let mut lines = Vec::new();
+      let mut address = address;
+      for _ in 0..count {
+          let line = disassembler::disassemble(|address| self.read_memory(address), address);
+          address = address.wrapping_add(line.bytes.len() as Address);
+          lines.push(line);
+      }
+      lines
+  }
+
+  // Goes back at least the given number of frames, as far as the rewind
+  // history allows
+  pub fn rewind(&mut self, frames: u64) -> Result<EmulationStep, GameBoyError> {
+      let target = self.frames.saturating_sub(frames);
+      match self.rewind.rewind_to(target) {
+          Some((frame, state)) => {
+              savestate::load(&mut self.gameboy, state)?;
+              // The recording continues from the rewound frame
+              if let Some(movie) = &mut self.recording {
+                  let rewound = (self.frames - frame) as usize;
+                  movie.inputs.truncate(movie.inputs.len().saturating_sub(rewound));
+              }
+              self.frames = frame;
+              self.frame_cycles = 0;
+              Ok(self.current_step())
+          },
+          None => Err(GameBoyError::NothingToRewind),
       }
+  }
 
+  fn current_step(&self) -> EmulationStep {
       let framebuffer = self.gameboy.frame();
+      let color_framebuffer = self.gameboy.color_frame();
+      let sgb_framebuffer = self.gameboy.sgb_frame();
+      let rgba_framebuffer = self.gameboy.rgba_frame(&self.palettes);
       let tiledata = self.gameboy.tiledata();
       let background = self.gameboy.background();
 
-      Ok(EmulationStep { framebuffer, tiledata, background })  
+      EmulationStep { framebuffer, color_framebuffer, sgb_framebuffer, rgba_framebuffer, tiledata, background, events: Vec::new() }
   }
 
+  // Only a selected line going low requests the interrupt, so key repeats
+  // of the host do not reach the game and movies replay the same interrupts.
+  // The host input is ignored while a movie is playing
   pub fn button_pressed(&mut self, b: Button) {
-      Joypad::button_pressed(&mut self.gameboy, b);
-      Interrupts::turnon(&mut self.gameboy, Interruption::Joypad);
+      if self.is_playing() {
+          return;
+      }
+      if Joypad::press(&mut self.gameboy, b) {
+          Interrupts::turnon(&mut self.gameboy, Interruption::Joypad);
+      }
   } 
 
   pub fn button_released(&mut self, b: Button) {
+      if self.is_playing() {
+          return;
+      }
       Joypad::button_released(&mut self.gameboy, b);
   }
+
+  // Records the input of every following frame. Starts from power on if
+  // nothing has been emulated yet, otherwise from a save state
+  pub fn start_recording(&mut self) {
+      let start = if self.frames == 0 && self.total_cycles == 0 {
+          MovieStart::PowerOn
+      } else {
+          MovieStart::SaveState(self.save_state())
+      };
+      self.recording = Some(Movie::new(savestate::rom_checksum(&self.gameboy), start, self.frame_cycles as u32));
+  }
+
+  pub fn stop_recording(&mut self) -> Option<Movie> {
+      self.recording.take()
+  }
+
+  // Restores the movie starting point, its input replaces the joypad for the
+  // next `movie.frames()` frames
+  pub fn play_movie(&mut self, movie: Movie) -> Result<(), GameBoyError> {
+      if movie.rom_checksum != savestate::rom_checksum(&self.gameboy) {
+          return Err(GameBoyError::MovieMismatch);
+      }
+
+      match &movie.start {
+          MovieStart::PowerOn => self.power_on(),
+          MovieStart::SaveState(state) => self.load_state(state)?,
+      }
+      Joypad::set_buttons(&mut self.gameboy, 0);
+      self.frames = 0;
+      self.frame_cycles = movie.frame_cycles as usize;
+      self.playback = if movie.inputs.is_empty() { None } else { Some((movie, 0)) };
+      Ok(())
+  }
+
+  pub fn is_playing(&self) -> bool {
+      self.playback.is_some()
+  }
+
+  // Restarts the GameBoy with the same cartridge, link cable and settings
+  fn power_on(&mut self) {
+      let cartridge = self.gameboy.cartridge.take();
+      let link_cable = std::mem::replace(&mut self.gameboy.link_cable, Box::new(link::Disconnected));
+      let strict = self.gameboy.strict;
+      let debugger = std::mem::take(&mut self.gameboy.debugger);
+      let tracer = self.gameboy.tracer.take();
+      let doctor = self.gameboy.doctor;
+      self.gameboy = GameBoy::with_model(cartridge, self.gameboy.model, self.boot_rom.clone());
+      self.gameboy.link_cable = link_cable;
+      self.gameboy.strict = strict;
+      self.gameboy.debugger = debugger;
+      self.gameboy.tracer = tracer;
+      self.gameboy.doctor = doctor;
+      if self.skip_boot && self.boot_rom.is_some() {
+          self.gameboy.skip_boot();
+      }
+      self.rewind.clear();
+      self.frames = 0;
+      self.frame_cycles = 0;
+      self.total_cycles = 0;
+  }
+
+  pub fn connect_link_cable(&mut self, cable: Box<dyn LinkCable>) {
+      self.gameboy.link_cable = cable;
+  }
+
+  // Debug mode where an illegal opcode stops the emulation with an error
+  // instead of locking up the CPU like the hardware does
+  pub fn set_strict(&mut self, strict: bool) {
+      self.gameboy.strict = strict;
+  }
+
+  // Traces every executed instruction that passes the filter
+  pub fn start_trace(&mut self, sink: TraceSink, filter: TraceFilter) {
+      self.gameboy.tracer = Some(Tracer::new(sink, filter));
+  }
+
+  pub fn stop_trace(&mut self) -> Result<Option<TraceSink>, GameBoyError> {
+      match self.gameboy.tracer.take() {
+          Some(mut tracer) => {
+              tracer.sink.flush()?;
+              Ok(Some(tracer.sink))
+          },
+          None => Ok(None),
+      }
+  }
+
+  // Starts at 0x0100 without the boot ROM and LY stuck at 0x90, so the
+  // trace can be compared with the logs of Gameboy Doctor
+  pub fn gameboy_doctor(&mut self) {
+      self.skip_boot_rom();
+      self.gameboy.doctor = true;
+  }
+
+  // Starts directly at the cartridge entry point with the state the boot ROM
+  // leaves, also after a power on. Meant to be called before the first step
+  pub fn skip_boot_rom(&mut self) {
+      self.skip_boot = true;
+      self.gameboy.skip_boot();
+  }
+
+  // Selects the hardware to emulate and powers it on again. Only the DMG
+  // boot ROM is built in, other models start after their boot ROM
+  pub fn set_model(&mut self, model: Model) {
+      self.boot_rom = (model == Model::DMG).then(BootROM::dmg);
+      self.gameboy.model = model;
+      self.power_on();
+  }
+
+  // Runs a dumped boot ROM at power on, its model is the one emulated
+  pub fn set_boot_rom(&mut self, boot_rom: BootROM) {
+      self.gameboy.model = boot_rom.model();
+      self.boot_rom = Some(boot_rom);
+      self.power_on();
+  }
+
+  pub fn set_palettes(&mut self, palettes: DMGPalettes) {
+      self.palettes = palettes;
+  }
+
+  pub fn model(&self) -> Model {
+      self.gameboy.model
+  }
+
+  pub fn save_state(&self) -> Vec<u8> {
+      savestate::save(&self.gameboy)
+  }
+
+  // Refused if the state was saved with another game
+  pub fn load_state(&mut self, state: &[u8]) -> Result<(), GameBoyError> {
+      savestate::load(&mut self.gameboy, state)?;
+      // The history belongs to the timeline that was left
+      self.rewind.clear();
+      self.frame_cycles = 0;
+      Ok(())
+  }
 }
 
 #[wasm_bindgen]
 impl EmulationWasm {
   pub fn new() -> Self {
     let gameboy = GameBoy::new(None);
-    let screenbuffer: Vec<ColoredPixel> = Vec::new();
+    let screenbuffer = RGBAFrame { width: SCREEN_WIDTH, height: SCREEN_HEIGHT, buffer: Vec::new() };
     EmulationWasm { 
         gameboy,
         screenbuffer,
+        palettes: DMGPalettes::uniform(ShadePalette::DMG),
+        filter: ScreenFilter::default(),
+        slots: Default::default(),
         total_cycles: 0
     }
   }
 
-  pub fn screen(&self) -> *const ColoredPixel {
-		self.screenbuffer.as_ptr()
+  pub fn screen(&self) -> *const u8 {
+		self.screenbuffer.buffer.as_ptr()
 	} 
 
-  pub fn step(&mut self) -> Result<JsValue,JsValue> {
+  // Size of the screen buffer, filters upscale it
+  pub fn screen_width(&self) -> u32 {
+      self.screenbuffer.width
+  }
+
+  pub fn screen_height(&self) -> u32 {
+      self.screenbuffer.height
+  }
+
+  // Same names as the desktop --filter option
+  pub fn set_filter(&mut self, filter: &str) -> Result<(), JsError> {
+      self.filter.filter = filter.parse::<Filter>().map_err(|error| JsError::new(&error))?;
+      Ok(())
+  }
+
+  pub fn set_ghosting(&mut self, ghosting: bool) {
+      self.filter.ghosting = ghosting;
+  }
+
+  // Same names and colors as the desktop --palette option
+  pub fn set_palette(&mut self, palette: &str) -> Result<(), JsError> {
+      self.palettes = palette.parse().map_err(|error: String| JsError::new(&error))?;
+      Ok(())
+  }
+
+  pub fn set_strict(&mut self, strict: bool) {
+      self.gameboy.strict = strict;
+  }
+
+  // The screen keeps refreshing after a lockup, the page can tell the user
+  pub fn is_locked(&self) -> bool {
+      self.gameboy.cpu.is_locked
+  }
+
+  // Emulation errors are thrown as JS errors with their description
+  pub fn step(&mut self) -> Result<JsValue,JsError> {
 
     let mut frame_cycles = 0;           
     
@@ -148,23 +654,56 @@ impl EmulationWasm {
                 frame_cycles += executed_cycles as usize;
                 self.total_cycles += executed_cycles;
             },
-            Err(_) => {
-                return Err(JsValue::from_str("error"))
+            Err(error) => {
+                return Err(JsError::from(error))
             }
         }
     }
 
-    self.screenbuffer = self.gameboy.frame().buffer.clone();
+    self.screenbuffer = self.filter.apply(&self.gameboy.rgba_frame(&self.palettes));
 
     Ok(JsValue::from_str(&self.total_cycles.to_string()))  
   }
 
   pub fn button_pressed(&mut self, b: Button) {
-      Joypad::button_pressed(&mut self.gameboy, b);
-      Interrupts::turnon(&mut self.gameboy, Interruption::Joypad);
+      if Joypad::press(&mut self.gameboy, b) {
+          Interrupts::turnon(&mut self.gameboy, Interruption::Joypad);
+      }
   } 
 
   pub fn button_released(&mut self, b: Button) {
       Joypad::button_released(&mut self.gameboy, b);
   }
+
+  pub fn save_state(&self) -> Vec<u8> {
+      savestate::save(&self.gameboy)
+  }
+
+  pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+      savestate::load(&mut self.gameboy, state)?;
+      Ok(())
+  }
+
+  // Slots are numbered from 1 to 9 and live as long as the emulation
+  pub fn save_slot(&mut self, slot: usize) -> Result<(), JsError> {
+      let index = EmulationWasm::slot_index(slot)?;
+      self.slots[index] = Some(self.save_state());
+      Ok(())
+  }
+
+  pub fn load_slot(&mut self, slot: usize) -> Result<(), JsError> {
+      let index = EmulationWasm::slot_index(slot)?;
+      match self.slots[index].clone() {
+          Some(state) => self.load_state(&state),
+          None => Err(JsError::new("Empty save state slot")),
+      }
+  }
+
+  fn slot_index(slot: usize) -> Result<usize, JsError> {
+      if (1..=SAVE_STATE_SLOTS).contains(&slot) {
+          Ok(slot - 1)
+      } else {
+          Err(JsError::new("Invalid save state slot"))
+      }
+  }
 }
\ No newline at end of file
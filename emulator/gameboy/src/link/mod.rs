@@ -0,0 +1,77 @@
+use std::{cell::RefCell, rc::Rc};
+
+pub mod printer;
+pub mod tcp;
+mod tests;
+
+// The other end of the serial port. The GameBoy that drives the clock calls
+// `exchange` when a transfer starts, a GameBoy waiting on an external clock
+// calls `poll` every machine cycle until its partner sends a byte.
+// https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+pub trait LinkCable {
+    // Sends our byte and returns the one shifted in from the partner, or None
+    // when the partner answers later
+    fn exchange(&mut self, outgoing: u8) -> Option<u8>;
+
+    // Called every machine cycle after `exchange` returned None, until the
+    // partner's byte arrives. Nothing is pending on cables that answer right
+    // away, as after loading a state, and the input line stays high
+    fn receive(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    // Offers our byte to a partner driving the clock, returns the byte it
+    // sent if a transfer happened
+    fn poll(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+// Nothing is plugged in, the serial input line stays high
+pub struct Disconnected;
+
+impl LinkCable for Disconnected {
+    fn exchange(&mut self, _outgoing: u8) -> Option<u8> {
+        Some(0xFF)
+    }
+}
+
+#[derive(Default)]
+struct LoopbackPort {
+    // Byte offered while waiting on an external clock
+    offered: Option<u8>,
+    // Byte received from the partner driving the clock
+    received: Option<u8>,
+}
+
+// Connects two emulations running in the same process
+pub struct LoopbackCable {
+    ports: Rc<RefCell<[LoopbackPort; 2]>>,
+    side: usize,
+}
+
+impl LoopbackCable {
+    pub fn pair() -> (LoopbackCable, LoopbackCable) {
+        let ports = Rc::new(RefCell::new([LoopbackPort::default(), LoopbackPort::default()]));
+        (
+            LoopbackCable { ports: ports.clone(), side: 0 },
+            LoopbackCable { ports, side: 1 },
+        )
+    }
+}
+
+impl LinkCable for LoopbackCable {
+    fn exchange(&mut self, outgoing: u8) -> Option<u8> {
+        let mut ports = self.ports.borrow_mut();
+        let partner = &mut ports[1 - self.side];
+        partner.received = Some(outgoing);
+        Some(partner.offered.take().unwrap_or(0xFF))
+    }
+
+    fn poll(&mut self, outgoing: u8) -> Option<u8> {
+        let mut ports = self.ports.borrow_mut();
+        let port = &mut ports[self.side];
+        port.offered = Some(outgoing);
+        port.received.take()
+    }
+}
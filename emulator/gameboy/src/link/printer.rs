@@ -0,0 +1,279 @@
+use std::{cell::RefCell, io, path::PathBuf, rc::Rc};
+
+use crate::{ColoredPixel, GameBoyFrame, SCREEN_WIDTH};
+
+use super::LinkCable;
+
+// https://gbdev.io/pandocs/Gameboy_Printer.html
+const MAGIC: [u8; 2] = [0x88, 0x33];
+const DEVICE_ID: u8 = 0x81;
+
+const COMMAND_INIT: u8 = 0x01;
+const COMMAND_PRINT: u8 = 0x02;
+const COMMAND_DATA: u8 = 0x04;
+const COMMAND_STATUS: u8 = 0x0F;
+
+const STATUS_CHECKSUM_ERROR: u8 = 0b00000001;
+const STATUS_BUSY: u8 = 0b00000010;
+const STATUS_IMAGE_FULL: u8 = 0b00000100;
+const STATUS_UNPROCESSED_DATA: u8 = 0b00001000;
+
+// A band is two rows of 20 tiles, 160x16 pixels
+const TILES_PER_ROW: usize = (SCREEN_WIDTH / 8) as usize;
+const TILE_BYTES: usize = 16;
+
+// Status inquiries answered as busy after a print
+const BUSY_INQUIRIES: u8 = 2;
+
+#[derive(Clone, Copy)]
+enum PacketState {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    DeviceId,
+    Status,
+}
+
+struct Printer {
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    // Decompressed tile data of the bands waiting to be printed
+    image: Vec<u8>,
+    status: u8,
+    busy_inquiries: u8,
+    pages: Vec<GameBoyFrame>,
+    output_dir: Option<PathBuf>,
+    // Pages that could not be saved, until the frontend takes them
+    save_errors: Vec<io::Error>,
+}
+
+// Game Boy Printer plugged to the link cable. Clones share the same printer,
+// so one can be connected to the GameBoy while another reads the pages.
+#[derive(Clone)]
+pub struct GameBoyPrinter {
+    printer: Rc<RefCell<Printer>>,
+}
+
+impl GameBoyPrinter {
+    pub fn new() -> Self {
+        GameBoyPrinter {
+            printer: Rc::new(RefCell::new(Printer {
+                state: PacketState::Magic(0),
+                command: 0,
+                compressed: false,
+                length: 0,
+                data: Vec::new(),
+                checksum: 0,
+                received_checksum: 0,
+                image: Vec::new(),
+                status: 0,
+                busy_inquiries: 0,
+                pages: Vec::new(),
+                output_dir: None,
+                save_errors: Vec::new(),
+            })),
+        }
+    }
+
+    // Every printed page is also saved as a PNG in this directory
+    pub fn with_output_dir(dir: PathBuf) -> Self {
+        let printer = GameBoyPrinter::new();
+        printer.printer.borrow_mut().output_dir = Some(dir);
+        printer
+    }
+
+    pub fn pages(&self) -> Vec<GameBoyFrame> {
+        self.printer.borrow().pages.clone()
+    }
+
+    // Errors of the pages that could not be saved since the last call
+    pub fn take_save_errors(&self) -> Vec<io::Error> {
+        std::mem::take(&mut self.printer.borrow_mut().save_errors)
+    }
+}
+
+impl Default for GameBoyPrinter {
+    fn default() -> Self {
+        GameBoyPrinter::new()
+    }
+}
+
+impl LinkCable for GameBoyPrinter {
+    fn exchange(&mut self, outgoing: u8) -> Option<u8> {
+        Some(self.printer.borrow_mut().receive(outgoing))
+    }
+}
+
+impl Printer {
+    // Returns the byte shifted out while receiving one from the GameBoy
+    fn receive(&mut self, byte: u8) -> u8 {
+        match self.state {
+            PacketState::Magic(index) => {
+                if byte == MAGIC[index] {
+                    self.state = if index + 1 < MAGIC.len() { PacketState::Magic(index + 1) } else { PacketState::Command };
+                } else {
+                    self.state = PacketState::Magic(0);
+                }
+            },
+            PacketState::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                self.state = PacketState::Compression;
+            },
+            PacketState::Compression => {
+                self.compressed = byte & 0b1 > 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.state = PacketState::LengthLow;
+            },
+            PacketState::LengthLow => {
+                self.length = byte as u16;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.state = PacketState::LengthHigh;
+            },
+            PacketState::LengthHigh => {
+                self.length |= (byte as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.clear();
+                self.state = if self.length > 0 { PacketState::Data } else { PacketState::ChecksumLow };
+            },
+            PacketState::Data => {
+                self.data.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.data.len() == self.length as usize {
+                    self.state = PacketState::ChecksumLow;
+                }
+            },
+            PacketState::ChecksumLow => {
+                self.received_checksum = byte as u16;
+                self.state = PacketState::ChecksumHigh;
+            },
+            PacketState::ChecksumHigh => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.process_packet();
+                self.state = PacketState::DeviceId;
+            },
+            PacketState::DeviceId => {
+                self.state = PacketState::Status;
+                return DEVICE_ID;
+            },
+            PacketState::Status => {
+                self.state = PacketState::Magic(0);
+                return self.status;
+            },
+        }
+        0x00
+    }
+
+    fn process_packet(&mut self) {
+        if self.checksum != self.received_checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            COMMAND_INIT => {
+                self.image.clear();
+                self.status = 0;
+                self.busy_inquiries = 0;
+            },
+            COMMAND_DATA => {
+                if self.data.is_empty() {
+                    // An empty data packet ends the image
+                    self.status |= STATUS_IMAGE_FULL;
+                } else {
+                    let band = if self.compressed { decompress(&self.data) } else { self.data.clone() };
+                    self.image.extend(band);
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+            },
+            COMMAND_PRINT if self.data.len() >= 4 => self.print(self.data[2]),
+            COMMAND_STATUS if self.busy_inquiries > 0 => {
+                self.busy_inquiries -= 1;
+                if self.busy_inquiries == 0 {
+                    self.status &= !STATUS_BUSY;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, palette: u8) {
+        let page = render(&self.image, palette);
+        self.image.clear();
+        self.status = (self.status | STATUS_BUSY) & !(STATUS_IMAGE_FULL | STATUS_UNPROCESSED_DATA);
+        self.busy_inquiries = BUSY_INQUIRIES;
+
+        if page.height == 0 {
+            return;
+        }
+
+        if let Some(dir) = &self.output_dir {
+            let path = dir.join(format!("print_{:03}.png", self.pages.len() + 1));
+            if let Err(error) = page.save_png(&path) {
+                let message = format!("Could not save printed page {}: {}", path.display(), error);
+                self.save_errors.push(io::Error::new(error.kind(), message));
+            }
+        }
+        self.pages.push(page);
+    }
+}
+
+// A byte with bit 7 set repeats the next byte (n & 0x7F) + 2 times,
+// otherwise the next n + 1 bytes are copied as they are
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+        if control & 0x80 > 0 {
+            if let Some(&value) = data.get(i) {
+                output.extend(std::iter::repeat_n(value, (control & 0x7F) as usize + 2));
+            }
+            i += 1;
+        } else {
+            let end = (i + control as usize + 1).min(data.len());
+            output.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+
+    output
+}
+
+fn render(image: &[u8], palette: u8) -> GameBoyFrame {
+    // The printer treats a zero palette as the default one
+    let palette = if palette == 0 { 0xE4 } else { palette };
+
+    let tile_rows = image.len() / (TILES_PER_ROW * TILE_BYTES);
+    let width = TILES_PER_ROW * 8;
+    let height = tile_rows * 8;
+    let mut buffer = vec![ColoredPixel::White; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let tile = (y / 8) * TILES_PER_ROW + x / 8;
+            let line = tile * TILE_BYTES + (y % 8) * 2;
+            let bit = 7 - (x % 8);
+            let low = (image[line] >> bit) & 0b1;
+            let high = (image[line + 1] >> bit) & 0b1;
+            let value = (high << 1) | low;
+
+            buffer[y * width + x] = ColoredPixel::from(palette >> (value * 2));
+        }
+    }
+
+    GameBoyFrame::new(width as u32, height as u32, buffer)
+}
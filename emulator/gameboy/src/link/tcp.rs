@@ -0,0 +1,136 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::LinkCable;
+
+pub const DEFAULT_SYNC_WINDOW: Duration = Duration::from_millis(100);
+
+// Every frame is a tag, a sequence number and the byte being transferred
+const FRAME_SIZE: usize = 3;
+const TRANSFER_TAG: u8 = 0x01;
+const REPLY_TAG: u8 = 0x02;
+
+enum Frame {
+    // Sent by the side driving the clock
+    Transfer(u8, u8),
+    // The partner answers with the byte it shifted out
+    Reply(u8, u8),
+}
+
+// Link cable to another emulator over TCP. Bytes are exchanged in lock-step:
+// the side driving the clock keeps running while its transfer waits for the
+// reply of the partner, and the byte is only shifted once the reply arrives.
+// A partner that does not answer within the sync window is taken as an
+// unplugged cable and the transfer ends with 0xFF.
+pub struct TcpLinkCable {
+    stream: TcpStream,
+    frames: Receiver<Frame>,
+    sync_window: Duration,
+    sequence: u8,
+    // Our byte and when to give up while the transfer waits for the partner
+    pending: Option<(u8, Instant)>,
+}
+
+impl TcpLinkCable {
+    // Waits for a partner to connect on the given port
+    pub fn listen(port: u16, sync_window: Duration) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        TcpLinkCable::accept(&listener, sync_window)
+    }
+
+    pub fn accept(listener: &TcpListener, sync_window: Duration) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        TcpLinkCable::new(stream, sync_window)
+    }
+
+    pub fn connect<A: ToSocketAddrs>(address: A, sync_window: Duration) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        TcpLinkCable::new(stream, sync_window)
+    }
+
+    fn new(stream: TcpStream, sync_window: Duration) -> io::Result<Self> {
+        // Every transfer is a single small frame waiting for an answer
+        stream.set_nodelay(true)?;
+
+        // Frames are read in the background so polling never blocks the emulation
+        let mut reader = stream.try_clone()?;
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; FRAME_SIZE];
+            while reader.read_exact(&mut buffer).is_ok() {
+                let frame = match buffer[0] {
+                    TRANSFER_TAG => Frame::Transfer(buffer[1], buffer[2]),
+                    REPLY_TAG => Frame::Reply(buffer[1], buffer[2]),
+                    _ => break,
+                };
+                if sender.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(TcpLinkCable { stream, frames, sync_window, sequence: 0, pending: None })
+    }
+
+    fn send(&mut self, tag: u8, sequence: u8, value: u8) {
+        // A broken connection behaves as an unplugged cable
+        let _ = self.stream.write_all(&[tag, sequence, value]);
+    }
+}
+
+impl LinkCable for TcpLinkCable {
+    fn exchange(&mut self, outgoing: u8) -> Option<u8> {
+        self.sequence = self.sequence.wrapping_add(1);
+        self.send(TRANSFER_TAG, self.sequence, outgoing);
+        self.pending = Some((outgoing, Instant::now() + self.sync_window));
+        self.receive()
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        let Some((outgoing, deadline)) = self.pending else {
+            return Some(0xFF);
+        };
+
+        loop {
+            let incoming = match self.frames.try_recv() {
+                Ok(Frame::Reply(sequence, value)) if sequence == self.sequence => value,
+                // Reply to a transfer that both sides started at once
+                Ok(Frame::Reply(_, _)) => continue,
+                // Both sides drive the clock, each gets the other's byte
+                Ok(Frame::Transfer(sequence, value)) => {
+                    self.send(REPLY_TAG, sequence, outgoing);
+                    value
+                },
+                Err(TryRecvError::Empty) if Instant::now() < deadline => return None,
+                // A late reply is dropped, its sequence is not the current one
+                Err(TryRecvError::Empty) => 0xFF,
+                // The partner is gone, as if the cable was unplugged
+                Err(TryRecvError::Disconnected) => 0xFF,
+            };
+            self.pending = None;
+            return Some(incoming);
+        }
+    }
+
+    fn poll(&mut self, outgoing: u8) -> Option<u8> {
+        while let Ok(frame) = self.frames.try_recv() {
+            if let Frame::Transfer(sequence, value) = frame {
+                self.send(REPLY_TAG, sequence, outgoing);
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for TcpLinkCable {
+    fn drop(&mut self) {
+        // Stops the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
@@ -0,0 +1,220 @@
+#[cfg(test)]
+use std::{io::{Read, Write}, net::{TcpListener, TcpStream}, thread, time::{Duration, Instant}};
+
+#[cfg(test)]
+use crate::{gameboy::GameBoy, io::serial::CLOCKS_PER_BIT, link::{LinkCable, LoopbackCable, printer::GameBoyPrinter, tcp::TcpLinkCable}, ColoredPixel, mmu::MMU};
+
+#[cfg(test)]
+const TRANSFER_CYCLES: u16 = 8 * CLOCKS_PER_BIT / 4;
+
+#[test]
+fn disconnected_shifts_in_ones() {
+    let mut gb = GameBoy::new(None);
+
+    MMU::write_byte(&mut gb, 0xFF01, 0x42);
+    MMU::write_byte(&mut gb, 0xFF02, 0x81);
+
+    for _ in 0..TRANSFER_CYCLES - 1 {
+        gb.cycle();
+    }
+    // The transfer is still running one cycle before the eighth bit
+    assert_eq!(MMU::read_byte(&gb, 0xFF02), 0xFF);
+    assert_eq!(MMU::read_byte(&gb, 0xFF01), 0x7F);
+
+    gb.cycle();
+    assert_eq!(MMU::read_byte(&gb, 0xFF01), 0xFF);
+    assert_eq!(MMU::read_byte(&gb, 0xFF02), 0x7F);
+    assert_eq!(MMU::read_byte(&gb, 0xFF0F) & 0b1000, 0b1000);
+}
+
+#[test]
+fn loopback_transfer() {
+    let (master_cable, slave_cable) = LoopbackCable::pair();
+
+    let mut master = GameBoy::new(None);
+    master.link_cable = Box::new(master_cable);
+    let mut slave = GameBoy::new(None);
+    slave.link_cable = Box::new(slave_cable);
+
+    // The slave waits for the master clock
+    MMU::write_byte(&mut slave, 0xFF01, 0x99);
+    MMU::write_byte(&mut slave, 0xFF02, 0x80);
+    slave.cycle();
+
+    MMU::write_byte(&mut master, 0xFF01, 0x42);
+    MMU::write_byte(&mut master, 0xFF02, 0x81);
+
+    for _ in 0..TRANSFER_CYCLES {
+        master.cycle();
+        slave.cycle();
+    }
+
+    assert_eq!(MMU::read_byte(&master, 0xFF01), 0x99);
+    assert_eq!(MMU::read_byte(&slave, 0xFF01), 0x42);
+    assert_eq!(MMU::read_byte(&master, 0xFF0F) & 0b1000, 0b1000);
+    assert_eq!(MMU::read_byte(&slave, 0xFF0F) & 0b1000, 0b1000);
+    assert_eq!(MMU::read_byte(&slave, 0xFF02) & 0x80, 0);
+}
+
+#[test]
+fn tcp_transfer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let sync_window = Duration::from_secs(5);
+
+    let slave = thread::spawn(move || {
+        let mut slave = GameBoy::new(None);
+        slave.link_cable = Box::new(TcpLinkCable::connect(address, sync_window).unwrap());
+
+        MMU::write_byte(&mut slave, 0xFF01, 0x99);
+        MMU::write_byte(&mut slave, 0xFF02, 0x80);
+        while MMU::read_byte(&slave, 0xFF02) & 0x80 != 0 {
+            slave.tick().unwrap();
+        }
+        MMU::read_byte(&slave, 0xFF01)
+    });
+
+    let mut master = GameBoy::new(None);
+    master.link_cable = Box::new(TcpLinkCable::accept(&listener, sync_window).unwrap());
+
+    MMU::write_byte(&mut master, 0xFF01, 0x42);
+    MMU::write_byte(&mut master, 0xFF02, 0x81);
+    while MMU::read_byte(&master, 0xFF02) & 0x80 != 0 {
+        master.tick().unwrap();
+    }
+
+    assert_eq!(MMU::read_byte(&master, 0xFF01), 0x99);
+    assert_eq!(slave.join().unwrap(), 0x42);
+}
+
+#[test]
+fn tcp_transfer_waits_for_a_slow_partner() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut partner = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+    let mut master = GameBoy::new(None);
+    master.link_cable = Box::new(TcpLinkCable::accept(&listener, Duration::from_secs(5)).unwrap());
+    MMU::write_byte(&mut master, 0xFF01, 0x42);
+    MMU::write_byte(&mut master, 0xFF02, 0x81);
+
+    // The partner got the transfer but has not answered, the master keeps
+    // running without shifting any bit
+    let mut frame = [0u8; 3];
+    partner.read_exact(&mut frame).unwrap();
+    assert_eq!(frame, [0x01, 1, 0x42]);
+    for _ in 0..TRANSFER_CYCLES * 4 {
+        master.cycle();
+    }
+    assert_eq!(MMU::read_byte(&master, 0xFF02) & 0x80, 0x80);
+    assert_eq!(MMU::read_byte(&master, 0xFF01), 0x42);
+
+    // The late reply still completes the transfer
+    partner.write_all(&[0x02, 1, 0x99]).unwrap();
+    while MMU::read_byte(&master, 0xFF02) & 0x80 != 0 {
+        master.tick().unwrap();
+    }
+    assert_eq!(MMU::read_byte(&master, 0xFF01), 0x99);
+}
+
+#[test]
+fn tcp_transfer_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let _partner = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let sync_window = Duration::from_millis(50);
+
+    let mut master = GameBoy::new(None);
+    master.link_cable = Box::new(TcpLinkCable::accept(&listener, sync_window).unwrap());
+    let start = Instant::now();
+    MMU::write_byte(&mut master, 0xFF01, 0x42);
+    MMU::write_byte(&mut master, 0xFF02, 0x81);
+
+    // The partner never answers, the transfer ends as with an unplugged cable
+    while MMU::read_byte(&master, 0xFF02) & 0x80 != 0 {
+        master.tick().unwrap();
+    }
+    assert!(start.elapsed() >= sync_window);
+    assert_eq!(MMU::read_byte(&master, 0xFF01), 0xFF);
+    assert_eq!(MMU::read_byte(&master, 0xFF0F) & 0b1000, 0b1000);
+}
+
+
+#[cfg(test)]
+fn send_packet(printer: &mut GameBoyPrinter, command: u8, compression: u8, data: &[u8]) -> (u8, u8) {
+    let length = data.len() as u16;
+    let mut packet = vec![0x88, 0x33, command, compression, length as u8, (length >> 8) as u8];
+    packet.extend_from_slice(data);
+    let checksum = packet[2..].iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+    packet.extend_from_slice(&[checksum as u8, (checksum >> 8) as u8]);
+
+    for byte in packet {
+        assert_eq!(printer.exchange(byte), Some(0x00));
+    }
+    (printer.exchange(0x00).unwrap(), printer.exchange(0x00).unwrap())
+}
+
+#[cfg(test)]
+fn print_band(printer: &mut GameBoyPrinter) {
+    let band = [0xFF; 40 * 16];
+    send_packet(printer, 0x01, 0, &[]);
+    send_packet(printer, 0x04, 0, &band);
+    send_packet(printer, 0x04, 0, &[]);
+    send_packet(printer, 0x02, 0, &[0x01, 0x13, 0xE4, 0x40]);
+}
+
+#[test]
+fn printer_prints_page() {
+    let mut printer = GameBoyPrinter::new();
+
+    assert_eq!(send_packet(&mut printer, 0x01, 0, &[]), (0x81, 0x00));
+
+    // One band of 40 tiles, the first line of every tile is color 3, the rest color 0
+    let mut tile = vec![0xFF, 0xFF];
+    tile.extend_from_slice(&[0x00; 14]);
+    let band: Vec<u8> = tile.iter().cycle().take(40 * 16).cloned().collect();
+    let (_, status) = send_packet(&mut printer, 0x04, 0, &band);
+    assert_eq!(status & 0b1000, 0b1000);
+
+    // The same band compressed
+    let compressed: Vec<u8> = (0..40).flat_map(|_| vec![0x80, 0xFF, 0x8C, 0x00]).collect();
+    send_packet(&mut printer, 0x04, 1, &compressed);
+    send_packet(&mut printer, 0x04, 0, &[]);
+
+    let (_, status) = send_packet(&mut printer, 0x02, 0, &[0x01, 0x13, 0xE4, 0x40]);
+    assert_eq!(status & 0b0010, 0b0010);
+
+    let pages = printer.pages();
+    assert_eq!(pages.len(), 1);
+    let page = &pages[0];
+    assert_eq!((page.width, page.height), (160, 32));
+    for y in 0..32 {
+        let expected = if y % 8 == 0 { ColoredPixel::Black } else { ColoredPixel::White };
+        assert_eq!(page.buffer[y * 160 + 7], expected);
+    }
+
+    // A bad checksum is reported in the status byte
+    for byte in [0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00] {
+        printer.exchange(byte);
+    }
+    assert_eq!(printer.exchange(0x00), Some(0x81));
+    assert_eq!(printer.exchange(0x00).unwrap() & 0b1, 0b1);
+}
+
+#[test]
+fn printer_reports_save_errors() {
+    let dir = std::env::temp_dir().join(format!("gameboy-printer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut printer = GameBoyPrinter::with_output_dir(dir.clone());
+
+    print_band(&mut printer);
+    assert!(dir.join("print_001.png").exists());
+    assert!(printer.take_save_errors().is_empty());
+
+    // The page is still printed when it can not be saved
+    std::fs::remove_dir_all(&dir).unwrap();
+    print_band(&mut printer);
+    assert_eq!(printer.pages().len(), 2);
+    let errors = printer.take_save_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("print_002.png"));
+    assert!(printer.take_save_errors().is_empty());
+}
@@ -1,6 +1,6 @@
-use crate::{ppu::*, rom::*, cartridge::Cartridge};
+use crate::{ppu::*, rom::*, cartridge::Cartridge, error::GameBoyError, savestate::{StateReader, StateWriter}};
 
-use super::{io::{io::IO, interrupts::Interrupts}, gameboy::GameBoy};
+use super::{io::{io::IO, interrupts::Interrupts, dma::DMA}, gameboy::GameBoy};
 
 pub(crate) type Address = u16;
 
@@ -22,7 +22,9 @@ pub(crate) const EXTRAM_SIZE: usize = (EXTRAM_END - EXTRAM_BEGIN + 1) as usize;
 
 pub(crate) const WRAM_BEGIN: Address = 0xC000;
 pub(crate) const WRAM_END: Address = 0xDFFF;
-pub(crate) const WRAM_SIZE: usize = (WRAM_END - WRAM_BEGIN + 1) as usize;
+// Bank 0 is always at C000, D000 maps one of the 7 others on the CGB
+pub(crate) const WRAM_BANK_SIZE: usize = 0x1000;
+pub(crate) const WRAM_BANKS: usize = 8;
 
 pub(crate) const ERAM_BEGIN: Address = 0xE000;
 pub(crate) const ERAM_END: Address = 0xFDFF;
@@ -46,35 +48,48 @@ pub(crate) const INTERRUPT_ENABLE_ADDRESS: Address = 0xFFFF;
 
 pub(crate) struct MMU {
     is_boot_rom_mapped: bool,
-    bootrom: ROM,
+    // Models without a boot ROM start at the cartridge entry point
+    pub(crate) bootrom: Option<BootROM>,
     eram: [u8; EXTRAM_SIZE],
-    wram: [u8; WRAM_SIZE],
+    wram: [u8; WRAM_BANK_SIZE * WRAM_BANKS],
+    // SVBK, 0 selects bank 1
+    wram_bank: u8,
     hram: [u8; HRAM_SIZE],
 }
 
 impl MMU {
-    pub fn new() -> Self {
-        let bootrom = ROM::dmg();
+    pub fn new(bootrom: Option<BootROM>) -> Self {
         MMU { 
-            is_boot_rom_mapped: true, 
+            is_boot_rom_mapped: bootrom.is_some(), 
             bootrom,
             eram: [0; EXTRAM_SIZE], 
-            wram: [0; WRAM_SIZE], 
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANKS], 
+            wram_bank: 0,
             hram: [0; HRAM_SIZE],
         }
     }
 
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.is_boot_rom_mapped);
+        state.write_bytes(&self.eram);
+        state.write_bytes(&self.wram);
+        state.write_u8(self.wram_bank);
+        state.write_bytes(&self.hram);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        self.is_boot_rom_mapped = state.read_bool()?;
+        state.read_bytes(&mut self.eram)?;
+        state.read_bytes(&mut self.wram)?;
+        self.wram_bank = state.read_u8()?;
+        state.read_bytes(&mut self.hram)
+    }
+
     pub(super) fn read_byte(gb: &GameBoy, address: Address) -> u8 {
         match address {
             GAMEROM_0_BEGIN ..= GAMEROM_0_END => {
-                match address {
-                    BOOT_BEGIN ..= BOOT_END => {
-                        if gb.mmu.is_boot_rom_mapped {
-                            gb.mmu.bootrom.read_byte(address)
-                        }else{
-                            Cartridge::read_byte(&gb, address)
-                        }
-                    },
+                match &gb.mmu.bootrom {
+                    Some(bootrom) if gb.mmu.is_boot_rom_mapped && bootrom.maps(address) => bootrom.read_byte(address),
                     _ => Cartridge::read_byte(&gb, address)
                 }
             },
@@ -84,7 +99,8 @@ impl MMU {
             WRAM_BEGIN ..= WRAM_END => MMU::read_wram(gb, address),
             // ERAM is mapped to WRAM, so we change its base
             ERAM_BEGIN ..= ERAM_END => MMU::read_wram(gb, address-ERAM_BEGIN+WRAM_BEGIN),
-            OAM_BEGIN ..= OAM_END => PPU::read_byte(gb, address),
+            // OAM is not accessible while a DMA transfer is running
+            OAM_BEGIN ..= OAM_END => if DMA::is_active(gb) { 0xFF } else { PPU::read_byte(gb, address) },
             // https://gbdev.io/pandocs/Memory_Map.html#fea0-feff-range
             NOTUSABLE_BEGIN ..= NOTUSABLE_END => 0xFF,
             IO_BEGIN ..= IO_END => IO::read_byte(gb, address),
@@ -106,7 +122,7 @@ impl MMU {
             WRAM_BEGIN ..= WRAM_END => MMU::write_wram(gb, address, value),
             // ERAM is mapped to WRAM, so we change its base
             ERAM_BEGIN ..= ERAM_END => MMU::write_wram(gb, address-ERAM_BEGIN+WRAM_BEGIN, value),
-            OAM_BEGIN ..= OAM_END => PPU::write_byte(gb, address, value),
+            OAM_BEGIN ..= OAM_END => if !DMA::is_active(gb) { PPU::write_byte(gb, address, value) },
             NOTUSABLE_BEGIN ..= NOTUSABLE_END => {},
             IO_BEGIN ..= IO_END => IO::write_byte(gb, address, value),
             HRAM_BEGIN ..= HRAM_END => MMU::write_hram(gb, address, value),
@@ -115,7 +131,7 @@ impl MMU {
     }
 
     fn read_wram(gb: &GameBoy, address: Address) -> u8 {
-        gb.mmu.wram[address as usize - WRAM_BEGIN as usize]
+        gb.mmu.wram[MMU::wram_index(gb, address)]
     }
 
     fn read_eram(gb: &GameBoy, address: Address) -> u8 {
@@ -127,7 +143,26 @@ impl MMU {
     }
 
     fn write_wram(gb: &mut GameBoy, address: Address, value: u8) {
-        gb.mmu.wram[address as usize - WRAM_BEGIN as usize] = value;
+        let index = MMU::wram_index(gb, address);
+        gb.mmu.wram[index] = value;
+    }
+
+    // https://gbdev.io/pandocs/CGB_Registers.html#ff70--svbk-cgb-mode-only-wram-bank
+    fn wram_index(gb: &GameBoy, address: Address) -> usize {
+        let offset = address as usize - WRAM_BEGIN as usize;
+        if offset < WRAM_BANK_SIZE {
+            return offset;
+        }
+        let bank = if gb.io.cgb_mode { (gb.mmu.wram_bank as usize).max(1) } else { 1 };
+        bank * WRAM_BANK_SIZE + offset - WRAM_BANK_SIZE
+    }
+
+    pub(crate) fn read_wram_bank(gb: &GameBoy) -> u8 {
+        0b1111_1000 | gb.mmu.wram_bank
+    }
+
+    pub(crate) fn write_wram_bank(gb: &mut GameBoy, value: u8) {
+        gb.mmu.wram_bank = value & 0b111;
     }
 
     fn write_eram(gb: &mut GameBoy, address: Address, value: u8) {
@@ -138,15 +173,11 @@ impl MMU {
         gb.mmu.hram[address as usize - HRAM_BEGIN as usize] = value;
     }
 
-    pub(crate) fn set_boot_mapping(gb: &mut GameBoy, value: u8) {
-        gb.mmu.is_boot_rom_mapped = value == 0;
+    pub(crate) fn is_boot_rom_mapped(gb: &GameBoy) -> bool {
+        gb.mmu.is_boot_rom_mapped
     }
 
-    pub(super) fn read_next_byte(gb: &GameBoy, address: Address) -> u8 {
-        MMU::read_byte(&gb, address+1)
-    }
-    
-    pub(super) fn read_next_word(gb: &GameBoy, address: Address) -> u16 {
-        ((MMU::read_byte(&gb, address+2) as u16) << 8) | (MMU::read_byte(&gb, address+1) as u16)
+    pub(crate) fn set_boot_mapping(gb: &mut GameBoy, value: u8) {
+        gb.mmu.is_boot_rom_mapped = value == 0;
     }
 }
\ No newline at end of file
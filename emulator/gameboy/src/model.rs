@@ -0,0 +1,90 @@
+use std::{fmt, str::FromStr};
+
+use crate::{cartridge::Cartridge, mmu::Address};
+
+// Game Boy hardware revisions, each with its own boot ROM
+// https://gbdev.io/pandocs/Power_Up_Sequence.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    // Early Japanese DMG
+    DMG0,
+    DMG,
+    // Game Boy Pocket and Light
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+}
+
+impl Model {
+    // AF, BC, DE and HL when the boot ROM jumps to the cartridge. The DMG and
+    // MGB flags assume a valid header checksum
+    // https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers
+    pub(crate) fn post_boot_registers(&self, cartridge: Option<&Cartridge>) -> [u16; 4] {
+        match self {
+            // Games without CGB support get the title checksum of Nintendo
+            // games in B, and HL points to the palette it picked
+            Model::CGB if !Model::cgb_cartridge(cartridge) => {
+                let checksum = cartridge.and_then(Cartridge::nintendo_title_checksum).unwrap_or(0);
+                let hl = if checksum == 0x43 || checksum == 0x58 { 0x991A } else { 0x007C };
+                [0x1180, (checksum as u16) << 8, 0x0008, hl]
+            },
+            Model::DMG0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            Model::DMG => [0x01B0, 0x0013, 0x00D8, 0x014D],
+            Model::MGB => [0xFFB0, 0x0013, 0x00D8, 0x014D],
+            Model::SGB => [0x0100, 0x0014, 0x0000, 0xC060],
+            Model::SGB2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+            Model::CGB => [0x1180, 0x0000, 0xFF56, 0x000D],
+        }
+    }
+
+    // Internal counter behind DIV when the boot ROM jumps to the cartridge,
+    // the CGB boot ROM takes longer for games without CGB support
+    pub(crate) fn post_boot_system_counter(&self, cartridge: Option<&Cartridge>) -> u16 {
+        match self {
+            Model::DMG0 => 0x1830,
+            Model::DMG | Model::MGB => 0xABCC,
+            Model::SGB | Model::SGB2 => 0xD85C,
+            Model::CGB if Model::cgb_cartridge(cartridge) => 0x1EA0,
+            Model::CGB => 0x267C,
+        }
+    }
+
+    // Hardware registers the boot ROM leaves with another value than the
+    // DMG one, P1 is 0xCF on all models even if some read 0xC7 at times
+    // https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers
+    pub(crate) fn post_boot_io_registers(&self) -> &'static [(Address, u8)] {
+        match self {
+            Model::SGB | Model::SGB2 => &[(0xFF26, 0xF0)],
+            Model::CGB => &[(0xFF02, 0x7F), (0xFF46, 0x00)],
+            _ => &[],
+        }
+    }
+
+    fn cgb_cartridge(cartridge: Option<&Cartridge>) -> bool {
+        cartridge.is_some_and(Cartridge::supports_cgb)
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let model = match name.to_uppercase().as_str() {
+            "DMG0" => Model::DMG0,
+            "DMG" => Model::DMG,
+            "MGB" => Model::MGB,
+            "SGB" => Model::SGB,
+            "SGB2" => Model::SGB2,
+            "CGB" => Model::CGB,
+            _ => return Err(format!("unknown model {}, expected DMG0, DMG, MGB, SGB, SGB2 or CGB", name)),
+        };
+        Ok(model)
+    }
+}
@@ -0,0 +1,110 @@
+use std::{io::Error, path::Path};
+
+use crate::{error::GameBoyError, savestate::{StateReader, StateWriter}};
+
+mod tests;
+
+const MAGIC: &[u8; 4] = b"GBMV";
+const VERSION: u8 = 2;
+
+const START_POWER_ON: u8 = 0;
+const START_SAVE_STATE: u8 = 1;
+
+// Where the recording begins
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MovieStart {
+    PowerOn,
+    SaveState(Vec<u8>),
+}
+
+// Joypad input of every frame, one byte per frame with the pressed buttons:
+// A, B, Select, Start, Right, Left, Up, Down from bit 0 to 7
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub(crate) rom_checksum: u32,
+    pub(crate) start: MovieStart,
+    // Cycles of the frame already emulated when the recording began, the
+    // debugger can leave a frame halfway
+    pub(crate) frame_cycles: u32,
+    pub(crate) inputs: Vec<u8>,
+}
+
+impl Movie {
+    pub(crate) fn new(rom_checksum: u32, start: MovieStart, frame_cycles: u32) -> Self {
+        Movie { rom_checksum, start, frame_cycles, inputs: Vec::new() }
+    }
+
+    pub fn start(&self) -> &MovieStart {
+        &self.start
+    }
+
+    pub fn frames(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn load(path: &Path) -> Result<Movie, GameBoyError> {
+        Movie::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::new();
+        movie.write_bytes(MAGIC);
+        movie.write_u8(VERSION);
+        movie.write_u32(self.rom_checksum);
+        match &self.start {
+            MovieStart::PowerOn => movie.write_u8(START_POWER_ON),
+            MovieStart::SaveState(state) => {
+                movie.write_u8(START_SAVE_STATE);
+                movie.write_u32(state.len() as u32);
+                movie.write_bytes(state);
+            },
+        }
+        movie.write_u32(self.frame_cycles);
+        movie.write_u32(self.inputs.len() as u32);
+        movie.write_bytes(&self.inputs);
+        movie.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, GameBoyError> {
+        Movie::parse(data).map_err(|error| match error {
+            GameBoyError::InvalidSaveState(reason) => GameBoyError::InvalidMovie(reason),
+            error => error,
+        })
+    }
+
+    // The reader reports errors as save state ones
+    fn parse(data: &[u8]) -> Result<Movie, GameBoyError> {
+        let mut movie = StateReader::new(data);
+
+        let mut magic = [0; 4];
+        movie.read_bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GameBoyError::InvalidMovie(String::from("wrong header")));
+        }
+        let version = movie.read_u8()?;
+        if version != VERSION {
+            return Err(GameBoyError::InvalidMovie(format!("unsupported version {}", version)));
+        }
+        let rom_checksum = movie.read_u32()?;
+        let start = match movie.read_u8()? {
+            START_POWER_ON => MovieStart::PowerOn,
+            START_SAVE_STATE => {
+                let length = movie.read_u32()? as usize;
+                MovieStart::SaveState(movie.read_vec(length)?)
+            },
+            _ => return Err(GameBoyError::InvalidMovie(String::from("unknown start"))),
+        };
+        let frame_cycles = movie.read_u32()?;
+        let frames = movie.read_u32()? as usize;
+        let inputs = movie.read_vec(frames)?;
+        if !movie.is_exhausted() {
+            return Err(GameBoyError::InvalidMovie(String::from("trailing bytes")));
+        }
+
+        Ok(Movie { rom_checksum, start, frame_cycles, inputs })
+    }
+}
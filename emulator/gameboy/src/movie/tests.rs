@@ -0,0 +1,102 @@
+#[cfg(test)]
+use crate::{Button, Emulation, error::GameBoyError, movie::{Movie, MovieStart}};
+
+#[cfg(test)]
+fn run_with_input(emu: &mut Emulation, frames: usize) -> u32 {
+    for frame in 0..frames {
+        match frame {
+            5 => emu.button_pressed(Button::Start),
+            8 => emu.button_released(Button::Start),
+            12 => emu.button_pressed(Button::A),
+            _ => {},
+        }
+        emu.step().unwrap();
+    }
+    emu.step().unwrap().framebuffer.checksum()
+}
+
+#[test]
+fn movie_roundtrip() {
+    let mut emu = Emulation::new(None);
+    emu.start_recording();
+    run_with_input(&mut emu, 20);
+    let movie = emu.stop_recording().unwrap();
+
+    assert_eq!(movie.start(), &MovieStart::PowerOn);
+    assert_eq!(movie.frames(), 21);
+    assert_eq!(movie.inputs[5], 0b00001000);
+    assert_eq!(movie.inputs[12], 0b00000001);
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+    assert!(Movie::from_bytes(b"GBMV").is_err());
+
+    let mut bytes = movie.to_bytes();
+    bytes.push(0);
+    let error = Movie::from_bytes(&bytes).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid movie: trailing bytes");
+}
+
+// The debugger can leave a frame halfway, playback resumes at the same point
+#[test]
+fn movie_recorded_mid_frame() {
+    let mut emu = Emulation::new(None);
+    emu.step().unwrap();
+    for _ in 0..100 {
+        emu.step_instruction().unwrap();
+    }
+    emu.start_recording();
+    emu.button_pressed(Button::B);
+    let expected = run_with_input(&mut emu, 10);
+    let state = emu.save_state();
+    let movie = emu.stop_recording().unwrap();
+    assert!(movie.frame_cycles > 0);
+    // The input of the frame left halfway comes first
+    assert_eq!(movie.frames(), 11);
+    assert_eq!(movie.inputs[0], 0b00000010);
+
+    let mut replay = Emulation::new(None);
+    let frames = movie.frames();
+    replay.play_movie(Movie::from_bytes(&movie.to_bytes()).unwrap()).unwrap();
+    let mut checksum = 0;
+    for _ in 0..frames {
+        checksum = replay.step().unwrap().framebuffer.checksum();
+    }
+    assert_eq!(checksum, expected);
+    assert_eq!(replay.save_state(), state);
+}
+
+#[test]
+fn movie_playback_is_deterministic() {
+    let mut emu = Emulation::new(None);
+    for _ in 0..3 {
+        emu.step().unwrap();
+    }
+    emu.start_recording();
+    let expected = run_with_input(&mut emu, 20);
+    let state = emu.save_state();
+    let movie = emu.stop_recording().unwrap();
+    assert!(matches!(movie.start(), MovieStart::SaveState(_)));
+
+    let mut replay = Emulation::new(None);
+    let frames = movie.frames();
+    replay.play_movie(movie).unwrap();
+    let mut checksum = 0;
+    for _ in 0..frames {
+        assert!(replay.is_playing());
+        checksum = replay.step().unwrap().framebuffer.checksum();
+    }
+
+    assert!(!replay.is_playing());
+    assert_eq!(checksum, expected);
+    assert_eq!(replay.save_state(), state);
+}
+
+#[test]
+fn movie_of_another_game() {
+    let mut emu = Emulation::new(None);
+    let movie = Movie::new(0x1234_5678, MovieStart::PowerOn, 0);
+
+    let error = emu.play_movie(movie).unwrap_err();
+    assert!(matches!(error, GameBoyError::MovieMismatch));
+    assert_eq!(error.to_string(), "Movie was recorded with another game");
+    assert!(!emu.is_playing());
+}
@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use crate::{ColoredPixel, PixelLayer};
+
+// Colors of the four shades from the lightest to the darkest, as 0xRRGGBB
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShadePalette(pub [u32; 4]);
+
+impl ShadePalette {
+    pub const GREYSCALE: ShadePalette = ShadePalette([0xFFFFFF, 0xC0C0C0, 0x606060, 0x000000]);
+    // Green LCD of the original Game Boy
+    pub const DMG: ShadePalette = ShadePalette([0xE0F8D0, 0x88C070, 0x346856, 0x081820]);
+    pub const POCKET: ShadePalette = ShadePalette([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F]);
+    // Backlit screen of the Game Boy Light
+    pub const LIGHT: ShadePalette = ShadePalette([0x00B581, 0x009A71, 0x00694A, 0x004F3B]);
+
+    pub fn rgba(&self, pixel: ColoredPixel) -> [u8; 4] {
+        let [_, red, green, blue] = self.0[u8::from(pixel) as usize].to_be_bytes();
+        [red, green, blue, 0xFF]
+    }
+}
+
+// Palettes for the background and window and for both object palettes, the
+// CGB boot ROM gives DMG games a set like this one
+// https://gbdev.io/pandocs/Power_Up_Sequence.html#compatibility-palettes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DMGPalettes {
+    pub background: ShadePalette,
+    pub obj0: ShadePalette,
+    pub obj1: ShadePalette,
+}
+
+impl DMGPalettes {
+    pub const fn uniform(palette: ShadePalette) -> Self {
+        DMGPalettes { background: palette, obj0: palette, obj1: palette }
+    }
+
+    pub fn layer(&self, layer: PixelLayer) -> ShadePalette {
+        match layer {
+            PixelLayer::Background => self.background,
+            PixelLayer::Object0 => self.obj0,
+            PixelLayer::Object1 => self.obj1,
+        }
+    }
+}
+
+impl Default for DMGPalettes {
+    fn default() -> Self {
+        DMGPalettes::uniform(ShadePalette::GREYSCALE)
+    }
+}
+
+impl fmt::Display for DMGPalettes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let colors: Vec<String> = [self.background, self.obj0, self.obj1].iter()
+            .flat_map(|palette| palette.0)
+            .map(|color| format!("{:06X}", color))
+            .collect();
+        write!(f, "{}", colors.join(","))
+    }
+}
+
+// A built-in palette name, or 4 colors in hex for every layer, or 12 for the
+// background, OBJ0 and OBJ1
+impl FromStr for DMGPalettes {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let palette = match name.to_lowercase().as_str() {
+            "greyscale" | "grayscale" => Some(ShadePalette::GREYSCALE),
+            "dmg" => Some(ShadePalette::DMG),
+            "pocket" => Some(ShadePalette::POCKET),
+            "light" => Some(ShadePalette::LIGHT),
+            _ => None,
+        };
+        if let Some(palette) = palette {
+            return Ok(DMGPalettes::uniform(palette));
+        }
+
+        let unknown = format!("unknown palette {}, expected greyscale, dmg, pocket, light or 4 or 12 colors as RRGGBB", name);
+        let colors = name.split(',')
+            .map(|color| u32::from_str_radix(color.trim().trim_start_matches('#'), 16).ok().filter(|color| *color <= 0xFFFFFF))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(unknown.clone())?;
+        let palette = |index: usize| ShadePalette(colors[index * 4..index * 4 + 4].try_into().unwrap());
+        match colors.len() {
+            4 => Ok(DMGPalettes::uniform(palette(0))),
+            12 => Ok(DMGPalettes { background: palette(0), obj0: palette(1), obj1: palette(2) }),
+            _ => Err(unknown),
+        }
+    }
+}
+
+mod tests;
@@ -0,0 +1,36 @@
+#[cfg(test)]
+use crate::{ColoredPixel, GameBoyFrame, PixelLayer};
+
+#[cfg(test)]
+use super::{DMGPalettes, ShadePalette};
+
+#[test]
+fn builtin_and_custom_palettes() {
+    assert_eq!("Pocket".parse::<DMGPalettes>().unwrap(), DMGPalettes::uniform(ShadePalette::POCKET));
+    assert_eq!("greyscale".parse::<DMGPalettes>().unwrap(), DMGPalettes::default());
+
+    let custom: DMGPalettes = "FFFFFF,AAAAAA,555555,000000".parse().unwrap();
+    assert_eq!(custom.obj1, ShadePalette([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]));
+
+    let colors = "E0F8D0,88C070,346856,081820,FFFFFF,C0C0C0,606060,000000,#C4CFA1,#8B956D,#4D533C,#1F1F1F";
+    let layers: DMGPalettes = colors.parse().unwrap();
+    assert_eq!(layers, DMGPalettes { background: ShadePalette::DMG, obj0: ShadePalette::GREYSCALE, obj1: ShadePalette::POCKET });
+    assert_eq!(layers.to_string().parse::<DMGPalettes>().unwrap(), layers);
+
+    assert!("sepia".parse::<DMGPalettes>().is_err());
+    assert!("FFFFFF,AAAAAA,555555".parse::<DMGPalettes>().is_err());
+    assert!("1000000,AAAAAA,555555,000000".parse::<DMGPalettes>().is_err());
+}
+
+#[test]
+fn rgba_frame() {
+    let mut frame = GameBoyFrame::new(2, 1, vec![ColoredPixel::White, ColoredPixel::DarkGray]);
+    let rgba = frame.to_rgba(&DMGPalettes::uniform(ShadePalette::DMG));
+    assert_eq!((rgba.width, rgba.height), (2, 1));
+    assert_eq!(rgba.buffer, vec![0xE0, 0xF8, 0xD0, 0xFF, 0x34, 0x68, 0x56, 0xFF]);
+
+    // Object pixels go through their own palette
+    frame.layers[1] = PixelLayer::Object1;
+    let palettes = DMGPalettes { background: ShadePalette::DMG, obj0: ShadePalette::DMG, obj1: ShadePalette::GREYSCALE };
+    assert_eq!(frame.to_rgba(&palettes).buffer, vec![0xE0, 0xF8, 0xD0, 0xFF, 0x60, 0x60, 0x60, 0xFF]);
+}
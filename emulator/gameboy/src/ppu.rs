@@ -2,11 +2,16 @@ use std::fmt;
 
 use pretty_hex::*;
 
-use super::{mmu::*, gameboy::GameBoy};
+use super::{mmu::*, gameboy::GameBoy, error::GameBoyError, savestate::{StateReader, StateWriter}};
 
 pub(crate) const BGMAP0_ADDRESS: Address = 0x9800;
 pub(crate) const BGMAP1_ADDRESS: Address = 0x9C00;
 
+// The CGB has a second bank with more tiles and the background attributes
+pub(crate) const VRAM_BANKS: usize = 2;
+pub(crate) const TILES_PER_BANK: usize = 384;
+const TILE_DATA_SIZE: usize = 0x1800;
+
 #[derive(Copy,Clone,Debug)]
 pub(crate) enum TilePixelValue {
     Zero,
@@ -24,17 +29,47 @@ impl Default for TilePixelValue {
 pub(crate) type Tile = [[TilePixelValue; 8]; 8];
 
 pub(crate) struct PPU{
-    pub(crate) vram: [u8; VRAM_SIZE],
+    pub(crate) vram: [[u8; VRAM_SIZE]; VRAM_BANKS],
+    // VBK, only switched in CGB mode
+    vram_bank: u8,
     pub(crate) oam: [u8; OAM_SIZE],
+    // Tiles of bank 1 follow the ones of bank 0
     pub(crate) tile_set: Vec<Tile>,
 }
 
 impl PPU {
     pub(super) fn new() -> PPU {
         PPU { 
-            vram: [0x0; VRAM_SIZE], 
+            vram: [[0x0; VRAM_SIZE]; VRAM_BANKS], 
+            vram_bank: 0,
             oam: [0; OAM_SIZE],
-            tile_set: vec![[[TilePixelValue::Zero; 8]; 8]; 384] 
+            tile_set: vec![[[TilePixelValue::Zero; 8]; 8]; TILES_PER_BANK * VRAM_BANKS] 
+        }
+    }
+
+    // The tile set is decoded again from VRAM after loading
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        for bank in &self.vram {
+            state.write_bytes(bank);
+        }
+        state.write_u8(self.vram_bank);
+        state.write_bytes(&self.oam);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), GameBoyError> {
+        for bank in self.vram.iter_mut() {
+            state.read_bytes(bank)?;
+        }
+        self.vram_bank = state.read_u8()?;
+        state.read_bytes(&mut self.oam)
+    }
+
+    // Decoded tiles are a cache of VRAM, rebuilt after loading a state
+    pub(crate) fn decode_tile_set(gb: &mut GameBoy) {
+        for bank in 0..VRAM_BANKS {
+            for index in (0..TILE_DATA_SIZE).step_by(2) {
+                PPU::decode_tile_row(gb, bank, index);
+            }
         }
     }
 
@@ -60,7 +95,7 @@ impl PPU {
     }
 
     pub(super) fn read_oam(gb: &GameBoy, address: Address) -> u8 {
-        gb.ppu.vram[(address - OAM_BEGIN) as usize]
+        gb.ppu.oam[(address - OAM_BEGIN) as usize]
     }  
 
     pub(super) fn write_oam(gb: &mut GameBoy, address: Address, value: u8) {
@@ -68,15 +103,31 @@ impl PPU {
     }  
 
     pub(super) fn read_vram(gb: &GameBoy, address: Address) -> u8 {
-        gb.ppu.vram[(address - VRAM_BEGIN) as usize]
+        gb.ppu.vram[gb.ppu.vram_bank as usize][(address - VRAM_BEGIN) as usize]
     }
 
     pub(crate) fn write_vram(gb: &mut GameBoy, address: Address, value: u8) {
+        let bank = gb.ppu.vram_bank as usize;
         let index = (address - VRAM_BEGIN) as usize;
-        gb.ppu.vram[index] = value;
+        gb.ppu.vram[bank][index] = value;
         // If our index is greater than 0x1800, we're not writing to the tile set storage
         // so we can just return.
-        if index >= 0x1800 { return }
+        if index >= TILE_DATA_SIZE { return }
+
+        PPU::decode_tile_row(gb, bank, index);
+    }
+
+    // https://gbdev.io/pandocs/CGB_Registers.html#ff4f--vbk-cgb-mode-only-vram-bank
+    pub(crate) fn read_vram_bank(gb: &GameBoy) -> u8 {
+        0b1111_1110 | gb.ppu.vram_bank
+    }
+
+    pub(crate) fn write_vram_bank(gb: &mut GameBoy, value: u8) {
+        gb.ppu.vram_bank = value & 0b1;
+    }
+
+    fn decode_tile_row(gb: &mut GameBoy, bank: usize, index: usize) {
+        let vram = &gb.ppu.vram[bank];
 
         // Tiles rows are encoded in two bytes with the first byte always
         // on an even address. Bitwise ANDing the address with 0xffe
@@ -85,12 +136,12 @@ impl PPU {
         let normalized_index = index & 0xFFFE;
 
         // First we need to get the two bytes that encode the tile row.
-        let byte1 = gb.ppu.vram[normalized_index];
-        let byte2 = gb.ppu.vram[normalized_index + 1];
+        let byte1 = vram[normalized_index];
+        let byte2 = vram[normalized_index + 1];
 
         // A tiles is 8 rows tall. Since each row is encoded with two bytes a tile
         // is therefore 16 bytes in total.
-        let tile_index = index / 16;
+        let tile_index = bank * TILES_PER_BANK + index / 16;
         // Every two bytes is a new row
         let row_index = (index % 16) / 2;
 
@@ -137,6 +188,6 @@ impl PPU {
 impl fmt::Display for PPU {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {:x}-{:x}\n", "PPU", VRAM_BEGIN, VRAM_END)?;
-        write!(f, "{}", pretty_hex(&self.vram))
+        write!(f, "{}", pretty_hex(&self.vram[0]))
     }
 }
\ No newline at end of file
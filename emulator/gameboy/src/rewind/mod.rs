@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+mod tests;
+
+// A snapshot is taken every REWIND_INTERVAL frames, about 20 seconds are kept
+pub const REWIND_INTERVAL: u64 = 2;
+pub const REWIND_CAPACITY: usize = 600;
+
+// Only the newest save state is kept whole, every older one is stored as the
+// run-length encoded XOR against the state that follows it
+pub(crate) struct RewindBuffer {
+    newest: Option<(u64, Vec<u8>)>,
+    deltas: VecDeque<(u64, Vec<u8>)>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        RewindBuffer { newest: None, deltas: VecDeque::new(), capacity }
+    }
+
+    pub(crate) fn push(&mut self, frame: u64, state: Vec<u8>) {
+        if let Some((newest_frame, newest_state)) = self.newest.take() {
+            if newest_state.len() == state.len() {
+                self.deltas.push_back((newest_frame, encode(&xor(&newest_state, &state))));
+            } else {
+                self.deltas.clear();
+            }
+        }
+        self.newest = Some((frame, state));
+
+        while self.deltas.len() + 1 > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    // Drops the snapshots taken after the given frame and returns the newest
+    // remaining one, or the oldest available if the history is shorter
+    pub(crate) fn rewind_to(&mut self, frame: u64) -> Option<(u64, &Vec<u8>)> {
+        while self.newest.as_ref().is_some_and(|(newest_frame, _)| *newest_frame > frame) {
+            let Some((older_frame, delta)) = self.deltas.pop_back() else { break };
+            let (_, newest_state) = self.newest.take().unwrap();
+            self.newest = Some((older_frame, xor(&newest_state, &decode(&delta))));
+        }
+
+        self.newest.as_ref().map(|(frame, state)| (*frame, state))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+}
+
+// Deltas are mostly zeros, a zero byte is followed by the length of its run
+pub(crate) fn encode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        if data[i] == 0 {
+            let mut run = 0;
+            while i < data.len() && data[i] == 0 && run < u8::MAX {
+                run += 1;
+                i += 1;
+            }
+            output.push(0);
+            output.push(run);
+        } else {
+            output.push(data[i]);
+            i += 1;
+        }
+    }
+
+    output
+}
+
+pub(crate) fn decode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        if data[i] == 0 {
+            let run = data.get(i + 1).copied().unwrap_or(0);
+            output.extend(std::iter::repeat_n(0, run as usize));
+            i += 2;
+        } else {
+            output.push(data[i]);
+            i += 1;
+        }
+    }
+
+    output
+}
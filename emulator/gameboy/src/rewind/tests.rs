@@ -0,0 +1,43 @@
+#[cfg(test)]
+use crate::{Emulation, rewind::{decode, encode, RewindBuffer, REWIND_INTERVAL}};
+
+#[test]
+fn delta_encoding() {
+    let data = [vec![0; 300], vec![1, 2, 0, 3], vec![0; 10]].concat();
+    let encoded = encode(&data);
+    assert!(encoded.len() < 12);
+    assert_eq!(decode(&encoded), data);
+}
+
+#[test]
+fn buffer_keeps_capacity() {
+    let mut buffer = RewindBuffer::new(3);
+    for frame in 1..=5 {
+        buffer.push(frame, vec![frame as u8; 16]);
+    }
+
+    assert_eq!(buffer.rewind_to(4), Some((4, &vec![4; 16])));
+    // Frames 1 and 2 fell out of the buffer
+    assert_eq!(buffer.rewind_to(0), Some((3, &vec![3; 16])));
+}
+
+#[test]
+fn emulation_rewind() {
+    let mut emu = Emulation::new(None);
+    for _ in 0..4 {
+        emu.step().unwrap();
+    }
+    let state = emu.save_state();
+    let frame = emu.step().unwrap().framebuffer;
+    for _ in 0..20 {
+        emu.step().unwrap();
+    }
+
+    emu.rewind(20).unwrap();
+    assert_eq!(emu.frames, 4);
+    assert_eq!(emu.save_state(), state);
+
+    // Replaying from there reaches the same frame again
+    assert_eq!(emu.step().unwrap().framebuffer, frame);
+    assert_eq!(emu.frames % REWIND_INTERVAL, 1);
+}
@@ -0,0 +1,83 @@
+use std::{fmt, path::Path};
+
+use pretty_hex::*;
+
+use crate::{cartridge::crc32, error::GameBoyError, model::Model};
+
+mod tests;
+
+pub(crate) const BOOT_BEGIN: u16 = 0x0000;
+pub(crate) const BOOT_END: u16 = 0x00FF;
+pub(crate) const BOOT_SIZE: usize = (BOOT_END - BOOT_BEGIN + 1) as usize;
+
+// The CGB boot ROM continues after the cartridge header, which stays visible
+// at 0x0100-0x01FF while it runs
+pub(crate) const CGB_BOOT_BEGIN: u16 = 0x0200;
+pub(crate) const CGB_BOOT_END: u16 = 0x08FF;
+pub(crate) const CGB_BOOT_SIZE: usize = CGB_BOOT_END as usize + 1;
+
+// CRC-32 of the known dumps, a boot ROM is only accepted if it is one of them
+const KNOWN_BOOT_ROMS: [(Model, usize, u32); 6] = [
+    (Model::DMG0, BOOT_SIZE, 0xC2F5CC97),
+    (Model::DMG, BOOT_SIZE, 0x59C8598E),
+    (Model::MGB, BOOT_SIZE, 0xE6920754),
+    (Model::SGB, BOOT_SIZE, 0xEC8A83B9),
+    (Model::SGB2, BOOT_SIZE, 0x53D0DD63),
+    (Model::CGB, CGB_BOOT_SIZE, 0x41884E46),
+];
+
+// Program mapped over the cartridge at power on, it unmaps itself by
+// writing to FF50 before jumping to 0x0100
+#[derive(Clone)]
+pub struct BootROM {
+    model: Model,
+    data: Vec<u8>,
+}
+
+impl BootROM {
+    // Identifies the model from the dump, unknown or modified boot ROMs are refused
+    pub fn new(data: Vec<u8>) -> Result<BootROM, GameBoyError> {
+        if data.len() != BOOT_SIZE && data.len() != CGB_BOOT_SIZE {
+            return Err(GameBoyError::InvalidBootROM(format!("{} bytes, expected {} or {}", data.len(), BOOT_SIZE, CGB_BOOT_SIZE)));
+        }
+
+        let checksum = crc32(&data);
+        match KNOWN_BOOT_ROMS.iter().find(|(_, size, crc)| *size == data.len() && *crc == checksum) {
+            Some((model, _, _)) => Ok(BootROM { model: *model, data }),
+            None => Err(GameBoyError::InvalidBootROM(format!("unknown dump with CRC-32 {:08X}", checksum))),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<BootROM, GameBoyError> {
+        BootROM::new(std::fs::read(path)?)
+    }
+
+    pub(crate) fn dmg() -> BootROM {
+        let data = include_bytes!("../../../assets/dmgrom.bin");
+        BootROM { model: Model::DMG, data: data.to_vec() }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub(crate) fn maps(&self, address: u16) -> bool {
+        match address {
+            BOOT_BEGIN ..= BOOT_END => true,
+            CGB_BOOT_BEGIN ..= CGB_BOOT_END => self.data.len() == CGB_BOOT_SIZE,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn read_byte(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+}
+
+impl fmt::Display for BootROM {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "BOOTROM {}", self.model)?;
+        write!(f, "{}", pretty_hex(&self.data))
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+use crate::{Emulation, error::GameBoyError, model::Model, debugger::Register};
+
+#[cfg(test)]
+use super::{BootROM, BOOT_SIZE};
+
+#[test]
+fn boot_rom_identified_by_hash() {
+    let dmg = include_bytes!("../../../assets/dmgrom.bin").to_vec();
+    assert_eq!(BootROM::new(dmg.clone()).unwrap().model(), Model::DMG);
+
+    let mut patched = dmg;
+    patched[0xA8] ^= 0xFF;
+    assert!(matches!(BootROM::new(patched), Err(GameBoyError::InvalidBootROM(_))));
+    assert!(matches!(BootROM::new(vec![0; BOOT_SIZE + 1]), Err(GameBoyError::InvalidBootROM(_))));
+}
+
+#[test]
+fn cgb_boot_rom_leaves_the_header_visible() {
+    let boot_rom = BootROM { model: Model::CGB, data: vec![0; super::CGB_BOOT_SIZE] };
+    assert!(boot_rom.maps(0x00FF));
+    assert!(!boot_rom.maps(0x0100));
+    assert!(!boot_rom.maps(0x01FF));
+    assert!(boot_rom.maps(0x0200));
+    assert!(boot_rom.maps(0x08FF));
+    assert!(!BootROM::dmg().maps(0x0200));
+}
+
+#[test]
+fn models_without_boot_rom_start_after_it() {
+    let mut emu = Emulation::new(None);
+    emu.set_model(Model::MGB);
+    assert_eq!(emu.model(), Model::MGB);
+    assert_eq!(emu.read_register(Register::AF), 0xFFB0);
+    assert_eq!(emu.read_register(Register::PC), 0x0100);
+
+    // The built in boot ROM comes back with the DMG
+    emu.set_model(Model::DMG);
+    assert_eq!(emu.read_register(Register::PC), 0x0000);
+    assert_eq!(emu.read_memory(0x0000), 0x31);
+}
@@ -0,0 +1,157 @@
+use std::{io::Error, path::Path};
+
+use crate::{cartridge::Cartridge, error::GameBoyError, gameboy::GameBoy, link::LinkCable, mmu::{Address, MMU}, model::Model, rom::BootROM, ColoredPixel, GameBoyFrame, CPU_CYCLES_PER_FRAME};
+
+mod tests;
+mod mooneye;
+mod screenshots;
+
+// JR -2, a jump to itself that test ROMs use to wait forever
+const JR_OPCODE: u8 = 0x18;
+const JR_SELF_OFFSET: u8 = 0xFE;
+// LD B,B does nothing, debuggers and test ROMs use it as a breakpoint
+const LD_B_B_OPCODE: u8 = 0x40;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    SerialContains(String),
+    PcReached(Address),
+    InfiniteLoop,
+    Breakpoint,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    FrameLimit,
+    Matched(StopCondition),
+}
+
+pub struct RunResult {
+    pub reason: StopReason,
+    pub frames: u64,
+    pub serial: Vec<u8>,
+    pub screen: GameBoyFrame,
+}
+
+// Runs a ROM without any interface until a stop condition is met or the
+// frame limit is reached
+pub struct Runner {
+    pub(crate) gameboy: GameBoy,
+    conditions: Vec<StopCondition>,
+    max_frames: u64,
+}
+
+impl Runner {
+    pub fn new(cartridge: Cartridge, max_frames: u64) -> Self {
+        Runner { gameboy: GameBoy::new(Some(cartridge)), conditions: Vec::new(), max_frames }
+    }
+
+    pub fn stop_when(mut self, condition: StopCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    // Emulates another model, only the DMG runs its boot ROM. The GameBoy is
+    // powered on again, so this goes before the other settings
+    pub fn model(mut self, model: Model) -> Self {
+        let cartridge = self.gameboy.cartridge.take();
+        let bootrom = (model == Model::DMG).then(BootROM::dmg);
+        self.gameboy = GameBoy::with_model(cartridge, model, bootrom);
+        self
+    }
+
+    // Starts at 0x0100 with the state the boot ROM leaves, which saves the
+    // first seconds of every run
+    pub fn skip_boot(mut self) -> Self {
+        self.gameboy.skip_boot();
+        self
+    }
+
+    // Illegal opcodes stop the run with an error instead of locking up the CPU
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.gameboy.strict = strict;
+        self
+    }
+
+    pub fn connect_link_cable(&mut self, cable: Box<dyn LinkCable>) {
+        self.gameboy.link_cable = cable;
+    }
+
+    pub fn run(&mut self) -> Result<RunResult, GameBoyError> {
+        let mut serial = Vec::new();
+        let mut cycles = 0;
+        let max_cycles = self.max_frames * CPU_CYCLES_PER_FRAME as u64;
+
+        let reason = loop {
+            if cycles >= max_cycles {
+                break StopReason::FrameLimit;
+            }
+            // Conditions on the program counter are checked before executing
+            if let Some(condition) = self.instruction_condition() {
+                break StopReason::Matched(condition);
+            }
+
+            cycles += self.gameboy.tick()? as u64;
+
+            if let Some(data) = self.gameboy.read_serial() {
+                serial.push(data);
+                if let Some(condition) = self.serial_condition(&serial) {
+                    break StopReason::Matched(condition);
+                }
+            }
+        };
+
+        Ok(RunResult {
+            reason,
+            frames: cycles / CPU_CYCLES_PER_FRAME as u64,
+            serial,
+            screen: self.gameboy.frame(),
+        })
+    }
+
+    fn instruction_condition(&self) -> Option<StopCondition> {
+        let gb = &self.gameboy;
+        // A CPU locked by an illegal opcode never leaves it
+        if gb.cpu.is_locked {
+            return self.conditions.iter().find(|condition| matches!(condition, StopCondition::InfiniteLoop)).cloned();
+        }
+        if gb.cpu.is_halted || gb.cpu.is_stopped {
+            return None;
+        }
+        let pc = gb.cpu.pc;
+        let opcode = MMU::read_byte(gb, pc);
+
+        self.conditions.iter().find(|condition| match condition {
+            StopCondition::PcReached(address) => pc == *address,
+            StopCondition::InfiniteLoop => opcode == JR_OPCODE && MMU::read_byte(gb, pc.wrapping_add(1)) == JR_SELF_OFFSET,
+            StopCondition::Breakpoint => opcode == LD_B_B_OPCODE,
+            StopCondition::SerialContains(_) => false,
+        }).cloned()
+    }
+
+    fn serial_condition(&self, serial: &[u8]) -> Option<StopCondition> {
+        let output = String::from_utf8_lossy(serial);
+        self.conditions.iter().find(|condition| match condition {
+            StopCondition::SerialContains(text) => output.contains(text.as_str()),
+            _ => false,
+        }).cloned()
+    }
+}
+
+// Compares the screen with a reference PNG. On a mismatch an image with the
+// differing pixels in black is written to `diff`
+pub fn compare_screen(screen: &GameBoyFrame, reference: &Path, diff: &Path) -> Result<bool, Error> {
+    let reference = GameBoyFrame::load_png(reference)?;
+    // Only the shades are in the PNG, not the palette of every pixel
+    if reference.width == screen.width && reference.buffer == screen.buffer {
+        return Ok(true);
+    }
+
+    let buffer = (0..(screen.width * screen.height) as usize).map(|i| {
+        let matches = reference.width == screen.width && reference.buffer.get(i) == screen.buffer.get(i);
+        if matches { ColoredPixel::White } else { ColoredPixel::Black }
+    }).collect();
+    GameBoyFrame::new(screen.width, screen.height, buffer).save_png(diff)?;
+
+    Ok(false)
+}
@@ -0,0 +1,404 @@
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+use crate::{cartridge::Cartridge, model::Model, runner::{Runner, StopCondition, StopReason}};
+
+// https://github.com/Gekkio/mooneye-test-suite
+// Passing tests load the Fibonacci numbers in B, C, D, E, H and L before
+// executing LD B,B, failing ones load 0x42
+#[cfg(test)]
+const FIBONACCI: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+#[cfg(test)]
+fn assert_mooneye(rom: &str) {
+    assert_mooneye_model(rom, Model::DMG);
+}
+
+#[cfg(test)]
+fn assert_mooneye_model(rom: &str, model: Model) {
+    // The ROMs are built from the mooneye-test-suite, which is not committed so
+    // the tests only run with --ignored
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets/mooneye-test-suite").join(rom);
+    assert!(path.exists(), "{} not found", path.display());
+
+    let cartridge = Cartridge::new(path).unwrap();
+    let mut runner = Runner::new(cartridge, 60 * 60).model(model).stop_when(StopCondition::Breakpoint);
+    let result = runner.run().unwrap();
+    assert_eq!(result.reason, StopReason::Matched(StopCondition::Breakpoint), "{} did not finish", rom);
+
+    let regs = &runner.gameboy.cpu.regs;
+    assert_eq!([regs.b, regs.c, regs.d, regs.e, regs.h, regs.l], FIBONACCI, "{} failed", rom);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_add_sp_e_timing() {
+    assert_mooneye("acceptance/add_sp_e_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_div_dmgabcmgb() {
+    assert_mooneye("acceptance/boot_div-dmgABCmgb.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_div_dmg0() {
+    assert_mooneye_model("acceptance/boot_div-dmg0.gb", Model::DMG0);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_div_s() {
+    assert_mooneye_model("acceptance/boot_div-S.gb", Model::SGB);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_div_cgbabcde() {
+    assert_mooneye_model("misc/boot_div-cgbABCDE.gb", Model::CGB);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_hwio_dmgabcmgb() {
+    assert_mooneye("acceptance/boot_hwio-dmgABCmgb.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_dmgabc() {
+    assert_mooneye("acceptance/boot_regs-dmgABC.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_dmg0() {
+    assert_mooneye_model("acceptance/boot_regs-dmg0.gb", Model::DMG0);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_mgb() {
+    assert_mooneye_model("acceptance/boot_regs-mgb.gb", Model::MGB);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_sgb() {
+    assert_mooneye_model("acceptance/boot_regs-sgb.gb", Model::SGB);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_sgb2() {
+    assert_mooneye_model("acceptance/boot_regs-sgb2.gb", Model::SGB2);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_boot_regs_cgb() {
+    assert_mooneye_model("misc/boot_regs-cgb.gb", Model::CGB);
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_call_cc_timing() {
+    assert_mooneye("acceptance/call_cc_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_call_cc_timing2() {
+    assert_mooneye("acceptance/call_cc_timing2.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_call_timing() {
+    assert_mooneye("acceptance/call_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_call_timing2() {
+    assert_mooneye("acceptance/call_timing2.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_di_timing_gs() {
+    assert_mooneye("acceptance/di_timing-GS.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_div_timing() {
+    assert_mooneye("acceptance/div_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_ei_sequence() {
+    assert_mooneye("acceptance/ei_sequence.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_ei_timing() {
+    assert_mooneye("acceptance/ei_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_halt_ime0_ei() {
+    assert_mooneye("acceptance/halt_ime0_ei.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_halt_ime0_nointr_timing() {
+    assert_mooneye("acceptance/halt_ime0_nointr_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_halt_ime1_timing() {
+    assert_mooneye("acceptance/halt_ime1_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_halt_ime1_timing2_gs() {
+    assert_mooneye("acceptance/halt_ime1_timing2-GS.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_if_ie_registers() {
+    assert_mooneye("acceptance/if_ie_registers.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_intr_timing() {
+    assert_mooneye("acceptance/intr_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_jp_cc_timing() {
+    assert_mooneye("acceptance/jp_cc_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_jp_timing() {
+    assert_mooneye("acceptance/jp_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_ld_hl_sp_e_timing() {
+    assert_mooneye("acceptance/ld_hl_sp_e_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_restart() {
+    assert_mooneye("acceptance/oam_dma_restart.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_start() {
+    assert_mooneye("acceptance/oam_dma_start.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_timing() {
+    assert_mooneye("acceptance/oam_dma_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_pop_timing() {
+    assert_mooneye("acceptance/pop_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_push_timing() {
+    assert_mooneye("acceptance/push_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_rapid_di_ei() {
+    assert_mooneye("acceptance/rapid_di_ei.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_ret_cc_timing() {
+    assert_mooneye("acceptance/ret_cc_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_ret_timing() {
+    assert_mooneye("acceptance/ret_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_reti_intr_timing() {
+    assert_mooneye("acceptance/reti_intr_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_reti_timing() {
+    assert_mooneye("acceptance/reti_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_rst_timing() {
+    assert_mooneye("acceptance/rst_timing.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_bits_mem_oam() {
+    assert_mooneye("acceptance/bits/mem_oam.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_bits_reg_f() {
+    assert_mooneye("acceptance/bits/reg_f.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_bits_unused_hwio_gs() {
+    assert_mooneye("acceptance/bits/unused_hwio-GS.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_instr_daa() {
+    assert_mooneye("acceptance/instr/daa.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_interrupts_ie_push() {
+    assert_mooneye("acceptance/interrupts/ie_push.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_basic() {
+    assert_mooneye("acceptance/oam_dma/basic.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_reg_read() {
+    assert_mooneye("acceptance/oam_dma/reg_read.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_oam_dma_sources_gs() {
+    assert_mooneye("acceptance/oam_dma/sources-GS.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_serial_boot_sclk_align_dmgabcmgb() {
+    assert_mooneye("acceptance/serial/boot_sclk_align-dmgABCmgb.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_div_write() {
+    assert_mooneye("acceptance/timer/div_write.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_rapid_toggle() {
+    assert_mooneye("acceptance/timer/rapid_toggle.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim00() {
+    assert_mooneye("acceptance/timer/tim00.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim00_div_trigger() {
+    assert_mooneye("acceptance/timer/tim00_div_trigger.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim01() {
+    assert_mooneye("acceptance/timer/tim01.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim01_div_trigger() {
+    assert_mooneye("acceptance/timer/tim01_div_trigger.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim10() {
+    assert_mooneye("acceptance/timer/tim10.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim10_div_trigger() {
+    assert_mooneye("acceptance/timer/tim10_div_trigger.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim11() {
+    assert_mooneye("acceptance/timer/tim11.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tim11_div_trigger() {
+    assert_mooneye("acceptance/timer/tim11_div_trigger.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tima_reload() {
+    assert_mooneye("acceptance/timer/tima_reload.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tima_write_reloading() {
+    assert_mooneye("acceptance/timer/tima_write_reloading.gb");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mooneye_timer_tma_write_reloading() {
+    assert_mooneye("acceptance/timer/tma_write_reloading.gb");
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+use crate::{cartridge::Cartridge, runner::{compare_screen, Runner, StopCondition, StopReason}, GameBoyFrame};
+
+#[cfg(test)]
+fn assets() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../assets")
+}
+
+// The reference images are in assets/screenshots, on a mismatch the
+// differing pixels are written next to the other temporary files
+#[cfg(test)]
+fn assert_reference(screen: &GameBoyFrame, name: &str, reference: &str) {
+    let reference_path = assets().join("screenshots").join(reference);
+    assert!(reference_path.exists(), "{} not found", reference_path.display());
+
+    let diff = std::env::temp_dir().join(reference.replace(".png", "-diff.png"));
+    let matches = compare_screen(screen, &reference_path, &diff).unwrap();
+    assert!(matches, "{} does not match {}, differences in {}", name, reference, diff.display());
+}
+
+// Test ROMs that report their result on the screen. The ROMs come from the
+// submodules in assets and are not committed, so the tests only run with
+// --ignored
+#[cfg(test)]
+fn assert_screenshot(rom: &str, frames: u64, reference: &str) {
+    let rom_path = assets().join(rom);
+    assert!(rom_path.exists(), "{} not found", rom_path.display());
+
+    let cartridge = Cartridge::new(rom_path).unwrap();
+    // dmg-acid2 signals the end of the test with LD B,B
+    let result = Runner::new(cartridge, frames).stop_when(StopCondition::Breakpoint).run().unwrap();
+    assert_reference(&result.screen, rom, reference);
+}
+
+// ROM only cartridge running the program at 0x0150, with the data the
+// program copies at 0x0200
+#[cfg(test)]
+fn program_cartridge(name: &str, program: &[u8], data: &[u8]) -> Cartridge {
+    let mut rom = vec![0; 0x8000];
+    // NOP, JP 0x0150
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x0150..0x0150 + program.len()].copy_from_slice(program);
+    rom[0x0200..0x0200 + data.len()].copy_from_slice(data);
+
+    let path = std::env::temp_dir().join(format!("screenshot_{}_{}.gb", name, std::process::id()));
+    std::fs::write(&path, rom).unwrap();
+    let cartridge = Cartridge::new(path.clone()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    cartridge
+}
+
+// Background columns in the 4 shades, the window in the bottom right
+// corner and 3 objects: black through OBP0, white through OBP1 and one
+// behind the background that only shows over its color 0
+#[test]
+fn dmg_layers() {
+    let program = [
+        0xF3, 0xAF, 0xE0, 0x40,                         // DI, LCD off
+        0x21, 0x10, 0x80, 0x11, 0x00, 0x02, 0x06, 0x30, // Tiles 1-3 from 0x0200 to 0x8010
+        0x1A, 0x13, 0x22, 0x05, 0x20, 0xFA,
+        0x21, 0x00, 0x98,                               // Map 0x9800 with the column modulo 4
+        0x7D, 0xE6, 0x03, 0x22, 0x7C, 0xFE, 0x9C, 0x20, 0xF7,
+        0x21, 0x00, 0xFE, 0x11, 0x30, 0x02, 0x06, 0x0C, // Objects from 0x0230 to OAM
+        0x1A, 0x13, 0x22, 0x05, 0x20, 0xFA,
+        0x3E, 0xE4, 0xE0, 0x47,                         // BGP
+        0x3E, 0xE4, 0xE0, 0x48,                         // OBP0
+        0x3E, 0x1B, 0xE0, 0x49,                         // OBP1, reversed
+        0x3E, 0x48, 0xE0, 0x4A,                         // WY = 72
+        0x3E, 0x57, 0xE0, 0x4B,                         // WX = 87
+        0x3E, 0xF3, 0xE0, 0x40,                         // LCD on with the window and objects
+        0xF0, 0x44, 0xB7, 0x20, 0xFB,                   // Wait for line 0
+        0xF0, 0x44, 0xFE, 0x90, 0x20, 0xFA,             // Then for line 144
+        0x40, 0x18, 0xFE,                               // LD B,B, JR -2
+    ];
+    let mut data = Vec::new();
+    for row in [[0xFF, 0x00], [0x00, 0xFF], [0xFF, 0xFF]] {
+        data.extend(row.repeat(8));
+    }
+    data.resize(0x30, 0);
+    data.extend([32, 12, 3, 0x00, 32, 28, 3, 0x10, 64, 36, 1, 0x80]);
+
+    let cartridge = program_cartridge("dmg_layers", &program, &data);
+    let result = Runner::new(cartridge, 10).skip_boot().stop_when(StopCondition::Breakpoint).run().unwrap();
+    assert_eq!(result.reason, StopReason::Matched(StopCondition::Breakpoint));
+    assert_reference(&result.screen, "dmg_layers", "dmg-layers.png");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn dmg_acid2() {
+    assert_screenshot("dmg-acid2/dmg-acid2.gb", 600, "dmg-acid2.png");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn instr_timing() {
+    assert_screenshot("gb-test-roms/instr_timing/instr_timing.gb", 600, "instr_timing.png");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mem_timing() {
+    assert_screenshot("gb-test-roms/mem_timing/mem_timing.gb", 1200, "mem_timing.png");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn mem_timing_2() {
+    assert_screenshot("gb-test-roms/mem_timing-2/mem_timing.gb", 1200, "mem_timing-2.png");
+}
+
+#[test]
+#[ignore = "needs test ROMs"]
+fn halt_bug() {
+    assert_screenshot("gb-test-roms/halt_bug.gb", 600, "halt_bug.png");
+}
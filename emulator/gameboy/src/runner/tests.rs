@@ -0,0 +1,81 @@
+#[cfg(test)]
+use crate::{gameboy::GameBoy, mmu::MMU, runner::{compare_screen, Runner, StopCondition, StopReason}, ColoredPixel, GameBoyFrame};
+
+#[cfg(test)]
+fn runner(max_frames: u64) -> Runner {
+    Runner { gameboy: GameBoy::new(None), conditions: Vec::new(), max_frames }
+}
+
+#[test]
+fn stop_at_frame_limit() {
+    let result = runner(2).stop_when(StopCondition::Breakpoint).run().unwrap();
+    assert_eq!(result.reason, StopReason::FrameLimit);
+    assert_eq!(result.frames, 2);
+}
+
+#[test]
+fn stop_at_infinite_loop() {
+    let mut runner = runner(1).stop_when(StopCondition::InfiniteLoop);
+    // NOP, JR -2
+    for (offset, byte) in [0x00, 0x18, 0xFE].iter().enumerate() {
+        MMU::write_byte(&mut runner.gameboy, 0xC000 + offset as u16, *byte);
+    }
+    runner.gameboy.cpu.pc = 0xC000;
+
+    let result = runner.run().unwrap();
+    assert_eq!(result.reason, StopReason::Matched(StopCondition::InfiniteLoop));
+    assert_eq!(runner.gameboy.cpu.pc, 0xC001);
+}
+
+#[test]
+fn stop_at_breakpoint_and_pc() {
+    let mut runner = runner(1).stop_when(StopCondition::Breakpoint).stop_when(StopCondition::PcReached(0xC003));
+    // NOP, NOP, LD B,B
+    for (offset, byte) in [0x00, 0x00, 0x40, 0x00].iter().enumerate() {
+        MMU::write_byte(&mut runner.gameboy, 0xC000 + offset as u16, *byte);
+    }
+    runner.gameboy.cpu.pc = 0xC000;
+
+    assert_eq!(runner.run().unwrap().reason, StopReason::Matched(StopCondition::Breakpoint));
+    assert_eq!(runner.gameboy.cpu.pc, 0xC002);
+
+    runner.gameboy.tick().unwrap();
+    assert_eq!(runner.run().unwrap().reason, StopReason::Matched(StopCondition::PcReached(0xC003)));
+}
+
+#[test]
+fn stop_on_serial_output() {
+    let mut runner = runner(1).stop_when(StopCondition::SerialContains(String::from("OK")));
+    // LD A,'O'; LDH (01),A; LD A,81; LDH (02),A; LD A,'K'; LDH (01),A; LD A,81; LDH (02),A
+    let program = [0x3E, b'O', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x3E, b'K', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE];
+    for (offset, byte) in program.iter().enumerate() {
+        MMU::write_byte(&mut runner.gameboy, 0xC000 + offset as u16, *byte);
+    }
+    runner.gameboy.cpu.pc = 0xC000;
+
+    let result = runner.run().unwrap();
+    assert_eq!(result.reason, StopReason::Matched(StopCondition::SerialContains(String::from("OK"))));
+    assert_eq!(result.serial, b"OK");
+}
+
+#[test]
+fn screen_comparison() {
+    let dir = std::env::temp_dir();
+    let reference = dir.join(format!("screen_{}.png", std::process::id()));
+    let diff = dir.join(format!("screen_{}-diff.png", std::process::id()));
+
+    let pixels = [ColoredPixel::White, ColoredPixel::LightGray, ColoredPixel::DarkGray, ColoredPixel::Black];
+    let mut screen = GameBoyFrame::new(4, 2, pixels.iter().chain(pixels.iter()).cloned().collect());
+    screen.save_png(&reference).unwrap();
+    assert!(compare_screen(&screen, &reference, &diff).unwrap());
+    assert!(!diff.exists());
+
+    screen.buffer[5] = ColoredPixel::White;
+    assert!(!compare_screen(&screen, &reference, &diff).unwrap());
+    let differences = GameBoyFrame::load_png(&diff).unwrap();
+    assert_eq!(differences.buffer.iter().filter(|p| **p == ColoredPixel::Black).count(), 1);
+    assert_eq!(differences.buffer[5], ColoredPixel::Black);
+
+    std::fs::remove_file(reference).unwrap();
+    std::fs::remove_file(diff).unwrap();
+}
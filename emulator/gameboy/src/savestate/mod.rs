@@ -0,0 +1,165 @@
+use crate::{error::GameBoyError, gameboy::GameBoy, cartridge::Cartridge, cpu::cpu::CPU, io::io::IO, mmu::MMU, ppu::PPU};
+
+mod tests;
+
+// Every save state starts with this header, states from another version
+// or another game are refused
+const MAGIC: &[u8; 4] = b"GBRS";
+const VERSION: u8 = 8;
+const HEADER_SIZE: usize = 13;
+
+pub(crate) struct StateWriter {
+    data: Vec<u8>,
+}
+
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub(crate) fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub(crate) fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        StateReader { data, position: 0 }
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, GameBoyError> {
+        let mut byte = [0; 1];
+        self.read_bytes(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, GameBoyError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, GameBoyError> {
+        let mut bytes = [0; 2];
+        self.read_bytes(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, GameBoyError> {
+        let mut bytes = [0; 4];
+        self.read_bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn read_vec(&mut self, length: usize) -> Result<Vec<u8>, GameBoyError> {
+        if length > self.data.len() - self.position {
+            return Err(GameBoyError::InvalidSaveState(String::from("truncated")));
+        }
+        let mut bytes = vec![0; length];
+        self.read_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    pub(crate) fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), GameBoyError> {
+        let end = self.position + bytes.len();
+        if end > self.data.len() {
+            return Err(GameBoyError::InvalidSaveState(String::from("truncated")));
+        }
+        bytes.copy_from_slice(&self.data[self.position..end]);
+        self.position = end;
+        Ok(())
+    }
+}
+
+// Header: magic, version, ROM checksum and payload length
+pub(crate) fn save(gb: &GameBoy) -> Vec<u8> {
+    let mut payload = StateWriter::new();
+    gb.cpu.save_state(&mut payload);
+    gb.mmu.save_state(&mut payload);
+    gb.ppu.save_state(&mut payload);
+    gb.io.save_state(&mut payload);
+
+    let mut state = StateWriter::new();
+    state.write_bytes(MAGIC);
+    state.write_u8(VERSION);
+    state.write_u32(rom_checksum(gb));
+    state.write_u32(payload.data.len() as u32);
+    state.write_bytes(&payload.data);
+    state.data
+}
+
+pub(crate) fn load(gb: &mut GameBoy, data: &[u8]) -> Result<(), GameBoyError> {
+    let mut state = StateReader::new(data);
+
+    let mut magic = [0; 4];
+    state.read_bytes(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(GameBoyError::InvalidSaveState(String::from("wrong header")));
+    }
+    let version = state.read_u8()?;
+    if version != VERSION {
+        return Err(GameBoyError::InvalidSaveState(format!("unsupported version {}", version)));
+    }
+    if state.read_u32()? != rom_checksum(gb) {
+        return Err(GameBoyError::SaveStateMismatch);
+    }
+    let length = state.read_u32()? as usize;
+    if data.len() != HEADER_SIZE + length {
+        return Err(GameBoyError::InvalidSaveState(String::from("corrupted")));
+    }
+
+    // The payload is read into new components first, so a bad state never
+    // leaves the GameBoy half loaded. It is read again into the GameBoy,
+    // which keeps what is not part of the state such as the pressed buttons
+    let payload = &data[HEADER_SIZE..];
+    load_payload(&mut CPU::new(), &mut MMU::new(None), &mut PPU::new(), &mut IO::new(), payload)?;
+    load_payload(&mut gb.cpu, &mut gb.mmu, &mut gb.ppu, &mut gb.io, payload)?;
+
+    PPU::decode_tile_set(gb);
+
+    Ok(())
+}
+
+// Every byte has to be read by the components
+fn load_payload(cpu: &mut CPU, mmu: &mut MMU, ppu: &mut PPU, io: &mut IO, payload: &[u8]) -> Result<(), GameBoyError> {
+    let mut state = StateReader::new(payload);
+    cpu.load_state(&mut state)?;
+    mmu.load_state(&mut state)?;
+    ppu.load_state(&mut state)?;
+    io.load_state(&mut state)?;
+    if !state.is_exhausted() {
+        return Err(GameBoyError::InvalidSaveState(String::from("corrupted")));
+    }
+    Ok(())
+}
+
+pub(crate) fn rom_checksum(gb: &GameBoy) -> u32 {
+    gb.cartridge.as_ref().map_or(0, Cartridge::checksum)
+}
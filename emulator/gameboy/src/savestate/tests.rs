@@ -0,0 +1,108 @@
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+use crate::{cartridge::Cartridge, gameboy::GameBoy, mmu::MMU, savestate};
+
+#[cfg(test)]
+fn test_cartridge(name: &str, fill: u8) -> Cartridge {
+    let path: PathBuf = std::env::temp_dir().join(format!("savestate_{}_{}.gb", name, std::process::id()));
+    let mut rom = vec![fill; 0x8000];
+    // ROM only cartridge without title
+    rom[0x0134..=0x0147].fill(0);
+    std::fs::write(&path, rom).unwrap();
+    let cartridge = Cartridge::new(path.clone()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    cartridge
+}
+
+#[test]
+fn save_load_roundtrip() {
+    let mut gb = GameBoy::new(None);
+    for _ in 0..10_000 {
+        gb.tick().unwrap();
+    }
+
+    let state = savestate::save(&gb);
+    for _ in 0..10_000 {
+        gb.tick().unwrap();
+    }
+    let expected = savestate::save(&gb);
+    let frame = gb.frame();
+
+    // Running again from the loaded state reaches the same point
+    savestate::load(&mut gb, &state).unwrap();
+    assert_eq!(savestate::save(&gb), state);
+    for _ in 0..10_000 {
+        gb.tick().unwrap();
+    }
+    assert_eq!(savestate::save(&gb), expected);
+    assert_eq!(gb.frame(), frame);
+}
+
+#[test]
+fn load_restores_tile_set() {
+    let mut gb = GameBoy::new(None);
+    MMU::write_byte(&mut gb, 0x8000, 0xFF);
+    let state = savestate::save(&gb);
+
+    let mut other = GameBoy::new(None);
+    savestate::load(&mut other, &state).unwrap();
+    assert_eq!(other.ppu.tile_set[0][0][0] as u8, 1);
+}
+
+#[test]
+fn refuse_other_game() {
+    let gb = GameBoy::new(Some(test_cartridge("first", 0x00)));
+    let state = savestate::save(&gb);
+
+    let mut other = GameBoy::new(Some(test_cartridge("second", 0xFF)));
+    assert!(savestate::load(&mut other, &state).is_err());
+
+    let mut same = GameBoy::new(Some(test_cartridge("same", 0x00)));
+    assert!(savestate::load(&mut same, &state).is_ok());
+}
+
+#[test]
+fn refuse_corrupted_state() {
+    let mut gb = GameBoy::new(None);
+    let state = savestate::save(&gb);
+
+    assert!(savestate::load(&mut gb, &state[..state.len() - 1]).is_err());
+    assert!(savestate::load(&mut gb, b"not a save state").is_err());
+
+    // Bytes left after every component is loaded, or missing in the middle
+    // of them, with a length that matches
+    let mut longer = state.clone();
+    longer.push(0);
+    let length = u32::from_le_bytes(longer[9..13].try_into().unwrap()) + 1;
+    longer[9..13].copy_from_slice(&length.to_le_bytes());
+    assert!(savestate::load(&mut gb, &longer).is_err());
+    let mut shorter = state[..state.len() - 1].to_vec();
+    shorter[9..13].copy_from_slice(&(length - 2).to_le_bytes());
+    assert!(savestate::load(&mut gb, &shorter).is_err());
+
+    let mut wrong_version = state.clone();
+    wrong_version[4] += 1;
+    assert!(savestate::load(&mut gb, &wrong_version).is_err());
+}
+
+#[test]
+fn corrupted_payload_leaves_the_gameboy_untouched() {
+    let mut gb = GameBoy::new(None);
+    let mut state = savestate::save(&gb);
+    for _ in 0..10_000 {
+        gb.tick().unwrap();
+    }
+    MMU::write_byte(&mut gb, 0xC000, 0x42);
+    let before = savestate::save(&gb);
+
+    // A valid header and length, but a byte left after every component
+    state.push(0);
+    let length = u32::from_le_bytes(state[9..13].try_into().unwrap()) + 1;
+    state[9..13].copy_from_slice(&length.to_le_bytes());
+    assert!(savestate::load(&mut gb, &state).is_err());
+
+    assert_eq!(savestate::save(&gb), before);
+    assert_eq!(MMU::read_byte(&gb, 0xC000), 0x42);
+}
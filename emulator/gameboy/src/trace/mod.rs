@@ -0,0 +1,90 @@
+use std::{collections::VecDeque, fs::File, io::{BufWriter, Write}, ops::RangeInclusive, path::Path};
+
+use crate::{cartridge::Cartridge, error::GameBoyError, gameboy::GameBoy, mmu::Address};
+
+mod tests;
+
+// Where the trace lines go, one per executed instruction in the format of
+// https://github.com/robert/gameboy-doctor
+pub enum TraceSink {
+    File(BufWriter<File>),
+    // Keeps only the newest lines, to see what led to a crash
+    RingBuffer { lines: VecDeque<String>, capacity: usize },
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+impl TraceSink {
+    pub fn file(path: &Path) -> Result<Self, GameBoyError> {
+        Ok(TraceSink::File(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn ring_buffer(capacity: usize) -> Self {
+        TraceSink::RingBuffer { lines: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn callback(callback: impl FnMut(&str) + 'static) -> Self {
+        TraceSink::Callback(Box::new(callback))
+    }
+
+    fn write(&mut self, line: &str) -> Result<(), GameBoyError> {
+        match self {
+            TraceSink::File(file) => writeln!(file, "{}", line)?,
+            TraceSink::RingBuffer { lines, capacity } => {
+                if lines.len() == *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line.to_string());
+            },
+            TraceSink::Callback(callback) => callback(line),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), GameBoyError> {
+        if let TraceSink::File(file) = self {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// Only instructions matching every filter are traced
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub pc_range: Option<RangeInclusive<Address>>,
+    // ROM bank the PC is in, RAM is never in a bank
+    pub bank: Option<usize>,
+}
+
+impl TraceFilter {
+    fn matches(&self, gb: &GameBoy) -> bool {
+        let pc = gb.cpu.pc;
+        let in_range = self.pc_range.as_ref().is_none_or(|range| range.contains(&pc));
+        let in_bank = self.bank.is_none_or(|bank| Cartridge::rom_bank(pc) == Some(bank));
+        in_range && in_bank
+    }
+}
+
+pub(crate) struct Tracer {
+    pub(crate) sink: TraceSink,
+    filter: TraceFilter,
+}
+
+impl Tracer {
+    pub(crate) fn new(sink: TraceSink, filter: TraceFilter) -> Self {
+        Tracer { sink, filter }
+    }
+
+    // Called by the CPU with the state before executing every instruction
+    pub(crate) fn trace(gb: &mut GameBoy) -> Result<(), GameBoyError> {
+        let line = match &gb.tracer {
+            Some(tracer) if tracer.filter.matches(gb) => gb.to_string(),
+            _ => return Ok(()),
+        };
+
+        if let Some(tracer) = &mut gb.tracer {
+            tracer.sink.write(&line)?;
+        }
+        Ok(())
+    }
+}
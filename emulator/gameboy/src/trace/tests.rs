@@ -0,0 +1,82 @@
+#[cfg(test)]
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+#[cfg(test)]
+use crate::{cartridge::Cartridge, Emulation, mmu::MMU};
+
+#[cfg(test)]
+use super::{TraceFilter, TraceSink};
+
+// NOP, JP 0150 and at 0150: INC A, JR -3
+#[cfg(test)]
+fn doctor_emulation(name: &str) -> Emulation {
+    let path: PathBuf = std::env::temp_dir().join(format!("trace_{}_{}.gb", name, std::process::id()));
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x0150..0x0153].copy_from_slice(&[0x3C, 0x18, 0xFD]);
+    std::fs::write(&path, rom).unwrap();
+    let cartridge = Cartridge::new(path.clone()).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let mut emu = Emulation::new(Some(cartridge));
+    emu.gameboy_doctor();
+    emu
+}
+
+#[cfg(test)]
+fn trace_lines(emu: &mut Emulation) -> Vec<String> {
+    match emu.stop_trace().unwrap() {
+        Some(TraceSink::RingBuffer { lines, .. }) => lines.into_iter().collect(),
+        _ => panic!("Expected a ring buffer"),
+    }
+}
+
+#[test]
+fn gameboy_doctor_format() {
+    let mut emu = doctor_emulation("format");
+    emu.start_trace(TraceSink::ring_buffer(10), TraceFilter::default());
+    for _ in 0..3 {
+        emu.step_instruction().unwrap();
+    }
+
+    assert_eq!(trace_lines(&mut emu), vec![
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01",
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,00",
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:3C,18,FD,00",
+    ]);
+    assert_eq!(MMU::read_byte(&emu.gameboy, 0xFF44), 0x90);
+}
+
+#[test]
+fn ring_buffer_keeps_newest_lines() {
+    let mut emu = doctor_emulation("ring");
+    emu.start_trace(TraceSink::ring_buffer(2), TraceFilter::default());
+    for _ in 0..5 {
+        emu.step_instruction().unwrap();
+    }
+
+    let lines = trace_lines(&mut emu);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("A:02") && lines[0].ends_with("PC:0151 PCMEM:18,FD,00,00"));
+    assert!(lines[1].starts_with("A:02") && lines[1].ends_with("PC:0150 PCMEM:3C,18,FD,00"));
+}
+
+#[test]
+fn filter_by_pc_range_and_bank() {
+    let mut emu = doctor_emulation("filter");
+    let traced = Rc::new(RefCell::new(Vec::new()));
+    let sink = traced.clone();
+    let filter = TraceFilter { pc_range: Some(0x0150..=0x0150), bank: Some(0) };
+    emu.start_trace(TraceSink::callback(move |line| sink.borrow_mut().push(line.to_string())), filter);
+    for _ in 0..6 {
+        emu.step_instruction().unwrap();
+    }
+    assert_eq!(traced.borrow().len(), 2);
+    assert!(traced.borrow().iter().all(|line| line.contains("PC:0150")));
+
+    emu.stop_trace().unwrap();
+    emu.start_trace(TraceSink::callback(|_| panic!("Bank 1 is not executed")), TraceFilter { pc_range: None, bank: Some(1) });
+    for _ in 0..6 {
+        emu.step_instruction().unwrap();
+    }
+}
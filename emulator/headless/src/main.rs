@@ -0,0 +1,165 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use gameboy::{cartridge::Cartridge, disassembler::{disassemble_bank, rom_banks}, link::printer::GameBoyPrinter, model::Model, runner::{Runner, StopCondition, StopReason}};
+
+// Exit codes for scripts
+const EXIT_PASSED: u8 = 0;
+const EXIT_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+/// Runs a ROM without a window until a stop condition is met
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required = true)]
+    cartridge: Option<PathBuf>,
+    /// Maximum number of frames to run
+    #[arg(long, value_name = "N", default_value_t = 3600)]
+    frames: u64,
+    /// Stop when the serial output contains TEXT
+    #[arg(long, value_name = "TEXT")]
+    until_serial: Vec<String>,
+    /// Stop and fail when the serial output contains TEXT
+    #[arg(long, value_name = "TEXT")]
+    fail_serial: Vec<String>,
+    /// Stop when the program counter reaches ADDRESS (hexadecimal)
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    until_pc: Option<u16>,
+    /// Stop on an infinite JR -2 loop or a CPU locked by an illegal opcode
+    #[arg(long)]
+    until_loop: bool,
+    /// Stop on a LD B,B breakpoint
+    #[arg(long)]
+    until_breakpoint: bool,
+    /// Stop with an error on illegal opcodes instead of locking up the CPU
+    #[arg(long)]
+    strict: bool,
+    /// Start the cartridge directly with the state the boot ROM leaves
+    #[arg(long)]
+    skip_boot: bool,
+    /// Emulate DMG0, DMG, MGB, SGB, SGB2 or CGB, only the DMG runs its boot ROM
+    #[arg(long, value_name = "MODEL", default_value = "DMG")]
+    model: Model,
+    /// Pass if the frame limit is reached before a stop condition
+    #[arg(long)]
+    timeout_passes: bool,
+    /// Save the last frame as PNG
+    #[arg(long, value_name = "FILE")]
+    screenshot: Option<PathBuf>,
+    /// Write the serial output to FILE
+    #[arg(long, value_name = "FILE")]
+    serial_log: Option<PathBuf>,
+    /// Plug a Game Boy Printer and save the printed pages as PNG in DIR
+    #[arg(long, value_name = "DIR")]
+    printer: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the disassembly of a ROM, bank by bank
+    Disasm {
+        rom: PathBuf,
+        /// Only disassemble this bank
+        #[arg(long, value_name = "N")]
+        bank: Option<usize>,
+    },
+}
+
+fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    let result = match &args.command {
+        Some(Command::Disasm { rom, bank }) => disasm(rom, *bank),
+        None => run(&args),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(args: &Cli) -> Result<u8, std::io::Error> {
+    let cartridge = Cartridge::new(args.cartridge.clone().unwrap_or_default())?;
+    let mut runner = Runner::new(cartridge, args.frames).model(args.model).strict(args.strict);
+    if args.skip_boot {
+        runner = runner.skip_boot();
+    }
+
+    for text in args.until_serial.iter().chain(&args.fail_serial) {
+        runner = runner.stop_when(StopCondition::SerialContains(text.clone()));
+    }
+    if let Some(address) = args.until_pc {
+        runner = runner.stop_when(StopCondition::PcReached(address));
+    }
+    if args.until_loop {
+        runner = runner.stop_when(StopCondition::InfiniteLoop);
+    }
+    if args.until_breakpoint {
+        runner = runner.stop_when(StopCondition::Breakpoint);
+    }
+    // Without stop conditions running the frames is all that is asked
+    let waits = !args.until_serial.is_empty() || !args.fail_serial.is_empty()
+        || args.until_pc.is_some() || args.until_loop || args.until_breakpoint;
+    let mut printer = None;
+    if let Some(dir) = &args.printer {
+        std::fs::create_dir_all(dir)?;
+        let connected = GameBoyPrinter::with_output_dir(dir.clone());
+        runner.connect_link_cable(Box::new(connected.clone()));
+        printer = Some(connected);
+    }
+
+    let result = runner.run()?;
+
+    if let Some(error) = printer.and_then(|printer| printer.take_save_errors().into_iter().next()) {
+        return Err(error);
+    }
+
+    if let Some(path) = &args.screenshot {
+        result.screen.save_png(path)?;
+    }
+    if let Some(path) = &args.serial_log {
+        std::fs::write(path, &result.serial)?;
+    }
+
+    let code = match &result.reason {
+        StopReason::Matched(StopCondition::SerialContains(text)) if args.fail_serial.contains(text) => EXIT_FAILED,
+        StopReason::Matched(_) => EXIT_PASSED,
+        StopReason::FrameLimit if waits && !args.timeout_passes => EXIT_FAILED,
+        StopReason::FrameLimit => EXIT_PASSED,
+    };
+    println!("Stopped after {} frames: {:?}", result.frames, result.reason);
+
+    Ok(code)
+}
+
+fn disasm(rom: &PathBuf, bank: Option<usize>) -> Result<u8, std::io::Error> {
+    let rom = std::fs::read(rom)?;
+    let banks = match bank {
+        Some(bank) if bank >= rom_banks(&rom) => {
+            eprintln!("The ROM only has {} banks", rom_banks(&rom));
+            return Ok(EXIT_ERROR);
+        },
+        Some(bank) => bank..bank + 1,
+        None => 0..rom_banks(&rom),
+    };
+
+    for bank in banks {
+        println!("; Bank {}", bank);
+        for line in disassemble_bank(&rom, bank) {
+            println!("{}", line);
+        }
+    }
+    Ok(EXIT_PASSED)
+}